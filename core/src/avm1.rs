@@ -1,9 +1,9 @@
 #[cfg(test)]
 #[macro_use]
-mod test_utils;
+pub(crate) mod test_utils;
 
 #[macro_use]
-mod function;
+pub(crate) mod function;
 #[macro_use]
 mod property_decl;
 
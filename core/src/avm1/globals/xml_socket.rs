@@ -5,10 +5,17 @@ use crate::avm1::{property_decl::Declaration, ScriptObject};
 use crate::avm1::{Activation, Error, Executable, ExecutionReason, TObject, Value};
 use crate::context::{GcContext, UpdateContext};
 use crate::display_object::TDisplayObject;
-use crate::socket::SocketHandle;
+use crate::socket::{validate_port, CloseReason, SocketHandle};
 use crate::string::AvmString;
 use gc_arena::{Collect, Gc};
-use std::cell::{Cell, RefCell, RefMut};
+use std::cell::{Cell, Ref, RefCell, RefMut};
+
+/// Default limit for a single message that has not been terminated yet.
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// Returned by [`XmlSocket::set_delimiter`] for an empty delimiter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EmptyDelimiter;
 
 #[derive(Clone, Debug, Collect)]
 #[collect(require_static)]
@@ -17,6 +24,12 @@ struct XmlSocketData {
     /// Connection timeout in milliseconds.
     timeout: Cell<u32>,
     read_buffer: RefCell<Vec<u8>>,
+    /// Maximum size of a message in bytes, including any data buffered from earlier packets.
+    max_message_size: Cell<usize>,
+    /// The byte sequence that ends each received message.
+    delimiter: RefCell<Vec<u8>>,
+    /// Ruffle-specific reason of the last connection failure, exposed as `lastError`.
+    last_error: Cell<Option<&'static str>>,
 }
 
 #[derive(Clone, Debug, Collect)]
@@ -32,6 +45,10 @@ impl<'gc> XmlSocket<'gc> {
         self.0.handle.replace(Some(handle))
     }
 
+    pub fn take_handle(&self) -> Option<SocketHandle> {
+        self.0.handle.take()
+    }
+
     pub fn timeout(&self) -> u32 {
         self.0.timeout.get()
     }
@@ -45,6 +62,37 @@ impl<'gc> XmlSocket<'gc> {
         self.0.read_buffer.borrow_mut()
     }
 
+    pub fn max_message_size(&self) -> usize {
+        self.0.max_message_size.get()
+    }
+
+    pub fn set_max_message_size(&self, max_message_size: usize) {
+        self.0.max_message_size.set(max_message_size);
+    }
+
+    pub fn delimiter(&self) -> Ref<'_, [u8]> {
+        Ref::map(self.0.delimiter.borrow(), Vec::as_slice)
+    }
+
+    /// Sets the byte sequence that ends each received message, instead of the null byte Flash uses.
+    ///
+    /// An empty delimiter couldn't end any message, so it's rejected and the current one is kept.
+    pub fn set_delimiter(&self, delimiter: Vec<u8>) -> Result<(), EmptyDelimiter> {
+        if delimiter.is_empty() {
+            return Err(EmptyDelimiter);
+        }
+        *self.0.delimiter.borrow_mut() = delimiter;
+        Ok(())
+    }
+
+    pub fn last_error(&self) -> Option<&'static str> {
+        self.0.last_error.get()
+    }
+
+    pub fn set_last_error(&self, last_error: Option<&'static str>) {
+        self.0.last_error.set(last_error);
+    }
+
     pub fn cast(value: Value<'gc>) -> Option<Self> {
         if let Value::Object(object) = value {
             if let NativeObject::XmlSocket(xml_socket) = object.native() {
@@ -57,6 +105,7 @@ impl<'gc> XmlSocket<'gc> {
 
 const PROTO_DECLS: &[Declaration] = declare_properties! {
     "timeout" => property(get_timeout, set_timeout);
+    "lastError" => property(get_last_error; DONT_ENUM | DONT_DELETE);
     "close" => method(close);
     "connect" => method(connect);
     "send" => method(send);
@@ -78,6 +127,18 @@ fn get_timeout<'gc>(
     }
 }
 
+fn get_last_error<'gc>(
+    _activation: &mut Activation<'_, 'gc>,
+    this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<Value<'gc>, Error<'gc>> {
+    if let Some(last_error) = XmlSocket::cast(this.into()).and_then(|x| x.last_error()) {
+        Ok(AvmString::from(last_error).into())
+    } else {
+        Ok(Value::Undefined)
+    }
+}
+
 fn set_timeout<'gc>(
     activation: &mut Activation<'_, 'gc>,
     this: Object<'gc>,
@@ -102,7 +163,8 @@ pub fn close<'gc>(
 ) -> Result<Value<'gc>, Error<'gc>> {
     if let Some(xml_socket) = XmlSocket::cast(this.into()) {
         if let Some(handle) = xml_socket.handle() {
-            activation.context.sockets.close(handle)
+            activation.context.sockets.close(handle);
+            xml_socket.set_last_error(Some(CloseReason::Local.as_str()));
         }
     }
 
@@ -115,9 +177,11 @@ pub fn connect<'gc>(
     args: &[Value<'gc>],
 ) -> Result<Value<'gc>, Error<'gc>> {
     if XmlSocket::cast(this.into()).is_some() {
+        // NOTE: As documented, passing `null` connects to the host the movie came from.
         let host = args
             .get(0)
             .copied()
+            .filter(|host| !matches!(host, Value::Undefined | Value::Null))
             .unwrap_or_else(|| {
                 let movie = activation.base_clip().movie();
 
@@ -138,13 +202,17 @@ pub fn connect<'gc>(
         let port = args
             .get(1)
             .unwrap_or(&Value::Undefined)
-            .coerce_to_u16(activation)?;
+            .coerce_to_f64(activation)?;
 
         let UpdateContext {
             sockets, navigator, ..
         } = &mut activation.context;
 
-        sockets.connect_avm1(*navigator, this, host.to_utf8_lossy().into_owned(), port);
+        let host = host.to_utf8_lossy().into_owned();
+        match validate_port(port) {
+            Some(port) => sockets.connect_avm1(*navigator, this, host, port),
+            None => sockets.reject_avm1(this, host),
+        }
 
         // NOTE: At this point we do not know if the connection will succeed
         //       because connecting is an asynchronous process, so we just return true.
@@ -161,17 +229,14 @@ pub fn send<'gc>(
 ) -> Result<Value<'gc>, Error<'gc>> {
     if let Some(xml_socket) = XmlSocket::cast(this.into()) {
         if let Some(handle) = xml_socket.handle() {
-            let mut data = args
+            let message = args
                 .get(0)
                 .unwrap_or(&Value::Undefined)
                 .coerce_to_string(activation)?
-                .to_string()
-                .into_bytes();
-
-            // The string needs to end with a null byte.
-            data.push(0);
+                .to_string();
 
-            activation.context.sockets.send(handle, data);
+            // NOTE: Flash silently drops messages sent over a closed XMLSocket.
+            let _ = activation.context.sockets.send_string(handle, &message);
         }
     }
 
@@ -238,6 +303,9 @@ pub fn constructor<'gc>(
             // Default timeout is 20_000 milliseconds (20 seconds)
             timeout: Cell::new(20000),
             read_buffer: RefCell::new(Vec::new()),
+            max_message_size: Cell::new(DEFAULT_MAX_MESSAGE_SIZE),
+            delimiter: RefCell::new(vec![b'\0']),
+            last_error: Cell::new(None),
         },
     ));
 
@@ -3,15 +3,27 @@ use crate::avm1::{
     error::Error,
     Object,
 };
+use crate::backend::navigator::{NavigatorBackend, NullNavigatorBackend};
 use crate::display_object::TDisplayObject;
 
 pub fn with_avm<F>(swf_version: u8, test: F)
 where
     F: for<'a, 'gc> FnOnce(&mut Activation<'_, 'gc>, Object<'gc>) -> Result<(), Error<'gc>>,
+{
+    with_avm_and_navigator(swf_version, NullNavigatorBackend::new(), test)
+}
+
+pub fn with_avm_and_navigator<F>(
+    swf_version: u8,
+    navigator: impl 'static + NavigatorBackend,
+    test: F,
+) where
+    F: for<'a, 'gc> FnOnce(&mut Activation<'_, 'gc>, Object<'gc>) -> Result<(), Error<'gc>>,
 {
     let movie = crate::tag_utils::SwfMovie::empty(swf_version);
     let player = crate::player::PlayerBuilder::new()
         .with_movie(movie)
+        .with_navigator(navigator)
         .build();
     let mut player = player.lock().unwrap();
     player.mutate_with_update_context(|context| {
@@ -111,11 +111,13 @@ pub struct SystemClasses<'gc> {
     pub qname: ClassObject<'gc>,
     pub mouseevent: ClassObject<'gc>,
     pub progressevent: ClassObject<'gc>,
+    pub outputprogressevent: ClassObject<'gc>,
     pub httpstatusevent: ClassObject<'gc>,
     pub textevent: ClassObject<'gc>,
     pub errorevent: ClassObject<'gc>,
     pub ioerrorevent: ClassObject<'gc>,
     pub securityerrorevent: ClassObject<'gc>,
+    pub socket: ClassObject<'gc>,
     pub transform: ClassObject<'gc>,
     pub colortransform: ClassObject<'gc>,
     pub matrix: ClassObject<'gc>,
@@ -239,11 +241,13 @@ impl<'gc> SystemClasses<'gc> {
             qname: object,
             mouseevent: object,
             progressevent: object,
+            outputprogressevent: object,
             httpstatusevent: object,
             textevent: object,
             errorevent: object,
             ioerrorevent: object,
             securityerrorevent: object,
+            socket: object,
             transform: object,
             colortransform: object,
             matrix: object,
@@ -797,6 +801,7 @@ fn load_playerglobal<'gc>(
             ("flash.events", "ErrorEvent", errorevent),
             ("flash.events", "KeyboardEvent", keyboardevent),
             ("flash.events", "ProgressEvent", progressevent),
+            ("flash.events", "OutputProgressEvent", outputprogressevent),
             ("flash.events", "HTTPStatusEvent", httpstatusevent),
             ("flash.events", "SecurityErrorEvent", securityerrorevent),
             ("flash.events", "IOErrorEvent", ioerrorevent),
@@ -819,6 +824,7 @@ fn load_playerglobal<'gc>(
             ("flash.net", "URLVariables", urlvariables),
             ("flash.net", "FileReference", filereference),
             ("flash.net", "FileFilter", filefilter),
+            ("flash.net", "Socket", socket),
             ("flash.utils", "ByteArray", bytearray),
             ("flash.utils", "Dictionary", dictionary),
             ("flash.system", "ApplicationDomain", application_domain),
@@ -7,6 +7,8 @@ package flash.net {
 
     import __ruffle__.stub_getter;
 
+    namespace ruffle = "__ruffle__";
+
     [Ruffle(InstanceAllocator)]
     public class Socket extends EventDispatcher implements IDataOutput, IDataInput {
 
@@ -36,6 +38,30 @@ package flash.net {
 
         public native function get connected():Boolean;
 
+        [API("668")] // AIR 2.0
+        public native function get localAddress():String;
+        [API("668")] // AIR 2.0
+        public native function get localPort():int;
+
+        // Ruffle-specific: why the connection was closed, or null if it wasn't.
+        ruffle native function get closeReason():String;
+
+        // Ruffle-specific: when non-zero, received data is only made available in whole records of this size.
+        ruffle native function get recordSize():uint;
+        ruffle native function set recordSize(value:uint):void;
+
+        // Ruffle-specific: disables Nagle's algorithm for the next connection. Changing it while connected does nothing.
+        ruffle native function get noDelay():Boolean;
+        ruffle native function set noDelay(value:Boolean):void;
+
+        // Ruffle-specific: reported as bytesTotal of socketData events. 0 means unknown.
+        ruffle native function get expectedTotal():uint;
+        ruffle native function set expectedTotal(value:uint):void;
+
+        // Ruffle-specific: dispatches events of a standard type (like "socketData") with a custom type instead.
+        // An empty custom type restores the standard one.
+        ruffle native function setEventType(standardType:String, customType:String):void;
+
         public native function get objectEncoding():uint;
         public native function set objectEncoding(value:uint):void;
 
@@ -1,5 +1,3 @@
-use std::rc::Rc;
-
 use crate::avm2::bytearray::{Endian, ObjectEncoding};
 use crate::avm2::error::{io_error, make_error_2008, security_error};
 pub use crate::avm2::object::socket_allocator;
@@ -7,11 +5,9 @@ use crate::avm2::parameters::ParametersExt;
 use crate::avm2::string::AvmString;
 use crate::avm2::{Activation, Error, Object, TObject, Value};
 use crate::context::UpdateContext;
+use crate::socket::{validate_port, CloseReason};
 use encoding_rs::Encoding;
 use encoding_rs::UTF_8;
-use flash_lso::amf0::read::AMF0Decoder;
-use flash_lso::amf3::read::AMF3Decoder;
-use flash_lso::types::{AMFVersion, Element};
 
 macro_rules! assert_socket_open {
     ($activation:expr, $socket:expr) => {
@@ -25,6 +21,20 @@ macro_rules! assert_socket_open {
     };
 }
 
+/// Like `assert_socket_open!`, but also fails once the write half of the socket was shut down,
+/// as nothing written after that would ever be sent.
+macro_rules! assert_socket_writable {
+    ($activation:expr, $socket:expr) => {
+        let handle = $socket
+            .handle()
+            .ok_or_else(|| invalid_socket_error($activation))?;
+
+        if !$activation.context.sockets.can_write(handle) {
+            return Err(invalid_socket_error($activation));
+        }
+    };
+}
+
 pub fn connect<'gc>(
     activation: &mut Activation<'_, 'gc>,
     this: Object<'gc>,
@@ -36,10 +46,8 @@ pub fn connect<'gc>(
     };
 
     let host = args.get_string(activation, 0)?;
-    let port = args.get_u32(activation, 1)?;
-    let port: u16 = port
-        .try_into()
-        .map_err(|_| invalid_port_number(activation))?;
+    let port = args.get_f64(activation, 1)?;
+    let port = validate_port(port).ok_or_else(|| invalid_port_number(activation))?;
 
     let UpdateContext {
         sockets, navigator, ..
@@ -90,7 +98,11 @@ pub fn close<'gc>(
 
         let UpdateContext { sockets, .. } = &mut activation.context;
 
-        sockets.close(handle)
+        sockets.close(handle);
+
+        // NOTE: Flash doesn't fire a close event here, so this is the only way to tell
+        //       a local close apart from the peer closing the connection.
+        socket.set_close_reason(Some(CloseReason::Local));
     }
 
     Ok(Value::Undefined)
@@ -162,6 +174,151 @@ pub fn get_connected<'gc>(
     Ok(Value::Bool(sockets.is_connected(handle)))
 }
 
+pub fn get_local_address<'gc>(
+    activation: &mut Activation<'_, 'gc>,
+    this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<Value<'gc>, Error<'gc>> {
+    if let Some(socket) = this.as_socket() {
+        let address = socket
+            .handle()
+            .and_then(|handle| activation.context.sockets.local_address(handle));
+
+        return Ok(match address {
+            Some(address) => AvmString::new_utf8(activation.gc(), address.ip().to_string()).into(),
+            None => Value::Null,
+        });
+    }
+
+    Ok(Value::Undefined)
+}
+
+pub fn get_local_port<'gc>(
+    activation: &mut Activation<'_, 'gc>,
+    this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<Value<'gc>, Error<'gc>> {
+    if let Some(socket) = this.as_socket() {
+        let address = socket
+            .handle()
+            .and_then(|handle| activation.context.sockets.local_address(handle));
+
+        return Ok(address.map_or(0, |address| address.port()).into());
+    }
+
+    Ok(Value::Undefined)
+}
+
+pub fn get_close_reason<'gc>(
+    _activation: &mut Activation<'_, 'gc>,
+    this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<Value<'gc>, Error<'gc>> {
+    if let Some(socket) = this.as_socket() {
+        return Ok(match socket.close_reason() {
+            Some(reason) => reason.as_str().into(),
+            None => Value::Null,
+        });
+    }
+
+    Ok(Value::Undefined)
+}
+
+pub fn get_record_size<'gc>(
+    _activation: &mut Activation<'_, 'gc>,
+    this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<Value<'gc>, Error<'gc>> {
+    if let Some(socket) = this.as_socket() {
+        return Ok(socket.record_size().into());
+    }
+
+    Ok(Value::Undefined)
+}
+
+pub fn set_record_size<'gc>(
+    activation: &mut Activation<'_, 'gc>,
+    this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<Value<'gc>, Error<'gc>> {
+    if let Some(socket) = this.as_socket() {
+        let record_size = args.get_u32(activation, 0)?;
+        socket.set_record_size(record_size);
+    }
+
+    Ok(Value::Undefined)
+}
+
+pub fn get_no_delay<'gc>(
+    _activation: &mut Activation<'_, 'gc>,
+    this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<Value<'gc>, Error<'gc>> {
+    if let Some(socket) = this.as_socket() {
+        return Ok(socket.no_delay().into());
+    }
+
+    Ok(Value::Undefined)
+}
+
+pub fn set_no_delay<'gc>(
+    _activation: &mut Activation<'_, 'gc>,
+    this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<Value<'gc>, Error<'gc>> {
+    if let Some(socket) = this.as_socket() {
+        let no_delay = args.get_bool(0);
+        socket.set_no_delay(no_delay);
+    }
+
+    Ok(Value::Undefined)
+}
+
+pub fn get_expected_total<'gc>(
+    _activation: &mut Activation<'_, 'gc>,
+    this: Object<'gc>,
+    _args: &[Value<'gc>],
+) -> Result<Value<'gc>, Error<'gc>> {
+    if let Some(socket) = this.as_socket() {
+        return Ok(socket.expected_total().unwrap_or(0).into());
+    }
+
+    Ok(Value::Undefined)
+}
+
+pub fn set_expected_total<'gc>(
+    activation: &mut Activation<'_, 'gc>,
+    this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<Value<'gc>, Error<'gc>> {
+    if let Some(socket) = this.as_socket() {
+        let expected_total = args.get_u32(activation, 0)?;
+        socket.set_expected_total(Some(expected_total).filter(|total| *total != 0));
+    }
+
+    Ok(Value::Undefined)
+}
+
+pub fn set_event_type<'gc>(
+    activation: &mut Activation<'_, 'gc>,
+    this: Object<'gc>,
+    args: &[Value<'gc>],
+) -> Result<Value<'gc>, Error<'gc>> {
+    if let Some(socket) = this.as_socket() {
+        let event_type = args.get_string(activation, 0)?;
+        let custom = args.get_string(activation, 1)?;
+
+        // NOTE: Unknown types are ignored, so the standard events keep being dispatched.
+        if !socket.set_event_type(&event_type.to_utf8_lossy(), &custom.to_utf8_lossy()) {
+            tracing::warn!(
+                "Socket does not dispatch events of type {event_type}, ignoring override"
+            );
+        }
+    }
+
+    Ok(Value::Undefined)
+}
+
 pub fn get_object_encoding<'gc>(
     _activation: &mut Activation<'_, 'gc>,
     this: Object<'gc>,
@@ -198,17 +355,11 @@ pub fn flush<'gc>(
 ) -> Result<Value<'gc>, Error<'gc>> {
     if let Some(socket) = this.as_socket() {
         let handle = socket.handle().ok_or(invalid_socket_error(activation))?;
-        if !activation.context.sockets.is_connected(handle) {
+        if !activation.context.sockets.can_write(handle) {
             return Err(invalid_socket_error(activation));
         }
 
-        let UpdateContext { sockets, .. } = &mut activation.context;
-
-        let mut buffer = socket.write_buffer();
-        let len = buffer.len();
-        let data = buffer.drain(..len).collect::<Vec<_>>();
-
-        sockets.send(handle, data)
+        activation.context.sockets.flush(handle);
     }
 
     Ok(Value::Undefined)
@@ -362,34 +513,7 @@ pub fn read_object<'gc>(
     if let Some(socket) = this.as_socket() {
         assert_socket_open!(activation, socket);
 
-        let mut bytes = socket.read_buffer();
-
-        let (bytes_left, value) = match socket.object_encoding() {
-            ObjectEncoding::Amf0 => {
-                let mut decoder = AMF0Decoder::default();
-                let (extra, amf) = decoder
-                    .parse_single_element(&bytes)
-                    .map_err(|_| "Error: Invalid object")?;
-                (
-                    extra.len(),
-                    crate::avm2::amf::deserialize_value(activation, &amf)?,
-                )
-            }
-            ObjectEncoding::Amf3 => {
-                let mut decoder = AMF3Decoder::default();
-                let (extra, amf) = decoder
-                    .parse_single_element(&bytes)
-                    .map_err(|_| "Error: Invalid object")?;
-                (
-                    extra.len(),
-                    crate::avm2::amf::deserialize_value(activation, &amf)?,
-                )
-            }
-        };
-
-        let len = bytes.len();
-        let _ = bytes.drain(..(len - bytes_left));
-        return Ok(value);
+        return socket.read_object(activation);
     }
 
     Ok(Value::Undefined)
@@ -509,7 +633,7 @@ pub fn write_boolean<'gc>(
     args: &[Value<'gc>],
 ) -> Result<Value<'gc>, Error<'gc>> {
     if let Some(socket) = this.as_socket() {
-        assert_socket_open!(activation, socket);
+        assert_socket_writable!(activation, socket);
 
         let byte = args.get_bool(0);
         socket.write_boolean(byte);
@@ -524,7 +648,7 @@ pub fn write_byte<'gc>(
     args: &[Value<'gc>],
 ) -> Result<Value<'gc>, Error<'gc>> {
     if let Some(socket) = this.as_socket() {
-        assert_socket_open!(activation, socket);
+        assert_socket_writable!(activation, socket);
 
         let byte = args.get_u32(activation, 0)?;
         socket.write_bytes(&[byte as u8]);
@@ -539,7 +663,7 @@ pub fn write_bytes<'gc>(
     args: &[Value<'gc>],
 ) -> Result<Value<'gc>, Error<'gc>> {
     if let Some(socket) = this.as_socket() {
-        assert_socket_open!(activation, socket);
+        assert_socket_writable!(activation, socket);
 
         let bytearray = args.get_object(activation, 0, "bytes")?;
         let offset = args.get_u32(activation, 1)? as usize;
@@ -573,7 +697,7 @@ pub fn write_double<'gc>(
     args: &[Value<'gc>],
 ) -> Result<Value<'gc>, Error<'gc>> {
     if let Some(socket) = this.as_socket() {
-        assert_socket_open!(activation, socket);
+        assert_socket_writable!(activation, socket);
 
         let num = args.get_f64(activation, 0)?;
         socket.write_double(num);
@@ -588,7 +712,7 @@ pub fn write_float<'gc>(
     args: &[Value<'gc>],
 ) -> Result<Value<'gc>, Error<'gc>> {
     if let Some(socket) = this.as_socket() {
-        assert_socket_open!(activation, socket);
+        assert_socket_writable!(activation, socket);
 
         let num = args.get_f64(activation, 0)?;
         socket.write_float(num as f32);
@@ -603,7 +727,7 @@ pub fn write_int<'gc>(
     args: &[Value<'gc>],
 ) -> Result<Value<'gc>, Error<'gc>> {
     if let Some(socket) = this.as_socket() {
-        assert_socket_open!(activation, socket);
+        assert_socket_writable!(activation, socket);
 
         let num = args.get_i32(activation, 0)?;
         socket.write_int(num);
@@ -618,7 +742,7 @@ pub fn write_multi_byte<'gc>(
     args: &[Value<'gc>],
 ) -> Result<Value<'gc>, Error<'gc>> {
     if let Some(socket) = this.as_socket() {
-        assert_socket_open!(activation, socket);
+        assert_socket_writable!(activation, socket);
 
         let string = args.get_string(activation, 0)?;
         let charset_label = args.get_string(activation, 1)?;
@@ -639,35 +763,10 @@ pub fn write_object<'gc>(
     args: &[Value<'gc>],
 ) -> Result<Value<'gc>, Error<'gc>> {
     if let Some(socket) = this.as_socket() {
-        assert_socket_open!(activation, socket);
+        assert_socket_writable!(activation, socket);
 
         let obj = args.get_value(0);
-        let amf_version = match socket.object_encoding() {
-            ObjectEncoding::Amf0 => AMFVersion::AMF0,
-            ObjectEncoding::Amf3 => AMFVersion::AMF3,
-        };
-
-        let amf = crate::avm2::amf::serialize_value(
-            activation,
-            obj,
-            amf_version,
-            &mut Default::default(),
-        )
-        .unwrap_or(flash_lso::types::Value::Undefined);
-
-        let element = Element::new("", Rc::new(amf));
-        let mut lso = flash_lso::types::Lso::new(vec![element], "", amf_version);
-        let bytes =
-            flash_lso::write::write_to_bytes(&mut lso).map_err(|_| "Failed to serialize object")?;
-        // This is kind of hacky: We need to strip out the header and any padding so that we only write
-        // the value. In the future, there should be a method to do this in the flash_lso crate.
-        let element_padding = match amf_version {
-            AMFVersion::AMF0 => 8,
-            AMFVersion::AMF3 => 7,
-        };
-        socket.write_bytes(
-            &bytes[flash_lso::write::header_length(&lso.header) + element_padding..bytes.len() - 1],
-        );
+        socket.write_object(activation, obj)?;
     }
 
     Ok(Value::Undefined)
@@ -679,7 +778,7 @@ pub fn write_short<'gc>(
     args: &[Value<'gc>],
 ) -> Result<Value<'gc>, Error<'gc>> {
     if let Some(socket) = this.as_socket() {
-        assert_socket_open!(activation, socket);
+        assert_socket_writable!(activation, socket);
 
         let num = args.get_i32(activation, 0)?;
         socket.write_short(num as i16);
@@ -694,7 +793,7 @@ pub fn write_unsigned_int<'gc>(
     args: &[Value<'gc>],
 ) -> Result<Value<'gc>, Error<'gc>> {
     if let Some(socket) = this.as_socket() {
-        assert_socket_open!(activation, socket);
+        assert_socket_writable!(activation, socket);
 
         let num = args.get_u32(activation, 0)?;
         socket.write_unsigned_int(num);
@@ -709,7 +808,7 @@ pub fn write_utf<'gc>(
     args: &[Value<'gc>],
 ) -> Result<Value<'gc>, Error<'gc>> {
     if let Some(socket) = this.as_socket() {
-        assert_socket_open!(activation, socket);
+        assert_socket_writable!(activation, socket);
 
         let string = args.get_string(activation, 0)?;
 
@@ -725,7 +824,7 @@ pub fn write_utf_bytes<'gc>(
     args: &[Value<'gc>],
 ) -> Result<Value<'gc>, Error<'gc>> {
     if let Some(socket) = this.as_socket() {
-        assert_socket_open!(activation, socket);
+        assert_socket_writable!(activation, socket);
 
         let string = args.get_string(activation, 0)?;
 
@@ -164,6 +164,7 @@ include "flash/events/IOErrorEvent.as"
 include "flash/events/KeyboardEvent.as"
 include "flash/events/NetDataEvent.as"
 include "flash/events/NetStatusEvent.as"
+include "flash/events/OutputProgressEvent.as"
 include "flash/events/PressAndTapGestureEvent.as"
 include "flash/events/ProgressEvent.as"
 include "flash/events/SampleDataEvent.as"
@@ -3,12 +3,29 @@ use crate::avm2::object::script_object::ScriptObjectData;
 use crate::avm2::object::{ClassObject, Object, ObjectPtr, TObject};
 use crate::avm2::value::Value;
 use crate::avm2::{Activation, Error};
-use crate::socket::SocketHandle;
+use crate::socket::{CloseReason, SocketHandle};
+use crate::string::AvmString;
+use flash_lso::amf0::read::AMF0Decoder;
+use flash_lso::amf3::read::AMF3Decoder;
+use flash_lso::types::{AMFVersion, Element};
 use gc_arena::barrier::unlock;
 use gc_arena::{lock::RefLock, Collect, Gc};
 use gc_arena::{GcWeak, Mutation};
 use std::cell::{Cell, Ref, RefCell, RefMut};
+use std::collections::HashMap;
 use std::fmt;
+use std::rc::Rc;
+
+/// The types of the events dispatched to sockets, which subclasses may dispatch under other types.
+pub const SOCKET_EVENT_TYPES: [&str; 7] = [
+    "connect",
+    "close",
+    "socketData",
+    "ioError",
+    "securityError",
+    "outputProgress",
+    "readWatermark",
+];
 
 /// A class instance allocator that allocates ShaderData objects.
 pub fn socket_allocator<'gc>(
@@ -26,7 +43,13 @@ pub fn socket_allocator<'gc>(
             object_encoding: Cell::new(ObjectEncoding::Amf3),
             timeout: Cell::new(0),
             handle: Cell::new(None),
+            close_reason: Cell::new(None),
+            record_size: Cell::new(0),
+            no_delay: Cell::new(false),
+            expected_total: Cell::new(None),
+            event_types: RefCell::new(HashMap::new()),
             read_buffer: RefCell::new(vec![]),
+            partial_record: RefCell::new(vec![]),
             write_buffer: RefCell::new(vec![]),
         },
     ))
@@ -98,6 +121,18 @@ impl<'gc> SocketObject<'gc> {
         self.0.handle.replace(Some(handle))
     }
 
+    pub fn take_handle(&self) -> Option<SocketHandle> {
+        self.0.handle.take()
+    }
+
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        self.0.close_reason.get()
+    }
+
+    pub fn set_close_reason(&self, close_reason: Option<CloseReason>) {
+        self.0.close_reason.set(close_reason)
+    }
+
     pub fn read_buffer(&self) -> RefMut<'_, Vec<u8>> {
         self.0.read_buffer.borrow_mut()
     }
@@ -106,6 +141,90 @@ impl<'gc> SocketObject<'gc> {
         self.0.write_buffer.borrow_mut()
     }
 
+    /// Received bytes that don't make up a whole record yet.
+    pub fn partial_record(&self) -> RefMut<'_, Vec<u8>> {
+        self.0.partial_record.borrow_mut()
+    }
+
+    pub fn record_size(&self) -> u32 {
+        self.0.record_size.get()
+    }
+
+    pub fn set_record_size(&self, record_size: u32) {
+        self.0.record_size.set(record_size);
+
+        if record_size == 0 {
+            // Back to a byte stream, so nothing needs to be held back anymore.
+            let partial = std::mem::take(&mut *self.partial_record());
+            self.read_buffer().extend(partial);
+        }
+    }
+
+    pub fn no_delay(&self) -> bool {
+        self.0.no_delay.get()
+    }
+
+    /// Sets whether the next connection disables Nagle's algorithm.
+    ///
+    /// This has no effect on an already open connection, use [`crate::socket::Sockets::set_no_delay`] for that.
+    pub fn set_no_delay(&self, no_delay: bool) {
+        self.0.no_delay.set(no_delay)
+    }
+
+    pub fn expected_total(&self) -> Option<u32> {
+        self.0.expected_total.get()
+    }
+
+    pub fn set_expected_total(&self, expected_total: Option<u32>) {
+        self.0.expected_total.set(expected_total)
+    }
+
+    /// The type that events of the given standard type are dispatched with.
+    pub fn event_type(&self, mc: &Mutation<'gc>, event_type: &'static str) -> AvmString<'gc> {
+        match self.0.event_types.borrow().get(event_type) {
+            Some(custom) => AvmString::new_utf8(mc, custom),
+            None => event_type.into(),
+        }
+    }
+
+    /// Dispatches events of the given standard type with the `custom` type instead, or with the
+    /// standard type again if `custom` is empty.
+    ///
+    /// Returns `false` if `event_type` isn't one of [`SOCKET_EVENT_TYPES`], in which case nothing changes.
+    pub fn set_event_type(&self, event_type: &str, custom: &str) -> bool {
+        let Some(event_type) = SOCKET_EVENT_TYPES.into_iter().find(|t| *t == event_type) else {
+            return false;
+        };
+
+        let mut event_types = self.0.event_types.borrow_mut();
+        if custom.is_empty() {
+            event_types.remove(event_type);
+        } else {
+            event_types.insert(event_type, custom.to_string());
+        }
+        true
+    }
+
+    /// Buffers data received from the backend.
+    ///
+    /// When a record size is set, only whole records are made available to reads.
+    /// Returns how many bytes were made available.
+    pub fn receive(&self, data: Vec<u8>) -> usize {
+        let record_size = self.record_size() as usize;
+        if record_size == 0 {
+            let len = data.len();
+            self.read_buffer().extend(data);
+            return len;
+        }
+
+        let mut partial = self.partial_record();
+        partial.extend(data);
+
+        let available = partial.len() - partial.len() % record_size;
+        self.read_buffer().extend(partial.drain(..available));
+        available
+    }
+
     pub fn read_bytes(&self, amnt: usize) -> Result<Vec<u8>, ByteArrayError> {
         let mut buf = self.read_buffer();
 
@@ -119,6 +238,15 @@ impl<'gc> SocketObject<'gc> {
         Ok(bytes.collect())
     }
 
+    /// Same as `read_bytes`, but leaves the bytes in the read buffer.
+    pub fn peek_bytes(&self, amnt: usize) -> Result<Vec<u8>, ByteArrayError> {
+        let buf = self.read_buffer();
+
+        buf.get(..amnt)
+            .map(|bytes| bytes.to_vec())
+            .ok_or(ByteArrayError::EndOfFile)
+    }
+
     pub fn write_bytes(&self, bytes: &[u8]) {
         self.0.write_buffer.borrow_mut().extend_from_slice(bytes)
     }
@@ -161,8 +289,102 @@ impl<'gc> SocketObject<'gc> {
             Err("RangeError: UTF String length must fit into a short".into())
         }
     }
+
+    /// Reads one AMF encoded value from the read buffer, using the object encoding of this socket.
+    ///
+    /// If the read buffer doesn't hold a whole value yet, nothing is consumed and an `EOFError`
+    /// is thrown, so the value can be read once the rest of it has arrived.
+    pub fn read_object(
+        &self,
+        activation: &mut Activation<'_, 'gc>,
+    ) -> Result<Value<'gc>, Error<'gc>> {
+        match self.object_encoding() {
+            ObjectEncoding::Amf0 => {
+                let mut decoder = AMF0Decoder::default();
+                let amf =
+                    self.take_amf(activation, |bytes| decoder.parse_single_element(bytes).ok())?;
+                crate::avm2::amf::deserialize_value(activation, &amf)
+            }
+            ObjectEncoding::Amf3 => {
+                let mut decoder = AMF3Decoder::default();
+                let amf =
+                    self.take_amf(activation, |bytes| decoder.parse_single_element(bytes).ok())?;
+                crate::avm2::amf::deserialize_value(activation, &amf)
+            }
+        }
+    }
+
+    /// Parses a value off the start of the read buffer, consuming it only if parsing succeeds.
+    ///
+    /// A value that starts with a valid type marker but doesn't parse is assumed to be cut short,
+    /// and fails with an EOF error, as the rest of it may still arrive. Anything else is an
+    /// invalid object.
+    ///
+    /// NOTE: The buffer is released before the value is deserialized, as that may run AS code.
+    fn take_amf<T>(
+        &self,
+        activation: &mut Activation<'_, 'gc>,
+        parse: impl FnOnce(&[u8]) -> Option<(&[u8], T)>,
+    ) -> Result<T, Error<'gc>> {
+        let mut buffer = self.read_buffer();
+        let truncated = match parse(&buffer) {
+            Some((extra, amf)) => {
+                let len = buffer.len() - extra.len();
+                buffer.drain(..len);
+                return Ok(amf);
+            }
+            None => !buffer
+                .first()
+                .is_some_and(|marker| *marker > AMF_MAX_MARKER),
+        };
+        drop(buffer);
+
+        if truncated {
+            Err(ByteArrayError::EndOfFile.to_avm(activation))
+        } else {
+            Err("Error: Invalid object".into())
+        }
+    }
+
+    /// Writes a value to the write buffer, AMF encoded with the object encoding of this socket.
+    pub fn write_object(
+        &self,
+        activation: &mut Activation<'_, 'gc>,
+        value: Value<'gc>,
+    ) -> Result<(), Error<'gc>> {
+        let amf_version = match self.object_encoding() {
+            ObjectEncoding::Amf0 => AMFVersion::AMF0,
+            ObjectEncoding::Amf3 => AMFVersion::AMF3,
+        };
+
+        let amf = crate::avm2::amf::serialize_value(
+            activation,
+            value,
+            amf_version,
+            &mut Default::default(),
+        )
+        .unwrap_or(flash_lso::types::Value::Undefined);
+
+        let element = Element::new("", Rc::new(amf));
+        let mut lso = flash_lso::types::Lso::new(vec![element], "", amf_version);
+        let bytes =
+            flash_lso::write::write_to_bytes(&mut lso).map_err(|_| "Failed to serialize object")?;
+        // This is kind of hacky: We need to strip out the header and any padding so that we only write
+        // the value. In the future, there should be a method to do this in the flash_lso crate.
+        let element_padding = match amf_version {
+            AMFVersion::AMF0 => 8,
+            AMFVersion::AMF3 => 7,
+        };
+        self.write_bytes(
+            &bytes[flash_lso::write::header_length(&lso.header) + element_padding..bytes.len() - 1],
+        );
+        Ok(())
+    }
 }
 
+/// The highest type marker used by either AMF0 or AMF3.
+const AMF_MAX_MARKER: u8 = 0x11;
+
 macro_rules! impl_write{
     ($($method_name:ident $data_type:ty ), *)
     =>
@@ -180,14 +402,20 @@ macro_rules! impl_write{
 }
 
 macro_rules! impl_read{
-    ($($method_name:ident $size:expr; $data_type:ty ), *)
+    ($($method_name:ident $peek_method_name:ident $size:expr; $data_type:ty ), *)
     =>
     {
         impl<'gc> SocketObject<'gc> {
             $( pub fn $method_name (&self) -> Result<$data_type, ByteArrayError> {
+                let val = self.$peek_method_name()?;
+                self.read_buffer().drain(..$size);
+                Ok(val)
+             } )*
+
+             $( pub fn $peek_method_name (&self) -> Result<$data_type, ByteArrayError> {
                 Ok(match self.endian() {
-                    Endian::Big => <$data_type>::from_be_bytes(self.read_bytes($size)?.try_into().unwrap()),
-                    Endian::Little => <$data_type>::from_le_bytes(self.read_bytes($size)?.try_into().unwrap())
+                    Endian::Big => <$data_type>::from_be_bytes(self.peek_bytes($size)?.try_into().unwrap()),
+                    Endian::Little => <$data_type>::from_le_bytes(self.peek_bytes($size)?.try_into().unwrap())
                 })
              } )*
         }
@@ -195,7 +423,7 @@ macro_rules! impl_read{
 }
 
 impl_write!(write_float f32, write_double f64, write_int i32, write_unsigned_int u32, write_short i16, write_unsigned_short u16);
-impl_read!(read_float 4; f32, read_double 8; f64, read_int 4; i32, read_unsigned_int 4; u32, read_short 2; i16, read_unsigned_short 2; u16, read_byte 1; i8, read_unsigned_byte 1; u8);
+impl_read!(read_float peek_float 4; f32, read_double peek_double 8; f64, read_int peek_int 4; i32, read_unsigned_int peek_unsigned_int 4; u32, read_short peek_short 2; i16, read_unsigned_short peek_unsigned_short 2; u16, read_byte peek_byte 1; i8, read_unsigned_byte peek_unsigned_byte 1; u8);
 
 #[derive(Collect)]
 #[collect(no_drop)]
@@ -204,14 +432,27 @@ pub struct SocketObjectData<'gc> {
     base: RefLock<ScriptObjectData<'gc>>,
     #[collect(require_static)]
     handle: Cell<Option<SocketHandle>>,
+    /// Why the last connection was closed by the backend.
+    #[collect(require_static)]
+    close_reason: Cell<Option<CloseReason>>,
 
     endian: Cell<Endian>,
     object_encoding: Cell<ObjectEncoding>,
     /// Socket connection timeout in milliseconds.
     timeout: Cell<u32>,
+    /// Size of the records that received data is delivered in, or 0 for a plain byte stream.
+    record_size: Cell<u32>,
+    /// Whether new connections disable Nagle's algorithm.
+    no_delay: Cell<bool>,
+    /// Size of the message the application expects, reported as `bytesTotal` of `socketData`.
+    expected_total: Cell<Option<u32>>,
+    /// Types that a subclass dispatches instead of the standard event types.
+    #[collect(require_static)]
+    event_types: RefCell<HashMap<&'static str, String>>,
 
     read_buffer: RefCell<Vec<u8>>,
     write_buffer: RefCell<Vec<u8>>,
+    partial_record: RefCell<Vec<u8>>,
 }
 
 impl fmt::Debug for SocketObject<'_> {
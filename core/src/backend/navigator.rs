@@ -1,7 +1,7 @@
 //! Browser-related platform functions
 
 use crate::loader::Error;
-use crate::socket::{ConnectionState, SocketAction, SocketHandle};
+use crate::socket::{ConnectionState, SocketAction, SocketCommand, SocketHandle, SocketOptions};
 use crate::string::WStr;
 use async_channel::{Receiver, Sender};
 use indexmap::IndexMap;
@@ -304,14 +304,20 @@ pub trait NavigatorBackend {
     ///
     /// Use [SocketAction::Data] to send data to AVM side.
     ///
+    /// The [SocketOptions] carry additional settings for the connection, which backends
+    /// may ignore if they do not support them.
+    ///
+    /// The receiver yields [SocketCommand]s, such as data to send over the connection.
+    ///
     /// When the Sender of the Receiver is dropped then this task should end.
     fn connect_socket(
         &mut self,
         host: String,
         port: u16,
         timeout: Duration,
+        options: SocketOptions,
         handle: SocketHandle,
-        receiver: Receiver<Vec<u8>>,
+        receiver: Receiver<SocketCommand>,
         sender: Sender<SocketAction>,
     );
 }
@@ -449,16 +455,164 @@ impl NavigatorBackend for NullNavigatorBackend {
         _host: String,
         _port: u16,
         _timeout: Duration,
+        _options: SocketOptions,
         handle: SocketHandle,
-        _receiver: Receiver<Vec<u8>>,
+        _receiver: Receiver<SocketCommand>,
         sender: Sender<SocketAction>,
     ) {
         sender
-            .try_send(SocketAction::Connect(handle, ConnectionState::Failed))
+            .try_send(SocketAction::Connect(handle, ConnectionState::Failed(None)))
             .expect("working channel send");
     }
 }
 
+/// A socket connection made through the [`LoopbackNavigatorBackend`].
+///
+/// Tests use this to script the actions the backend would report, and to inspect
+/// the data sent by the movie.
+#[cfg(test)]
+pub struct LoopbackSocket {
+    pub host: String,
+    pub port: u16,
+    pub options: SocketOptions,
+    pub handle: SocketHandle,
+    receiver: Receiver<SocketCommand>,
+    sender: Sender<SocketAction>,
+}
+
+#[cfg(test)]
+impl LoopbackSocket {
+    /// Queues an action, as if the backend reported it.
+    pub fn push(&self, action: SocketAction) {
+        self.sender.try_send(action).expect("working channel send");
+    }
+
+    pub fn connect(&self) {
+        self.push(SocketAction::Connect(
+            self.handle,
+            ConnectionState::Connected,
+        ));
+    }
+
+    pub fn receive(&self, data: &[u8]) {
+        self.push(SocketAction::Data(self.handle, data.to_vec()));
+    }
+
+    pub fn close(&self) {
+        self.push(SocketAction::Close(
+            self.handle,
+            crate::socket::CloseReason::Graceful,
+        ));
+    }
+
+    pub fn reset(&self) {
+        self.push(SocketAction::Close(
+            self.handle,
+            crate::socket::CloseReason::Reset,
+        ));
+    }
+
+    /// Whether the player dropped its end of the connection, which tells backends to close it.
+    pub fn is_closed(&self) -> bool {
+        self.receiver.is_closed()
+    }
+
+    /// Takes all commands the player has sent to this socket so far.
+    pub fn commands(&self) -> Vec<SocketCommand> {
+        let mut commands = vec![];
+
+        while let Ok(command) = self.receiver.try_recv() {
+            commands.push(command);
+        }
+
+        commands
+    }
+
+    /// Takes all data the movie has sent over this socket so far, ignoring other commands.
+    pub fn written(&self) -> Vec<u8> {
+        let mut data = vec![];
+
+        for command in self.commands() {
+            if let SocketCommand::Send(chunk) | SocketCommand::SendPriority(chunk) = command {
+                data.extend(chunk);
+            }
+        }
+
+        data
+    }
+}
+
+/// A navigator for tests which does not open any real connections.
+///
+/// Every socket connection is recorded as a [`LoopbackSocket`] instead, and no
+/// actions are sent until the test pushes them.
+#[cfg(test)]
+#[derive(Default)]
+pub struct LoopbackNavigatorBackend {
+    inner: NullNavigatorBackend,
+    sockets: std::rc::Rc<std::cell::RefCell<Vec<LoopbackSocket>>>,
+}
+
+#[cfg(test)]
+impl LoopbackNavigatorBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The sockets connected through this backend, in connection order.
+    pub fn sockets(&self) -> std::rc::Rc<std::cell::RefCell<Vec<LoopbackSocket>>> {
+        self.sockets.clone()
+    }
+}
+
+#[cfg(test)]
+impl NavigatorBackend for LoopbackNavigatorBackend {
+    fn navigate_to_url(
+        &self,
+        url: &str,
+        target: &str,
+        vars_method: Option<(NavigationMethod, IndexMap<String, String>)>,
+    ) {
+        self.inner.navigate_to_url(url, target, vars_method)
+    }
+
+    fn fetch(&self, request: Request) -> OwnedFuture<Box<dyn SuccessResponse>, ErrorResponse> {
+        self.inner.fetch(request)
+    }
+
+    fn resolve_url(&self, url: &str) -> Result<Url, ParseError> {
+        self.inner.resolve_url(url)
+    }
+
+    fn spawn_future(&mut self, future: OwnedFuture<(), Error>) {
+        self.inner.spawn_future(future)
+    }
+
+    fn pre_process_url(&self, url: Url) -> Url {
+        self.inner.pre_process_url(url)
+    }
+
+    fn connect_socket(
+        &mut self,
+        host: String,
+        port: u16,
+        _timeout: Duration,
+        options: SocketOptions,
+        handle: SocketHandle,
+        receiver: Receiver<SocketCommand>,
+        sender: Sender<SocketAction>,
+    ) {
+        self.sockets.borrow_mut().push(LoopbackSocket {
+            host,
+            port,
+            options,
+            handle,
+            receiver,
+            sender,
+        });
+    }
+}
+
 // The following functions are helper functions used in different
 // NavigatorBackend implementations.
 // To avoid duplicated code, they are placed here as public functions.
@@ -455,7 +455,7 @@ impl<'a, 'gc> UpdateContext<'a, 'gc> {
         //       the player instance instead of cleaning up.
 
         // Clean up the stage before loading another root movie.
-        self.sockets.close_all();
+        Sockets::close_for_navigation(self);
         self.timers.remove_all();
 
         self.set_root_movie(movie);
@@ -2243,6 +2243,7 @@ pub struct PlayerBuilder {
     #[cfg(feature = "known_stubs")]
     stub_report_output: Option<std::path::PathBuf>,
     avm2_optimizer_enabled: bool,
+    restricted_ports: Vec<u16>,
 }
 
 impl PlayerBuilder {
@@ -2295,6 +2296,7 @@ impl PlayerBuilder {
             #[cfg(feature = "known_stubs")]
             stub_report_output: None,
             avm2_optimizer_enabled: true,
+            restricted_ports: vec![],
         }
     }
 
@@ -2502,6 +2504,15 @@ impl PlayerBuilder {
         self
     }
 
+    /// Sets the ports that movies may never connect sockets to.
+    ///
+    /// No ports are restricted by default. Pass [`crate::socket::DEFAULT_RESTRICTED_PORTS`] to
+    /// refuse the same ports Flash Player does.
+    pub fn with_restricted_ports(mut self, ports: impl IntoIterator<Item = u16>) -> Self {
+        self.restricted_ports = ports.into_iter().collect();
+        self
+    }
+
     fn create_gc_root<'gc>(
         gc_context: &'gc gc_arena::Mutation<'gc>,
         player_version: u8,
@@ -2689,6 +2700,7 @@ impl PlayerBuilder {
                 .avm2
                 .set_optimizer_enabled(self.avm2_optimizer_enabled);
             Avm2::load_player_globals(context).expect("Unable to load AVM2 globals");
+            context.sockets.set_restricted_ports(self.restricted_ports);
 
             let stage = context.stage;
             stage.set_align(context, self.align);
@@ -1,223 +1,3062 @@
 use crate::{
     avm1::{
-        globals::xml_socket::XmlSocket, Activation as Avm1Activation, ActivationIdentifier,
-        ExecutionReason, Object as Avm1Object, TObject as Avm1TObject,
+        Activation as Avm1Activation, ExecutionReason, Object as Avm1Object,
+        TObject as Avm1TObject, Value as Avm1Value,
     },
     avm2::{
-        object::SocketObject, Activation as Avm2Activation, Avm2, EventObject,
-        TObject as Avm2TObject,
+        object::SocketObject, Activation as Avm2Activation, ClassObject, Object as Avm2Object,
+        TObject as Avm2TObject, Value as Avm2Value,
     },
+};
+use gc_arena::Collect;
+use slotmap::new_key_type;
+use std::{
+    net::{IpAddr, SocketAddr},
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use web_time::Instant;
+
+#[cfg(feature = "socket")]
+use crate::{
+    avm1::{globals::xml_socket::XmlSocket, ActivationIdentifier},
+    avm2::{Avm2, EventObject},
     backend::navigator::NavigatorBackend,
     context::UpdateContext,
     string::AvmString,
 };
-use async_channel::{unbounded, Receiver, Sender as AsyncSender, Sender};
-use gc_arena::Collect;
-use slotmap::{new_key_type, SlotMap};
+#[cfg(feature = "socket")]
+use async_channel::{unbounded, Receiver, Sender as AsyncSender, Sender, TryRecvError};
+#[cfg(feature = "socket")]
+use memchr::memmem;
+#[cfg(feature = "socket_debug")]
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+#[cfg(feature = "socket")]
+use slotmap::SlotMap;
+#[cfg(feature = "socket")]
 use std::{
+    borrow::Cow,
     cell::{Cell, RefCell},
-    time::Duration,
+    collections::{HashMap, HashSet, VecDeque},
+    future::Future,
 };
 
+#[cfg(not(feature = "socket"))]
+mod disabled;
+#[cfg(test)]
+mod test_support;
+#[cfg(all(test, feature = "socket"))]
+mod tests;
+
+#[cfg(not(feature = "socket"))]
+pub use disabled::Sockets;
+
 new_key_type! {
+    /// Identifies a socket for as long as it is open.
+    ///
+    /// Slots of closed sockets may be reused, but with a bumped version, so the handle of
+    /// a closed socket never compares equal to the handle of a socket opened later.
     pub struct SocketHandle;
 }
 
 #[derive(Copy, Clone, Collect)]
 #[collect(no_drop)]
-enum SocketKind<'gc> {
+pub(crate) enum SocketKind<'gc> {
     Avm2(SocketObject<'gc>),
     Avm1(Avm1Object<'gc>),
 }
 
+#[cfg(feature = "socket")]
+impl SocketKind<'_> {
+    /// Whether both are the same AS object.
+    fn is_same_object(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Avm2(a), Self::Avm2(b)) => Avm2Object::ptr_eq(*a, *b),
+            (Self::Avm1(a), Self::Avm1(b)) => Avm1Object::ptr_eq(*a, *b),
+            _ => false,
+        }
+    }
+
+    fn descriptor(&self) -> SocketKindDescriptor {
+        match self {
+            Self::Avm2(_) => SocketKindDescriptor::Socket,
+            Self::Avm1(_) => SocketKindDescriptor::XmlSocket,
+        }
+    }
+}
+
+/// What kind of object a socket belongs to, as listed by [`Sockets::describe_sockets`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SocketKindDescriptor {
+    /// An AVM1 `XMLSocket`.
+    XmlSocket,
+    /// An AVM2 `flash.net.Socket`.
+    Socket,
+}
+
+#[cfg(feature = "socket")]
 #[derive(Collect)]
 #[collect(no_drop)]
 struct Socket<'gc> {
     target: SocketKind<'gc>,
-    sender: RefCell<AsyncSender<Vec<u8>>>,
+    /// The host this socket is connecting to, after the embedder's host rewriter had its say.
+    #[collect(require_static)]
+    host: String,
+    /// The port this socket is connecting to.
+    port: u16,
+    sender: RefCell<AsyncSender<SocketCommand>>,
     connected: Cell<bool>,
+    /// Path MTU of the connection, if reported by the backend.
+    path_mtu: Cell<Option<u16>>,
+    /// Local address of the connection, if reported by the backend.
+    #[collect(require_static)]
+    local_address: Cell<Option<SocketAddr>>,
+    /// Address the connection was made to, if reported by the backend.
+    #[collect(require_static)]
+    remote_address: Cell<Option<SocketAddr>>,
+    /// When the connection was established.
+    #[collect(require_static)]
+    connected_at: Cell<Option<Instant>>,
+    /// Number of bytes handed to the backend over the lifetime of the socket.
+    bytes_sent: Cell<u64>,
+    /// Whether the connection attempt succeeded, once the backend reported it.
+    #[collect(require_static)]
+    connection_state: RefCell<Option<ConnectionState>>,
+    /// Host code waiting for the connection attempt to finish.
+    #[collect(require_static)]
+    connect_waiters: RefCell<Vec<AsyncSender<ConnectionState>>>,
+    #[collect(require_static)]
+    stats: Cell<SocketStats>,
+    /// Whether the backend was asked to stop reading, as too much data is buffered.
+    reading_paused: Cell<bool>,
+    /// Bytes handed to the backend that it hasn't acknowledged with [`SocketAction::Sent`] yet.
+    unacked_bytes: Cell<u64>,
+    /// How many bytes at the start of the AVM2 write buffer were flushed, but held back by the send rate limit.
+    flushed_bytes: Cell<usize>,
+    /// Data sent with [`Sockets::send`] that is held back by the send rate limit.
+    #[collect(require_static)]
+    held_back: RefCell<Vec<u8>>,
+    /// Actions that arrived before the backend reported whether the connection succeeded.
+    #[collect(require_static)]
+    early_actions: RefCell<Vec<SocketAction>>,
+    /// Whether the backend reported [`SocketAction::WriteDrained`], and nothing was sent since.
+    write_drained: Cell<bool>,
+    /// Whether the write half of the connection was shut down with [`Sockets::shutdown_write`].
+    write_shut_down: Cell<bool>,
+    #[collect(require_static)]
+    codec: RefCell<Option<Box<dyn SocketCodec>>>,
+    /// Bytes the backend hasn't written out yet, if it was given a counter to report them with.
+    #[cfg(feature = "socket_debug")]
+    #[collect(require_static)]
+    queued_bytes: Option<QueuedBytes>,
+    /// A label set by the embedder for debugging tools, which the movie never sees.
+    #[collect(require_static)]
+    tag: Option<String>,
+    /// When the oldest send that no data was received after happened, if latency is tracked.
+    #[collect(require_static)]
+    awaiting_data_since: Cell<Option<Instant>>,
+    #[collect(require_static)]
+    latency: Cell<LatencyStats>,
+    /// Whether `readWatermark` was dispatched, and the read buffer wasn't drained below half the watermark since.
+    above_read_watermark: Cell<bool>,
 }
 
+#[cfg(feature = "socket")]
 impl<'gc> Socket<'gc> {
-    fn new(target: SocketKind<'gc>, sender: AsyncSender<Vec<u8>>) -> Self {
+    fn new(target: SocketKind<'gc>, host: String, sender: AsyncSender<SocketCommand>) -> Self {
         Self {
             target,
+            host,
+            port: 0,
             sender: RefCell::new(sender),
             connected: Cell::new(false),
+            path_mtu: Cell::new(None),
+            local_address: Cell::new(None),
+            remote_address: Cell::new(None),
+            connected_at: Cell::new(None),
+            bytes_sent: Cell::new(0),
+            connection_state: RefCell::new(None),
+            connect_waiters: RefCell::new(vec![]),
+            stats: Cell::new(SocketStats::default()),
+            reading_paused: Cell::new(false),
+            unacked_bytes: Cell::new(0),
+            flushed_bytes: Cell::new(0),
+            held_back: RefCell::new(vec![]),
+            early_actions: RefCell::new(vec![]),
+            write_drained: Cell::new(true),
+            write_shut_down: Cell::new(false),
+            codec: RefCell::new(None),
+            #[cfg(feature = "socket_debug")]
+            queued_bytes: None,
+            tag: None,
+            awaiting_data_since: Cell::new(None),
+            latency: Cell::new(LatencyStats::default()),
+            above_read_watermark: Cell::new(false),
+        }
+    }
+
+    /// Makes room for at least `capacity` bytes in the read buffer, without reallocating
+    /// if it's already large enough.
+    fn reserve_read_buffer(&self, capacity: usize) {
+        let reserve = |buffer: &mut Vec<u8>| buffer.reserve(capacity.saturating_sub(buffer.len()));
+
+        match self.target {
+            SocketKind::Avm2(target) => reserve(&mut target.read_buffer()),
+            SocketKind::Avm1(target) => {
+                let target = XmlSocket::cast(target.into()).expect("target should be XmlSocket");
+                reserve(&mut target.read_buffer());
+            }
+        }
+    }
+
+    /// Runs data sent by the movie through the codec of this socket, if it has one.
+    fn encode(&self, data: Vec<u8>) -> Vec<u8> {
+        match &mut *self.codec.borrow_mut() {
+            Some(codec) => codec.encode(data),
+            None => data,
+        }
+    }
+
+    /// Number of received bytes that are buffered on the player side.
+    fn buffered_bytes(&self) -> usize {
+        match self.target {
+            SocketKind::Avm2(target) => target.read_buffer().len() + target.partial_record().len(),
+            SocketKind::Avm1(target) => XmlSocket::cast(target.into())
+                .map_or(0, |xml_socket| xml_socket.read_buffer().len()),
+        }
+    }
+
+    /// Copies up to `len` bytes from the front of the read buffer, leaving them in place.
+    fn peek_read_buffer(&self, len: usize) -> Vec<u8> {
+        match self.target {
+            SocketKind::Avm2(target) => {
+                let buffer = target.read_buffer();
+                buffer[..len.min(buffer.len())].to_vec()
+            }
+            SocketKind::Avm1(target) => XmlSocket::cast(target.into())
+                .map(|xml_socket| {
+                    let buffer = xml_socket.read_buffer();
+                    buffer[..len.min(buffer.len())].to_vec()
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    fn clear_read_buffer(&self) {
+        match self.target {
+            SocketKind::Avm2(target) => {
+                target.read_buffer().clear();
+                target.partial_record().clear();
+            }
+            SocketKind::Avm1(target) => {
+                if let Some(xml_socket) = XmlSocket::cast(target.into()) {
+                    xml_socket.read_buffer().clear();
+                }
+            }
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum ConnectionState {
     Connected,
-    Failed,
+    /// The connection could not be established, with the error reported by the backend, if any.
+    Failed(Option<String>),
     TimedOut,
+    /// The host name could not be resolved.
+    DnsError,
+    /// The connection was refused by the player's socket policy.
+    SecurityDenied,
+    /// The connection was vetoed by the embedder's connect filter.
+    SecurityBlocked,
+}
+
+/// The IP version a connection was made over.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IpFamily {
+    V4,
+    V6,
+}
+
+impl From<IpAddr> for IpFamily {
+    fn from(ip: IpAddr) -> Self {
+        // NOTE: IPv4-mapped IPv6 addresses still connect over IPv4.
+        match ip.to_canonical() {
+            IpAddr::V4(_) => Self::V4,
+            IpAddr::V6(_) => Self::V6,
+        }
+    }
+}
+
+/// Why a socket connection was closed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CloseReason {
+    /// The connection was closed gracefully.
+    Graceful,
+    /// The connection was reset or broke down unexpectedly.
+    Reset,
+    /// The connection was closed by the player, because it violated a configured policy.
+    Policy,
+    /// The connection was closed by the movie itself.
+    ///
+    /// As in Flash, no close event is fired in this case.
+    Local,
+    /// The connection was reset by the player, see [`Sockets::abort`].
+    Aborted,
+}
+
+impl CloseReason {
+    /// The name of this reason, as exposed to ActionScript.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Graceful => "graceful",
+            Self::Reset => "reset",
+            Self::Policy => "policy",
+            Self::Local => "local",
+            Self::Aborted => "aborted",
+        }
+    }
+}
+
+/// An event reported by the backend handling a socket.
+///
+/// Backends report the actions of a connection in order, starting with [`SocketAction::Connect`],
+/// which only actions describing the connection itself (like [`SocketAction::Resolved`] or
+/// [`SocketAction::RemoteAddress`]) may precede.
+/// The player keeps that order per connection: should data arrive before the connection was
+/// reported anyway, it is only handled after the connect event was dispatched.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SocketAction {
+    Connect(SocketHandle, ConnectionState),
+    Data(SocketHandle, Vec<u8>),
+    Close(SocketHandle, CloseReason),
+    /// Reports the path MTU (or negotiated MSS) of a connected socket.
+    ///
+    /// This is best-effort and only sent by backends running on platforms that expose it.
+    PathMtu(SocketHandle, u16),
+    /// Reports the addresses the host of a socket resolved to, so they can be cached.
+    Resolved(SocketHandle, Vec<IpAddr>),
+    /// Reports how many bytes are still waiting to be written, after the backend flushed some.
+    OutputProgress(SocketHandle, u64),
+    /// Acknowledges that the backend wrote this many bytes to the connection.
+    Sent(SocketHandle, u64),
+    /// Reports that the write queue of the backend emptied, and everything in it was flushed to the OS.
+    ///
+    /// This is only sent by backends that can tell, after the last write of a burst.
+    WriteDrained(SocketHandle),
+    /// Reports the local address the connection is bound to, right before it is reported as connected.
+    ///
+    /// This is only sent by backends that know it.
+    LocalAddress(SocketHandle, SocketAddr),
+    /// Reports the address the connection was made to, right before it is reported as connected.
+    ///
+    /// With fallback hosts, this tells which of them was connected to.
+    RemoteAddress(SocketHandle, SocketAddr),
+    /// Acknowledges a [`SocketCommand::Flush`].
+    Flushed(SocketHandle),
+}
+
+/// Where a socket is in its lifecycle, as returned by [`Sockets::connection_status`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SocketStatus {
+    /// The backend hasn't reported whether the connection attempt succeeded yet.
+    Connecting,
+    Connected,
+    /// The connection attempt failed, but the socket wasn't closed yet.
+    Failed(ConnectionState),
+    /// The socket was closed, or never existed.
+    Closed,
+}
+
+/// Traffic counters of a single socket, which can be reset with [`Sockets::reset_stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SocketStats {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub messages_sent: u64,
+    pub messages_received: u64,
+    /// The most received bytes that were buffered at once.
+    pub peak_buffered_bytes: usize,
+}
+
+/// A histogram of the time between sending data over a socket and receiving the next data on it,
+/// recorded when enabled with [`Sockets::set_latency_tracking`].
+///
+/// This is only a rough proxy for the round-trip time: the peer may send data that doesn't answer
+/// anything, or answer several sends at once.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LatencyStats {
+    /// How many samples fell into each of [`LATENCY_BUCKET_BOUNDS`], followed by the count
+    /// of samples slower than all of them.
+    pub buckets: [u64; LATENCY_BUCKET_BOUNDS.len() + 1],
+    pub samples: u64,
+    pub total: Duration,
+    pub max: Duration,
+}
+
+/// The inclusive upper bounds of the buckets of [`LatencyStats`].
+pub const LATENCY_BUCKET_BOUNDS: [Duration; 10] = [
+    Duration::from_millis(1),
+    Duration::from_millis(2),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(20),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(200),
+    Duration::from_millis(500),
+    Duration::from_millis(1000),
+];
+
+impl LatencyStats {
+    #[cfg(feature = "socket")]
+    fn record(&mut self, latency: Duration) {
+        let bucket = LATENCY_BUCKET_BOUNDS
+            .iter()
+            .position(|bound| latency <= *bound)
+            .unwrap_or(LATENCY_BUCKET_BOUNDS.len());
+        self.buckets[bucket] += 1;
+        self.samples += 1;
+        self.total += latency;
+        self.max = self.max.max(latency);
+    }
+
+    /// The mean of all samples, or `None` if there are none.
+    pub fn mean(&self) -> Option<Duration> {
+        (self.samples > 0).then(|| {
+            Duration::from_nanos((self.total.as_nanos() / u128::from(self.samples)) as u64)
+        })
+    }
+}
+
+/// A count of bytes the player handed to the backend that weren't written out yet.
+///
+/// The player and the backend share the count, so the player sees it drop as soon as data is
+/// written, instead of once the backend's [`SocketAction::Sent`] is handled.
+#[derive(Clone, Debug, Default)]
+pub struct QueuedBytes(Arc<AtomicU64>);
+
+impl QueuedBytes {
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn add(&self, bytes: u64) {
+        self.0.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Lowers the count by bytes that were written out.
+    pub fn remove(&self, bytes: u64) {
+        let _ = self
+            .0
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |queued| {
+                Some(queued.saturating_sub(bytes))
+            });
+    }
+}
+
+/// Counts are only equal to themselves, not to other counts that happen to have the same value.
+impl PartialEq for QueuedBytes {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for QueuedBytes {}
+
+/// Additional settings for a new socket connection, passed to the backend.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SocketOptions {
+    /// Addresses the host resolved to for an earlier connection.
+    ///
+    /// Backends may connect to these directly instead of resolving the host again.
+    pub resolved_addresses: Option<Vec<IpAddr>>,
+
+    /// How long sent data may stay unacknowledged before the connection is considered dead
+    /// (`TCP_USER_TIMEOUT`), or `None` for the OS default.
+    ///
+    /// Backends that cannot apply this should ignore it.
+    pub tcp_user_timeout: Option<Duration>,
+
+    /// How long the connection may be idle before TCP keepalive probes are sent, or `None`
+    /// to leave keepalive disabled.
+    ///
+    /// Backends that cannot apply this should ignore it.
+    pub keepalive: Option<Duration>,
+
+    /// The network interface the connection should be bound to (`SO_BINDTODEVICE`), or `None`
+    /// to let the OS route it.
+    ///
+    /// Backends that cannot apply this should ignore it.
+    pub bind_device: Option<String>,
+
+    /// How long a connection closed by the player may be kept open for reuse by a later
+    /// connection to the same host and port, or `None` to close it right away.
+    ///
+    /// Backends should only pool connections that are still healthy, and must check that a pooled
+    /// connection wasn't closed by the peer before reusing it. Backends without a pool should ignore it.
+    pub pool_idle_timeout: Option<Duration>,
+
+    /// Whether Nagle's algorithm should be disabled (`TCP_NODELAY`) once connected.
+    ///
+    /// Defaults to `false`, matching Flash Player.
+    pub no_delay: bool,
+
+    /// The most bytes backends should read at once before reporting them with
+    /// [`SocketAction::Data`], or `None` for the backend default.
+    ///
+    /// Smaller chunks lower latency, larger ones cause fewer actions for bulk transfers.
+    pub read_chunk_size: Option<NonZeroUsize>,
+
+    /// The count of queued bytes of this connection, which backends should lower with
+    /// [`QueuedBytes::remove`] as they hand data off. The player only keeps it up to date
+    /// with the `socket_debug` feature.
+    pub queued_bytes: QueuedBytes,
+
+    /// Further hosts to try in order, should connecting to the given host fail.
+    ///
+    /// Backends report the connection as failed only once all of them failed, and should share
+    /// the connect timeout between them. Backends that cannot try several hosts should ignore this.
+    pub fallback_hosts: Vec<(String, u16)>,
+}
+
+/// Why data passed to [`Sockets::send`] and friends wasn't sent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SendError {
+    /// The socket was closed, or never existed.
+    NoSuchSocket,
+}
+
+/// A command sent from the player to the backend handling a socket.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SocketCommand {
+    /// Sends data over the connection.
+    Send(Vec<u8>),
+    /// Sends data over the connection ahead of any queued [`SocketCommand::Send`] data.
+    ///
+    /// Messages are never split up, so this only jumps ahead of whole messages.
+    /// Backends without separate lanes should treat it like [`SocketCommand::Send`].
+    SendPriority(Vec<u8>),
+    /// Enables or disables Nagle's algorithm (`TCP_NODELAY`) on the live connection.
+    ///
+    /// Backends that cannot change this at runtime should ignore it.
+    SetNoDelay(bool),
+    /// Shuts down the write half of the connection, while still reading from it.
+    ShutdownWrite,
+    /// Stops reading from the connection until [`SocketCommand::ResumeReading`],
+    /// so that the OS applies TCP backpressure to the peer.
+    ///
+    /// Backends that cannot stop reading should ignore it.
+    PauseReading,
+    /// Starts reading from the connection again after [`SocketCommand::PauseReading`].
+    ResumeReading,
+    /// Resets the connection instead of closing it gracefully, dropping any pending writes.
+    ///
+    /// Backends that cannot reset a connection should just close it.
+    Abort,
+    /// Asks the backend to report [`SocketAction::Flushed`] once everything sent before was
+    /// written to the connection.
+    ///
+    /// Backends that cannot tell should report it once they handed the data on.
+    Flush,
+}
+
+impl SocketAction {
+    /// A short name of this action, which does not include any payload.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Connect(_, _) => "connect",
+            Self::Data(_, _) => "data",
+            Self::Close(_, _) => "close",
+            Self::PathMtu(_, _) => "path_mtu",
+            Self::Resolved(_, _) => "resolved",
+            Self::OutputProgress(_, _) => "output_progress",
+            Self::Sent(_, _) => "sent",
+            Self::WriteDrained(_) => "write_drained",
+            Self::LocalAddress(_, _) => "local_address",
+            Self::RemoteAddress(_, _) => "remote_address",
+            Self::Flushed(_) => "flushed",
+        }
+    }
+
+    /// The handle of the socket this action is targeting.
+    pub fn handle(&self) -> SocketHandle {
+        match self {
+            Self::Connect(handle, _)
+            | Self::Data(handle, _)
+            | Self::Close(handle, _)
+            | Self::PathMtu(handle, _)
+            | Self::Resolved(handle, _)
+            | Self::OutputProgress(handle, _)
+            | Self::Sent(handle, _)
+            | Self::WriteDrained(handle)
+            | Self::LocalAddress(handle, _)
+            | Self::RemoteAddress(handle, _)
+            | Self::Flushed(handle) => *handle,
+        }
+    }
+
+    /// Returns this action, targeting another socket.
+    #[cfg(feature = "socket")]
+    fn with_handle(self, handle: SocketHandle) -> Self {
+        match self {
+            Self::Connect(_, state) => Self::Connect(handle, state),
+            Self::Data(_, data) => Self::Data(handle, data),
+            Self::Close(_, reason) => Self::Close(handle, reason),
+            Self::PathMtu(_, mtu) => Self::PathMtu(handle, mtu),
+            Self::Resolved(_, addresses) => Self::Resolved(handle, addresses),
+            Self::OutputProgress(_, pending) => Self::OutputProgress(handle, pending),
+            Self::Sent(_, written) => Self::Sent(handle, written),
+            Self::WriteDrained(_) => Self::WriteDrained(handle),
+            Self::LocalAddress(_, address) => Self::LocalAddress(handle, address),
+            Self::RemoteAddress(_, address) => Self::RemoteAddress(handle, address),
+            Self::Flushed(_) => Self::Flushed(handle),
+        }
+    }
+}
+
+/// Timeout used when a movie asks for a connect timeout of 0.
+#[cfg(feature = "socket")]
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Shortest connect timeout we honor.
+#[cfg(feature = "socket")]
+const MIN_CONNECT_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// Longest connect timeout we honor.
+#[cfg(feature = "socket")]
+const MAX_CONNECT_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// The most bytes reserved up front for the read buffer of a new connection.
+#[cfg(feature = "socket")]
+const MAX_READ_BUFFER_CAPACITY: usize = 1024 * 1024;
+
+/// How many connection attempts [`Sockets::connection_history`] remembers.
+#[cfg(feature = "socket")]
+const MAX_CONNECTION_HISTORY: usize = 256;
+
+/// How long resolved addresses of a host are reused, unless set with [`Sockets::set_dns_cache_ttl`].
+#[cfg(feature = "socket")]
+const DEFAULT_DNS_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// How many hosts the DNS cache remembers, before the oldest resolutions are forgotten.
+#[cfg(feature = "socket")]
+const MAX_DNS_CACHE_ENTRIES: usize = 256;
+
+/// Converts a connect timeout in milliseconds, as set by a movie, into the timeout passed to the backend.
+///
+/// A timeout of 0 means the default timeout, anything else is clamped to a sane range.
+#[cfg(feature = "socket")]
+fn connect_timeout(millis: u32) -> Duration {
+    if millis == 0 {
+        return DEFAULT_CONNECT_TIMEOUT;
+    }
+
+    let requested = Duration::from_millis(millis.into());
+    let timeout = requested.clamp(MIN_CONNECT_TIMEOUT, MAX_CONNECT_TIMEOUT);
+    if timeout != requested {
+        tracing::debug!(?requested, ?timeout, "clamping socket connect timeout");
+    }
+    timeout
+}
+
+/// Decides which sockets to close when the aggregate socket memory budget is exceeded.
+///
+/// Receives the bytes currently buffered across all sockets and the buffered bytes of each open
+/// socket, and returns the handles to close. Returning no handles refuses the incoming data instead.
+pub type BudgetExceededCallback =
+    Box<dyn FnMut(usize, &[(SocketHandle, usize)]) -> Vec<SocketHandle>>;
+
+/// A connection attempt that failed, as reported to the sink set with [`Sockets::set_diagnostic_sink`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SocketDiagnostic {
+    pub handle: SocketHandle,
+    /// The host that was connected to, after the embedder's host rewriter had its say.
+    pub host: String,
+    /// The port that was connected to, or 0 for invalid ports.
+    pub port: u16,
+    /// Why the connection failed.
+    pub state: ConnectionState,
+    /// When the failure was handled by the player.
+    pub timestamp: Instant,
+}
+
+/// Ports that Flash Player refuses to connect to, as they belong to well-known non-web services.
+///
+/// These aren't restricted unless passed to [`Sockets::set_restricted_ports`].
+pub const DEFAULT_RESTRICTED_PORTS: &[u16] = &[
+    1, 7, 9, 11, 13, 15, 17, 19, 20, 21, 22, 23, 25, 37, 42, 43, 53, 77, 79, 87, 95, 101, 102, 103,
+    104, 109, 110, 111, 113, 115, 117, 119, 123, 135, 139, 143, 179, 389, 465, 512, 513, 514, 515,
+    526, 530, 531, 532, 540, 556, 563, 587, 601, 636, 993, 995, 2049, 4045, 6000,
+];
+
+/// Called with the handle of a socket whose send queue just became empty.
+pub type SendQueueDrainedCallback = Box<dyn FnMut(SocketHandle)>;
+
+/// A connection the movie asked for, as listed by [`Sockets::connection_history`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConnectionAttempt {
+    pub handle: SocketHandle,
+    /// The host the movie asked for, before the embedder's host rewriter had its say.
+    pub host: String,
+    /// The port the movie asked for, or 0 for invalid ports.
+    pub port: u16,
+    /// How the attempt turned out, or `None` while it's pending or if it was closed first.
+    pub outcome: Option<ConnectionState>,
+}
+
+/// Decides whether a movie may connect to the given host and port.
+pub type ConnectFilter = Box<dyn Fn(&str, u16) -> bool>;
+
+/// Returns the host and port to connect to instead of the given ones, or `None` to leave them be.
+pub type HostRewriter = Box<dyn Fn(&str, u16) -> Option<(String, u16)>>;
+
+/// Receives every handled action, with the time since recording started.
+///
+/// Where the actions end up, such as a capture file, is up to the embedder.
+pub type SocketActionRecorder = Box<dyn FnMut(Duration, &SocketAction)>;
+
+/// Transforms the byte stream of a socket, such as a transport codec wrapping every message.
+///
+/// Both directions are streams: a call may see part of a frame, or several of them. Codecs buffer
+/// whatever they need to themselves, and return only the bytes that are ready.
+pub trait SocketCodec {
+    /// Transforms data sent by the movie, before it is handed to the backend.
+    fn encode(&mut self, data: Vec<u8>) -> Vec<u8>;
+
+    /// Transforms data received from the backend, before it is buffered for the movie.
+    fn decode(&mut self, data: Vec<u8>) -> Vec<u8>;
+}
+
+#[cfg(feature = "socket")]
+struct Recording {
+    recorder: SocketActionRecorder,
+    /// Whether received data is recorded, or replaced with zeroes of the same length.
+    include_payloads: bool,
+    started_at: Instant,
+}
+
+#[cfg(feature = "socket")]
+/// A capture of actions being fed to the sockets of a movie, instead of real connections.
+struct Replay {
+    actions: VecDeque<(Duration, SocketAction)>,
+    /// Captured handles, in the order their connections were opened.
+    unattached: VecDeque<SocketHandle>,
+    /// Captured handles, and the handles of the sockets that replay them.
+    attached: HashMap<SocketHandle, SocketHandle>,
+    /// Keeps the command channels open. Commands sent during a replay are dropped.
+    receivers: Vec<Receiver<SocketCommand>>,
+    preserve_timing: bool,
+    /// When the first connection was opened, which captured times are relative to.
+    started_at: Option<Instant>,
+}
+
+#[cfg(feature = "socket")]
+impl Replay {
+    fn new(actions: Vec<(Duration, SocketAction)>, preserve_timing: bool) -> Self {
+        let mut unattached = VecDeque::new();
+        for (_, action) in &actions {
+            if !unattached.contains(&action.handle()) {
+                unattached.push_back(action.handle());
+            }
+        }
+
+        Self {
+            actions: actions.into(),
+            unattached,
+            attached: HashMap::new(),
+            receivers: vec![],
+            preserve_timing,
+            started_at: None,
+        }
+    }
+
+    /// Lets a new socket replay the next captured connection.
+    fn attach(&mut self, handle: SocketHandle, receiver: Receiver<SocketCommand>) {
+        self.started_at.get_or_insert_with(Instant::now);
+        self.receivers.push(receiver);
+
+        match self.unattached.pop_front() {
+            Some(captured) => {
+                self.attached.insert(captured, handle);
+            }
+            None => tracing::warn!("Movie opened more connections than the capture has"),
+        }
+    }
+
+    /// Takes the actions that are due, targeting the sockets replaying them.
+    fn due_actions(&mut self, now: Instant) -> Vec<SocketAction> {
+        for receiver in &self.receivers {
+            while receiver.try_recv().is_ok() {}
+        }
+
+        let Some(started_at) = self.started_at else {
+            return vec![];
+        };
+
+        let mut due = vec![];
+        while let Some((at, action)) = self.actions.front() {
+            if self.preserve_timing && now.saturating_duration_since(started_at) < *at {
+                break;
+            }
+
+            // NOTE: To keep the order of actions, we wait for the movie to open this connection.
+            let Some(&handle) = self.attached.get(&action.handle()) else {
+                break;
+            };
+
+            if let Some((_, action)) = self.actions.pop_front() {
+                due.push(action.with_handle(handle));
+            }
+        }
+        due
+    }
+}
+
+/// Ends every message sent over an AVM1 `XMLSocket`, and by default every message received.
+#[cfg(feature = "socket")]
+const XML_SOCKET_DELIMITER: u8 = b'\0';
+
+/// Takes every complete message out of `buffer`, leaving any unterminated remainder behind.
+///
+/// Delimiters are only searched for from `scan_from` on, so a remainder that is known not to
+/// contain one isn't scanned again whenever more data arrives.
+/// The delimiters themselves are dropped, so back-to-back delimiters produce empty messages.
+/// An empty delimiter can't end a message, so nothing is taken out.
+#[cfg(feature = "socket")]
+fn extract_messages(buffer: &mut Vec<u8>, delimiter: &[u8], scan_from: usize) -> Vec<Vec<u8>> {
+    if delimiter.is_empty() {
+        return Vec::new();
+    }
+
+    let mut messages = Vec::new();
+    let mut start = 0;
+
+    for index in memmem::find_iter(&buffer[scan_from..], delimiter) {
+        let index = scan_from + index;
+        messages.push(buffer[start..index].to_vec());
+        start = index + delimiter.len();
+    }
+
+    buffer.drain(..start);
+    messages
+}
+
+/// Converts a port number passed by ActionScript, rejecting anything that isn't a valid port.
+///
+/// Fractions are truncated, as ActionScript would when coercing to an integer, but values out of
+/// range are rejected instead of wrapping around. Both AVMs use this, so they accept the same ports.
+pub fn validate_port(port: f64) -> Option<u16> {
+    let port = port.trunc();
+
+    if (0.0..=u16::MAX.into()).contains(&port) {
+        Some(port as u16)
+    } else {
+        None
+    }
+}
+
+/// The text of the `ioError` event fired when a connection attempt fails.
+///
+/// Flash reports all failures as #2031, so we keep the code and only make the message more helpful.
+#[cfg(feature = "socket")]
+fn io_error_message(state: &ConnectionState) -> Cow<'static, str> {
+    match state {
+        ConnectionState::DnsError => "Error #2031: Socket Error. Unable to resolve host.".into(),
+        ConnectionState::Failed(Some(error)) => {
+            format!("Error #2031: Socket Error. {error}").into()
+        }
+        _ => "Error #2031: Socket Error.".into(),
+    }
+}
+
+/// Creates the span grouping all events of a single socket connection.
+///
+/// Filtering on the `handle` field of these spans shows a connection across its whole lifetime.
+#[cfg(feature = "socket")]
+fn socket_span(handle: SocketHandle) -> tracing::Span {
+    tracing::debug_span!("socket", handle = ?handle)
+}
+
+/// Constructs an event to dispatch to an AVM2 socket.
+///
+/// If that fails, the error is logged and the socket is closed, so that one broken socket
+/// can't keep the events of the other sockets from being handled.
+fn construct_socket_event<'gc>(
+    activation: &mut Avm2Activation<'_, 'gc>,
+    class: ClassObject<'gc>,
+    args: &[Avm2Value<'gc>],
+    handle: SocketHandle,
+) -> Option<Avm2Object<'gc>> {
+    match class.construct(activation, args) {
+        // NOTE: Dispatching anything but an event panics, so this is checked here.
+        Ok(event) if event.as_event().is_some() => return Some(event),
+        Ok(event) => tracing::error!("Socket event is not an Event: {:?}", event),
+        Err(e) => tracing::error!("Failed to construct socket event: {:?}", e),
+    }
+
+    activation.context.sockets.close(handle);
+    None
 }
 
-#[derive(Debug, PartialEq, Eq)]
-pub enum SocketAction {
-    Connect(SocketHandle, ConnectionState),
-    Data(SocketHandle, Vec<u8>),
-    Close(SocketHandle),
-}
+/// Calls an event handler of an AVM1 `XMLSocket`, logging any error it throws.
+fn call_xml_socket_handler<'gc>(
+    target: Avm1Object<'gc>,
+    name: &'static str,
+    args: &[Avm1Value<'gc>],
+    activation: &mut Avm1Activation<'_, 'gc>,
+) {
+    if let Err(e) = target.call_method(name.into(), args, activation, ExecutionReason::Special) {
+        tracing::warn!("Error in XMLSocket.{}: {:?}", name, e);
+    }
+}
+
+#[cfg(feature = "socket")]
+/// A socket closed by the player, whose backend is still writing out the data sent before.
+struct DrainingSocket {
+    handle: SocketHandle,
+    /// Dropping this tells the backend to close the connection.
+    sender: AsyncSender<SocketCommand>,
+    /// When to close the connection, even if the backend didn't report [`SocketAction::Flushed`].
+    deadline: Instant,
+}
+
+#[cfg(feature = "socket")]
+/// A token bucket limiting how many bytes all sockets may send per second, together.
+struct SendRateLimiter {
+    max_bytes_per_sec: u32,
+    /// Bytes that may be sent right now. Goes negative when priority data overdraws it.
+    available: f64,
+    refilled_at: Instant,
+}
+
+#[cfg(feature = "socket")]
+impl SendRateLimiter {
+    fn new(max_bytes_per_sec: u32) -> Self {
+        Self {
+            max_bytes_per_sec,
+            available: max_bytes_per_sec.into(),
+            refilled_at: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled_at);
+        self.refilled_at = now;
+
+        // Allow bursts of up to one second worth of data.
+        let max = f64::from(self.max_bytes_per_sec);
+        self.available = (self.available + elapsed.as_secs_f64() * max).min(max);
+    }
+
+    /// Takes up to `wanted` bytes out of the bucket, returning how many may be sent.
+    fn take(&mut self, wanted: usize, now: Instant) -> usize {
+        self.refill(now);
+
+        let taken = (self.available.max(0.0) as usize).min(wanted);
+        self.available -= taken as f64;
+        taken
+    }
+
+    /// Takes `len` bytes out of the bucket, even if that overdraws it.
+    fn take_all(&mut self, len: usize, now: Instant) {
+        self.refill(now);
+        self.available -= len as f64;
+    }
+}
+
+/// Artificial latency and jitter added to socket traffic, see [`Sockets::set_simulated_lag`].
+#[cfg(feature = "socket_debug")]
+struct SimulatedLag {
+    latency: Duration,
+    jitter: Duration,
+    rng: SmallRng,
+    /// Commands for the backends, with when to send them.
+    outbound: VecDeque<(
+        Instant,
+        SocketHandle,
+        AsyncSender<SocketCommand>,
+        SocketCommand,
+    )>,
+    /// Actions reported by the backends, with when to handle them.
+    inbound: VecDeque<(Instant, SocketAction)>,
+}
+
+#[cfg(feature = "socket_debug")]
+impl SimulatedLag {
+    fn new(latency: Duration, jitter: Duration) -> Self {
+        Self {
+            latency,
+            jitter,
+            // NOTE: A fixed seed makes the jitter the same on every run, so lag bugs can be reproduced.
+            rng: SmallRng::seed_from_u64(0),
+            outbound: VecDeque::new(),
+            inbound: VecDeque::new(),
+        }
+    }
+
+    /// When something delayed now is due, which is never before anything delayed earlier,
+    /// so nothing is reordered.
+    fn due(&mut self, now: Instant, last: Option<Instant>) -> Instant {
+        let jitter = self.jitter.mul_f64(self.rng.gen());
+        let due = now + self.latency + jitter;
+        last.map_or(due, |last| due.max(last))
+    }
+
+    /// Delays data sent by the movie, and whatever is sent after it on the same socket.
+    ///
+    /// Returns the command if it can be sent right away.
+    fn delay_command(
+        &mut self,
+        handle: SocketHandle,
+        sender: &AsyncSender<SocketCommand>,
+        command: SocketCommand,
+        now: Instant,
+    ) -> Option<SocketCommand> {
+        let is_data = matches!(
+            command,
+            SocketCommand::Send(_) | SocketCommand::SendPriority(_)
+        );
+        if !is_data && !self.outbound.iter().any(|(_, h, _, _)| *h == handle) {
+            return Some(command);
+        }
+
+        let due = self.due(now, self.outbound.back().map(|(due, ..)| *due));
+        self.outbound
+            .push_back((due, handle, sender.clone(), command));
+        None
+    }
+
+    /// Sends the commands that are due to their backends.
+    fn send_due(&mut self, now: Instant) {
+        while let Some((due, ..)) = self.outbound.front() {
+            if *due > now {
+                break;
+            }
+
+            let (_, _, sender, command) = self.outbound.pop_front().expect("front exists");
+            // NOTE: The backend may be gone by now, which drops the command, as it would have without lag.
+            let _ = sender.try_send(command);
+        }
+    }
+
+    /// Delays received data, and whatever is received after it on the same socket.
+    ///
+    /// Returns the actions that are due, in the order they were received.
+    fn delay_actions(&mut self, actions: Vec<SocketAction>, now: Instant) -> Vec<SocketAction> {
+        let mut immediate = vec![];
+        for action in actions {
+            let handle = action.handle();
+            if !matches!(action, SocketAction::Data(..))
+                && !self.inbound.iter().any(|(_, a)| a.handle() == handle)
+            {
+                immediate.push(action);
+                continue;
+            }
+
+            let due = self.due(now, self.inbound.back().map(|(due, _)| *due));
+            self.inbound.push_back((due, action));
+        }
+
+        let mut due = vec![];
+        while self.inbound.front().is_some_and(|(d, _)| *d <= now) {
+            due.push(self.inbound.pop_front().expect("front exists").1);
+        }
+        due.extend(immediate);
+        due
+    }
+}
+
+#[cfg(feature = "socket")]
+/// Manages the collection of Sockets.
+pub struct Sockets<'gc> {
+    sockets: SlotMap<SocketHandle, Socket<'gc>>,
+
+    receiver: Receiver<SocketAction>,
+    sender: Sender<SocketAction>,
+
+    /// How long connections may stay open after navigating away, so their backends can write out
+    /// pending data.
+    ///
+    /// When `None`, sockets are closed immediately.
+    navigation_drain_timeout: Option<Duration>,
+
+    /// How long closing a socket may wait for its backend to pick up the data sent before.
+    close_drain_timeout: Option<Duration>,
+
+    /// Sockets that will be closed once their backend acknowledged a flush.
+    draining: Vec<DrainingSocket>,
+
+    /// Whether an open AVM2 connection that is replaced by a new one fires its close event.
+    dispatch_close_on_replace: bool,
+
+    /// Sockets whose open connection was replaced, which fire close on the next update.
+    replaced: Vec<SocketKind<'gc>>,
+
+    /// How long a single connection may stay open before it is force-closed.
+    ///
+    /// When `None`, connections may stay open indefinitely.
+    max_connection_lifetime: Option<Duration>,
+
+    /// Addresses that hosts resolved to, and when they were resolved.
+    dns_cache: HashMap<String, (Vec<IpAddr>, Instant)>,
+
+    /// How long entries of `dns_cache` are reused, before their hosts are resolved again.
+    dns_cache_ttl: Duration,
+
+    /// How many received bytes may be buffered across all sockets.
+    ///
+    /// When `None`, there is no limit.
+    memory_budget: Option<usize>,
+
+    /// Chooses the sockets to close when the memory budget is exceeded.
+    ///
+    /// When `None`, the socket with the largest buffer is closed.
+    on_budget_exceeded: Option<BudgetExceededCallback>,
+
+    /// Lets the embedder veto connections before they reach the backend.
+    connect_filter: Option<ConnectFilter>,
+
+    /// Lets the embedder point connections at other hosts than the movie asked for.
+    host_rewriter: Option<HostRewriter>,
+
+    /// Whether policy decisions are made on rewritten hosts, instead of the ones the movie asked for.
+    check_rewritten_hosts: bool,
+
+    /// `TCP_USER_TIMEOUT` for new connections, where the backend supports it.
+    tcp_user_timeout: Option<Duration>,
+
+    /// TCP keepalive idle time for new connections, where the backend supports it.
+    keepalive: Option<Duration>,
+
+    /// Network interface new connections are bound to, where the backend supports it.
+    bind_device: Option<String>,
+
+    /// How long backends keep closed connections around for reuse, if they pool them.
+    pool_idle_timeout: Option<Duration>,
+
+    /// The most bytes backends should read at once for new connections.
+    read_chunk_size: Option<NonZeroUsize>,
+
+    /// How many bytes are reserved for the read buffer of a socket once it connects.
+    read_buffer_capacity: usize,
+
+    /// How many buffered bytes make AVM2 sockets dispatch `readWatermark`.
+    read_watermark: Option<NonZeroUsize>,
+
+    /// Sends larger than this are handed to the backend in several commands of at most this size.
+    max_send_chunk_size: Option<NonZeroUsize>,
+
+    /// The most actions handled in one update, so a burst of activity can't stall a frame.
+    max_events_per_update: Option<NonZeroUsize>,
+
+    /// Actions that were received, but held back for the next update by `max_events_per_update`.
+    deferred_actions: VecDeque<SocketAction>,
+
+    /// The latest connection attempts of the movie, oldest first.
+    connection_history: VecDeque<ConnectionAttempt>,
+
+    /// Kinds of actions that were ignored at least once, so each kind is only warned about once.
+    ignored_action_kinds: HashSet<&'static str>,
+
+    /// Ports that connections are never allowed to.
+    restricted_ports: HashSet<u16>,
+
+    /// How many bytes may be buffered on a socket before the backend stops reading from it.
+    ///
+    /// When `None`, reading never pauses.
+    read_high_water_mark: Option<usize>,
+
+    /// How few bytes must be buffered on a paused socket before the backend resumes reading from it.
+    read_low_water_mark: usize,
+
+    /// Called whenever the send queue of a socket becomes empty.
+    on_send_queue_drained: Option<SendQueueDrainedCallback>,
+
+    /// Limits how fast all sockets may send data, together.
+    ///
+    /// When `None`, sending is not limited.
+    send_rate_limiter: Option<SendRateLimiter>,
+
+    /// Artificial lag for testing movies under adverse network conditions.
+    #[cfg(feature = "socket_debug")]
+    simulated_lag: Option<SimulatedLag>,
+
+    recording: Option<Recording>,
+
+    /// When set, connections are served from a capture instead of the backend.
+    replay: Option<Replay>,
+
+    /// Receives a record of every failed connection attempt.
+    diagnostic_sink: Option<std::sync::mpsc::Sender<SocketDiagnostic>>,
+
+    /// Whether the time between sends and the next received data is recorded.
+    track_latency: bool,
+}
+
+#[cfg(feature = "socket")]
+unsafe impl<'gc> Collect for Sockets<'gc> {
+    fn trace(&self, cc: &gc_arena::Collection) {
+        for (_, socket) in self.sockets.iter() {
+            socket.trace(cc)
+        }
+        self.replaced.trace(cc);
+    }
+}
+
+#[cfg(feature = "socket")]
+impl<'gc> Sockets<'gc> {
+    pub fn empty() -> Self {
+        let (sender, receiver) = unbounded();
+
+        Self {
+            sockets: SlotMap::with_key(),
+            receiver,
+            sender,
+            navigation_drain_timeout: None,
+            close_drain_timeout: None,
+            draining: vec![],
+            dispatch_close_on_replace: false,
+            replaced: vec![],
+            max_connection_lifetime: None,
+            dns_cache: HashMap::new(),
+            dns_cache_ttl: DEFAULT_DNS_CACHE_TTL,
+            memory_budget: None,
+            on_budget_exceeded: None,
+            connect_filter: None,
+            host_rewriter: None,
+            check_rewritten_hosts: false,
+            tcp_user_timeout: None,
+            keepalive: None,
+            bind_device: None,
+            pool_idle_timeout: None,
+            read_chunk_size: None,
+            read_buffer_capacity: 0,
+            read_watermark: None,
+            max_send_chunk_size: None,
+            max_events_per_update: None,
+            deferred_actions: VecDeque::new(),
+            connection_history: VecDeque::new(),
+            ignored_action_kinds: HashSet::new(),
+            restricted_ports: HashSet::new(),
+            read_high_water_mark: None,
+            read_low_water_mark: 0,
+            on_send_queue_drained: None,
+            send_rate_limiter: None,
+            #[cfg(feature = "socket_debug")]
+            simulated_lag: None,
+            recording: None,
+            replay: None,
+            diagnostic_sink: None,
+            track_latency: false,
+        }
+    }
+
+    /// Lists all cached host resolutions that are still fresh, along with when they were resolved.
+    pub fn dns_cache_entries(&self) -> Vec<(String, Vec<IpAddr>, Instant)> {
+        let now = Instant::now();
+
+        self.dns_cache
+            .iter()
+            .filter(|(_, (_, resolved_at))| self.is_dns_entry_fresh(*resolved_at, now))
+            .map(|(host, (addresses, resolved_at))| (host.clone(), addresses.clone(), *resolved_at))
+            .collect()
+    }
+
+    /// Forgets all cached host resolutions, so the next connections resolve their hosts again.
+    pub fn clear_dns_cache(&mut self) {
+        self.dns_cache.clear();
+    }
+
+    /// Sets how long the addresses a host resolved to are reused for new connections.
+    ///
+    /// Once an entry is older than this, the next connection to its host resolves it again.
+    pub fn set_dns_cache_ttl(&mut self, ttl: Duration) {
+        self.dns_cache_ttl = ttl;
+    }
+
+    fn is_dns_entry_fresh(&self, resolved_at: Instant, now: Instant) -> bool {
+        now.saturating_duration_since(resolved_at) < self.dns_cache_ttl
+    }
+
+    /// Caches the addresses a host resolved to, making room by forgetting stale entries, and
+    /// then the oldest ones, once the cache is full.
+    fn cache_resolution(&mut self, host: String, addresses: Vec<IpAddr>) {
+        let now = Instant::now();
+
+        if !self.dns_cache.contains_key(&host) && self.dns_cache.len() >= MAX_DNS_CACHE_ENTRIES {
+            let ttl = self.dns_cache_ttl;
+            self.dns_cache
+                .retain(|_, (_, resolved_at)| now.saturating_duration_since(*resolved_at) < ttl);
+
+            while self.dns_cache.len() >= MAX_DNS_CACHE_ENTRIES {
+                let Some(oldest) = self
+                    .dns_cache
+                    .iter()
+                    .min_by_key(|(_, (_, resolved_at))| *resolved_at)
+                    .map(|(host, _)| host.clone())
+                else {
+                    break;
+                };
+                self.dns_cache.remove(&oldest);
+            }
+        }
+
+        self.dns_cache.insert(host, (addresses, now));
+    }
+
+    fn options_for(&self, host: &str) -> SocketOptions {
+        let now = Instant::now();
+
+        SocketOptions {
+            resolved_addresses: self
+                .dns_cache
+                .get(host)
+                .filter(|(_, resolved_at)| self.is_dns_entry_fresh(*resolved_at, now))
+                .map(|(addresses, _)| addresses.clone()),
+            tcp_user_timeout: self.tcp_user_timeout,
+            keepalive: self.keepalive,
+            bind_device: self.bind_device.clone(),
+            pool_idle_timeout: self.pool_idle_timeout,
+            no_delay: false,
+            read_chunk_size: self.read_chunk_size,
+            queued_bytes: QueuedBytes::default(),
+            fallback_hosts: vec![],
+        }
+    }
+
+    /// Enables TCP keepalive with the given idle time for new connections, or disables it with `None`.
+    pub fn set_keepalive(&mut self, keepalive: Option<Duration>) {
+        self.keepalive = keepalive;
+    }
+
+    /// Sets how many bytes backends read at once for new connections, or `None` for the backend default.
+    pub fn set_read_chunk_size(&mut self, read_chunk_size: Option<NonZeroUsize>) {
+        self.read_chunk_size = read_chunk_size;
+    }
+
+    /// Sets how many bytes are reserved for the read buffer of a socket once it connects, so
+    /// high-throughput connections reallocate it less often.
+    ///
+    /// This is clamped to 1 MiB, as the memory is reserved before anything was received.
+    pub fn set_read_buffer_capacity(&mut self, capacity: usize) {
+        self.read_buffer_capacity = capacity.min(MAX_READ_BUFFER_CAPACITY);
+    }
+
+    /// Makes AVM2 sockets dispatch a `readWatermark` progress event once their read buffer holds
+    /// at least `watermark` bytes, or stops them from doing so with `None`.
+    ///
+    /// The event is dispatched after `socketData`, and only once until the movie reads the buffer
+    /// below half the watermark.
+    pub fn set_read_watermark(&mut self, watermark: Option<NonZeroUsize>) {
+        self.read_watermark = watermark;
+    }
+
+    /// Sets the most bytes handed to the backend in one go, or `None` to hand over sends as they are.
+    ///
+    /// Larger sends are split up, so one huge send doesn't hold up the backend and it can report
+    /// progress while writing it.
+    pub fn set_max_send_chunk_size(&mut self, max_send_chunk_size: Option<NonZeroUsize>) {
+        self.max_send_chunk_size = max_send_chunk_size;
+    }
+
+    /// Limits how many actions one call to [`Sockets::update_sockets`] handles, or removes the limit with `None`.
+    ///
+    /// Actions over the limit are handled on later updates, taking turns between sockets,
+    /// so one busy socket can't starve the others.
+    pub fn set_max_events_per_update(&mut self, max_events_per_update: Option<NonZeroUsize>) {
+        self.max_events_per_update = max_events_per_update;
+    }
+
+    /// Sets the TCP user timeout for new connections, or `None` to use the OS default.
+    pub fn set_tcp_user_timeout(&mut self, timeout: Option<Duration>) {
+        self.tcp_user_timeout = timeout;
+    }
+
+    /// Binds new connections to the named network interface (`SO_BINDTODEVICE`), or lets the OS
+    /// pick one with `None`.
+    pub fn set_bind_device(&mut self, device: Option<String>) {
+        self.bind_device = device;
+    }
+
+    /// Lets backends keep connections closed by the movie open for at most `idle_timeout`, so a
+    /// later connection to the same host and port can reuse them, or disables this with `None`.
+    ///
+    /// NOTE: Data the peer sends after the close is lost, so this only suits protocols where
+    ///       the peer stays silent between exchanges.
+    pub fn set_connection_pool_idle_timeout(&mut self, idle_timeout: Option<Duration>) {
+        self.pool_idle_timeout = idle_timeout;
+    }
+
+    /// Force-closes connections once they have been open for longer than `lifetime`, no matter how
+    /// busy they are, or lets them stay open indefinitely with `None`.
+    ///
+    /// The movie sees this like the peer closing the connection, with [`CloseReason::Policy`].
+    pub fn set_max_connection_lifetime(&mut self, lifetime: Option<Duration>) {
+        self.max_connection_lifetime = lifetime;
+    }
+
+    pub fn set_memory_budget(&mut self, budget: Option<usize>) {
+        self.memory_budget = budget;
+    }
+
+    pub fn set_on_budget_exceeded(&mut self, callback: Option<BudgetExceededCallback>) {
+        self.on_budget_exceeded = callback;
+    }
+
+    /// Sets a channel that receives a [`SocketDiagnostic`] for every connection that fails, timed
+    /// out or couldn't resolve its host, so embedders can show a network error log.
+    pub fn set_diagnostic_sink(&mut self, sink: Option<std::sync::mpsc::Sender<SocketDiagnostic>>) {
+        self.diagnostic_sink = sink;
+    }
+
+    /// Enables recording the time between sends and the next received data, see [`LatencyStats`].
+    pub fn set_latency_tracking(&mut self, enabled: bool) {
+        self.track_latency = enabled;
+    }
+
+    /// Reports a failed connection attempt to the diagnostic sink, if one is set.
+    fn report_diagnostic(&self, handle: SocketHandle, state: &ConnectionState) {
+        let (Some(sink), Some(socket)) = (&self.diagnostic_sink, self.sockets.get(handle)) else {
+            return;
+        };
+
+        // NOTE: The embedder may have dropped the receiver, which is fine.
+        let _ = sink.send(SocketDiagnostic {
+            handle,
+            host: socket.host.clone(),
+            port: socket.port,
+            state: state.clone(),
+            timestamp: Instant::now(),
+        });
+    }
+
+    /// Sets a filter that is consulted, on the main thread, before each connection is handed to the backend.
+    ///
+    /// Connections it rejects fail with [`ConnectionState::SecurityBlocked`].
+    pub fn set_connect_filter(&mut self, filter: Option<ConnectFilter>) {
+        self.connect_filter = filter;
+    }
+
+    /// Sets a hook that may point connections at another host and port than the movie asked for,
+    /// like a staging server instead of the production one.
+    ///
+    /// The connect filter and restricted ports still see the host and port the movie asked for,
+    /// unless `check_rewritten_hosts` is set. Backends only ever see the rewritten ones.
+    pub fn set_host_rewriter(
+        &mut self,
+        rewriter: Option<HostRewriter>,
+        check_rewritten_hosts: bool,
+    ) {
+        self.host_rewriter = rewriter;
+        self.check_rewritten_hosts = check_rewritten_hosts;
+    }
+
+    /// Applies the host rewriter to a connection the movie asked for, returning the host and port
+    /// to connect to, and whether the connection is allowed.
+    fn connect_target(&self, host: &str, port: u16) -> (String, u16, bool) {
+        let rewritten = self
+            .host_rewriter
+            .as_ref()
+            .and_then(|rewrite| rewrite(host, port));
+
+        match rewritten {
+            Some((new_host, new_port)) => {
+                tracing::debug!(
+                    from = %host,
+                    to = %new_host,
+                    port = new_port,
+                    "rewriting socket target"
+                );
+
+                let allowed = if self.check_rewritten_hosts {
+                    self.is_connect_allowed(&new_host, new_port)
+                } else {
+                    self.is_connect_allowed(host, port)
+                };
+                (new_host, new_port, allowed)
+            }
+            None => (host.to_string(), port, self.is_connect_allowed(host, port)),
+        }
+    }
+
+    /// Sets the ports that connections are refused to, which default to none.
+    ///
+    /// Connections to these ports fail with [`ConnectionState::SecurityBlocked`].
+    pub fn set_restricted_ports(&mut self, ports: impl IntoIterator<Item = u16>) {
+        self.restricted_ports = ports.into_iter().collect();
+    }
+
+    /// Checks a new connection against the port policy and the connect filter.
+    fn is_connect_allowed(&self, host: &str, port: u16) -> bool {
+        if self.restricted_ports.contains(&port) {
+            tracing::warn!(port, "Refusing to connect to a restricted port");
+            return false;
+        }
+
+        self.connect_filter
+            .as_ref()
+            .map_or(true, |filter| filter(host, port))
+    }
+
+    /// Sets when reading from sockets is paused and resumed, based on how many received bytes are buffered.
+    ///
+    /// Reading pauses once more than `high` bytes are buffered, and resumes once fewer than `low` are.
+    /// Passing `None` for `high` disables this.
+    pub fn set_read_water_marks(&mut self, high: Option<usize>, low: usize) {
+        self.read_high_water_mark = high;
+        self.read_low_water_mark = low;
+
+        if high.is_none() {
+            for (_, socket) in self.sockets.iter() {
+                if socket.reading_paused.replace(false) {
+                    let _ = socket
+                        .sender
+                        .borrow()
+                        .try_send(SocketCommand::ResumeReading);
+                }
+            }
+        }
+    }
+
+    /// Pauses reading from sockets that buffer too much data, and resumes it once content read enough of it.
+    fn apply_read_backpressure(&self) {
+        let Some(high) = self.read_high_water_mark else {
+            return;
+        };
+
+        for (handle, socket) in self.sockets.iter() {
+            let buffered = socket.buffered_bytes();
+            let paused = socket.reading_paused.get();
+
+            let command = if !paused && buffered > high {
+                SocketCommand::PauseReading
+            } else if paused && buffered < self.read_low_water_mark {
+                SocketCommand::ResumeReading
+            } else {
+                continue;
+            };
+
+            let _span = socket_span(handle).entered();
+            tracing::debug!(buffered, ?command, "applying read backpressure");
+
+            socket.reading_paused.set(!paused);
+            let _ = socket.sender.borrow().try_send(command);
+        }
+    }
+
+    /// Sets a callback that is called whenever all data queued on a socket was written by the backend.
+    pub fn set_on_send_queue_drained(&mut self, callback: Option<SendQueueDrainedCallback>) {
+        self.on_send_queue_drained = callback;
+    }
+
+    /// Limits how many bytes all sockets may send per second, together, or removes the limit with `None`.
+    ///
+    /// Data over the limit is held back and sent on later frames.
+    pub fn set_global_max_bytes_per_sec(&mut self, max_bytes_per_sec: Option<u32>) {
+        self.send_rate_limiter = max_bytes_per_sec.map(SendRateLimiter::new);
+    }
+
+    /// Delays what movies send and receive by `latency` plus a random share of `jitter`, to test
+    /// netcode under lag without a real proxy, or stops doing so with `None`.
+    ///
+    /// Only sent data and received data are delayed, along with anything that follows them on the
+    /// same socket, so nothing is reordered. Turning this off lets everything delayed through.
+    #[cfg(feature = "socket_debug")]
+    pub fn set_simulated_lag(&mut self, lag: Option<(Duration, Duration)>) {
+        tracing::warn!(?lag, "simulating socket lag");
+
+        if let Some(old) = self.simulated_lag.take() {
+            for (_, _, sender, command) in old.outbound {
+                let _ = sender.try_send(command);
+            }
+            self.deferred_actions
+                .extend(old.inbound.into_iter().map(|(_, action)| action));
+        }
+        self.simulated_lag = lag.map(|(latency, jitter)| SimulatedLag::new(latency, jitter));
+    }
+
+    /// Passes every action handled from now on to the recorder.
+    ///
+    /// Unless `include_payloads` is set, received data is replaced with zeroes of the same length.
+    pub fn start_recording(&mut self, recorder: SocketActionRecorder, include_payloads: bool) {
+        self.recording = Some(Recording {
+            recorder,
+            include_payloads,
+            started_at: Instant::now(),
+        });
+    }
+
+    pub fn stop_recording(&mut self) {
+        self.recording = None;
+    }
+
+    /// Serves new connections from a capture made with [`Sockets::start_recording`],
+    /// instead of opening real ones.
+    ///
+    /// Captured connections are replayed in the order the movie opens connections. With
+    /// `preserve_timing`, actions are delayed like they were when captured, otherwise they
+    /// are fed as fast as possible.
+    pub fn start_replay(&mut self, capture: Vec<(Duration, SocketAction)>, preserve_timing: bool) {
+        self.replay = Some(Replay::new(capture, preserve_timing));
+    }
+
+    pub fn stop_replay(&mut self) {
+        self.replay = None;
+    }
+
+    fn record_action(&mut self, action: &SocketAction) {
+        let Some(recording) = &mut self.recording else {
+            return;
+        };
+
+        let elapsed = recording.started_at.elapsed();
+        match action {
+            SocketAction::Data(handle, data) if !recording.include_payloads => {
+                let redacted = SocketAction::Data(*handle, vec![0; data.len()]);
+                (recording.recorder)(elapsed, &redacted);
+            }
+            action => (recording.recorder)(elapsed, action),
+        }
+    }
+
+    /// Queues the replayed actions that are due.
+    fn feed_replay(&mut self, now: Instant) {
+        let Some(replay) = &mut self.replay else {
+            return;
+        };
+
+        for action in replay.due_actions(now) {
+            // We own the receiver, so the channel can never be closed here.
+            let _ = self.sender.try_send(action);
+        }
+    }
+
+    pub fn set_navigation_drain_timeout(&mut self, timeout: Option<Duration>) {
+        self.navigation_drain_timeout = timeout;
+    }
+
+    /// Makes [`Sockets::close`] hand held back data to the backend, and keep the connection open
+    /// until the backend wrote it out, for at most `timeout`. With `None`, sockets are closed
+    /// right away.
+    ///
+    /// Either way, the socket is gone for the movie as soon as it's closed.
+    pub fn set_close_drain_timeout(&mut self, timeout: Option<Duration>) {
+        self.close_drain_timeout = timeout;
+    }
+
+    /// Makes an open AVM2 connection that is replaced by a new `connect` fire its close event on
+    /// the next update, before any event of the new connection, so listeners don't keep waiting
+    /// for data from it. This is off by default.
+    ///
+    /// Connections that are still pending never opened, so they're dropped without an event either way.
+    pub fn set_dispatch_close_on_replace(&mut self, enabled: bool) {
+        self.dispatch_close_on_replace = enabled;
+    }
+
+    /// Closes the connections of a socket that is about to connect again.
+    ///
+    /// If one of them was open and `dispatch_close` is set, the socket fires close on the next
+    /// update. Returns whether any connection was closed.
+    fn close_replaced(&mut self, target: SocketKind<'gc>, dispatch_close: bool) -> bool {
+        let was_open = self
+            .sockets
+            .values()
+            .any(|socket| socket.target.is_same_object(&target) && socket.connected.get());
+
+        let closed = self.close_by_target(target);
+        if was_open && dispatch_close {
+            self.replaced.push(target);
+        }
+        closed
+    }
+
+    pub fn connect_avm2(
+        &mut self,
+        backend: &mut dyn NavigatorBackend,
+        target: SocketObject<'gc>,
+        host: String,
+        port: u16,
+    ) {
+        self.connect_avm2_with_fallbacks(backend, target, host, port, vec![]);
+    }
+
+    /// Connects like [`Sockets::connect_avm2`], but lets the backend try the fallback hosts in order
+    /// when connecting to `host` fails.
+    ///
+    /// The socket fails to connect only once every host failed. Fallback hosts that aren't allowed
+    /// to be connected to are skipped; [`Sockets::remote_address`] tells which host was connected to.
+    pub fn connect_avm2_with_fallbacks(
+        &mut self,
+        backend: &mut dyn NavigatorBackend,
+        target: SocketObject<'gc>,
+        host: String,
+        port: u16,
+        fallback_hosts: Vec<(String, u16)>,
+    ) {
+        // As written in the AS3 docs, we are supposed to close the existing connection,
+        // when a new one is created. This is done first, so that a connection that is
+        // still pending can never fire its connect event after the new one was started.
+        self.close_replaced(SocketKind::Avm2(target), self.dispatch_close_on_replace);
+
+        let requested = (host.clone(), port);
+        let (host, port, allowed) = self.connect_target(&host, port);
+        let (sender, receiver) = unbounded();
+
+        let fallback_hosts = fallback_hosts
+            .into_iter()
+            .filter_map(|(host, port)| {
+                let (host, port, allowed) = self.connect_target(&host, port);
+                allowed.then_some((host, port))
+            })
+            .collect();
+        let options = SocketOptions {
+            no_delay: target.no_delay(),
+            fallback_hosts,
+            ..self.options_for(&host)
+        };
+        let mut socket = Socket::new(SocketKind::Avm2(target), host.clone(), sender);
+        socket.port = port;
+        #[cfg(feature = "socket_debug")]
+        {
+            socket.queued_bytes = Some(options.queued_bytes.clone());
+        }
+        let handle = self.sockets.insert(socket);
+        self.record_attempt(handle, requested);
+
+        let _span = socket_span(handle).entered();
+        tracing::debug!(host = %host, port, "connecting AVM2 socket");
+
+        if let Some(replay) = &mut self.replay {
+            // NOTE: While replaying, the capture provides all actions of this connection.
+            replay.attach(handle, receiver);
+        } else if allowed {
+            // NOTE: This call will send SocketAction::Connect to sender with connection status.
+            backend.connect_socket(
+                host,
+                port,
+                connect_timeout(target.timeout()),
+                options,
+                handle,
+                receiver,
+                self.sender.clone(),
+            );
+        } else {
+            self.block_connection(handle);
+        }
+
+        target.set_close_reason(None);
+        target.set_handle(handle);
+    }
+
+    pub fn connect_avm1(
+        &mut self,
+        backend: &mut dyn NavigatorBackend,
+        target: Avm1Object<'gc>,
+        host: String,
+        port: u16,
+    ) {
+        let (sender, receiver) = unbounded();
+
+        let xml_socket = match XmlSocket::cast(target.into()) {
+            Some(xml_socket) => xml_socket,
+            None => return,
+        };
+
+        // NOTE: AS2 docs don't specify what happens when connect is called with open connection,
+        //       but we will close the existing connection anyway, before starting the new one.
+        //       Anything it received is dropped too, as it's not part of the new connection, and
+        //       an open one fires onClose, as the script would otherwise keep waiting for its data.
+        if self.close_replaced(SocketKind::Avm1(target), true) {
+            xml_socket.read_buffer().clear();
+        }
+
+        let requested = (host.clone(), port);
+        let (host, port, allowed) = self.connect_target(&host, port);
+
+        let options = self.options_for(&host);
+        let mut socket = Socket::new(SocketKind::Avm1(target), host.clone(), sender);
+        socket.port = port;
+        #[cfg(feature = "socket_debug")]
+        {
+            socket.queued_bytes = Some(options.queued_bytes.clone());
+        }
+        let handle = self.sockets.insert(socket);
+        self.record_attempt(handle, requested);
+
+        let _span = socket_span(handle).entered();
+        tracing::debug!(host = %host, port, "connecting AVM1 socket");
+
+        if let Some(replay) = &mut self.replay {
+            // NOTE: While replaying, the capture provides all actions of this connection.
+            replay.attach(handle, receiver);
+        } else if allowed {
+            // NOTE: This call will send SocketAction::Connect to sender with connection status.
+            backend.connect_socket(
+                host,
+                port,
+                connect_timeout(xml_socket.timeout()),
+                options,
+                handle,
+                receiver,
+                self.sender.clone(),
+            );
+        } else {
+            self.block_connection(handle);
+        }
+
+        xml_socket.set_handle(handle);
+    }
+
+    /// Points an AVM1 `XMLSocket` at a connection that fails right away, as the movie asked for
+    /// an invalid port.
+    ///
+    /// Like any failed connection, this fires `onConnect(false)` on the next update.
+    pub fn reject_avm1(&mut self, target: Avm1Object<'gc>, host: String) {
+        let Some(xml_socket) = XmlSocket::cast(target.into()) else {
+            return;
+        };
+
+        if self.close_replaced(SocketKind::Avm1(target), true) {
+            xml_socket.read_buffer().clear();
+        }
+
+        // NOTE: No backend ever sees this socket, so nothing listens on the other end of this.
+        let (sender, _receiver) = unbounded();
+        let socket = Socket::new(SocketKind::Avm1(target), host.clone(), sender);
+        let handle = self.sockets.insert(socket);
+        self.record_attempt(handle, (host, 0));
+
+        let _span = socket_span(handle).entered();
+        tracing::warn!("Refusing to connect to an invalid port");
+
+        let _ = self.sender.try_send(SocketAction::Connect(
+            handle,
+            ConnectionState::Failed(Some("Invalid port".to_string())),
+        ));
+
+        xml_socket.set_handle(handle);
+    }
+
+    /// Connects the AS object behind an open socket again, replacing the old connection.
+    ///
+    /// The old connection is closed and the object is pointed at a fresh socket, so its handle never
+    /// outlives the connection it belongs to. It fires a new connect event once the backend connects.
+    /// Returns the new handle, or `None` if the given socket isn't open.
+    pub fn reconnect(
+        &mut self,
+        backend: &mut dyn NavigatorBackend,
+        handle: SocketHandle,
+        host: String,
+        port: u16,
+    ) -> Option<SocketHandle> {
+        let target = self.sockets.get(handle)?.target;
+
+        // NOTE: Connecting closes the existing connection of the object.
+        match target {
+            SocketKind::Avm2(target) => {
+                self.connect_avm2(backend, target, host, port);
+                target.handle()
+            }
+            SocketKind::Avm1(target) => {
+                self.connect_avm1(backend, target, host, port);
+                XmlSocket::cast(target.into()).and_then(|xml_socket| xml_socket.handle())
+            }
+        }
+    }
+
+    /// Hands an open AVM2 socket over to another `Socket` object, keeping the connection alive.
+    ///
+    /// This is meant for debugging tools that reload scripts. Buffered data moves over to the new
+    /// object, and the old one no longer refers to the connection. If the socket is connected,
+    /// the new object gets a connect event of its own.
+    /// Returns `false` if the socket doesn't exist or belongs to an object of another kind.
+    pub fn rebind_avm2(&mut self, handle: SocketHandle, new_target: SocketObject<'gc>) -> bool {
+        self.rebind_target(handle, SocketKind::Avm2(new_target))
+    }
+
+    /// Hands an open AVM1 socket over to another `XMLSocket` object, like [`Sockets::rebind_avm2`].
+    pub fn rebind_avm1(&mut self, handle: SocketHandle, new_target: Avm1Object<'gc>) -> bool {
+        self.rebind_target(handle, SocketKind::Avm1(new_target))
+    }
+
+    fn rebind_target(&mut self, handle: SocketHandle, new_target: SocketKind<'gc>) -> bool {
+        let Some(socket) = self.sockets.get_mut(handle) else {
+            return false;
+        };
+
+        let previous_handle = match (socket.target, new_target) {
+            (SocketKind::Avm2(old), SocketKind::Avm2(new)) => {
+                let read_buffer = std::mem::take(&mut *old.read_buffer());
+                let partial_record = std::mem::take(&mut *old.partial_record());
+                let write_buffer = std::mem::take(&mut *old.write_buffer());
+                new.read_buffer().extend(read_buffer);
+                new.partial_record().extend(partial_record);
+                new.write_buffer().extend(write_buffer);
+
+                old.take_handle();
+                new.set_close_reason(None);
+                new.set_handle(handle)
+            }
+            (SocketKind::Avm1(old), SocketKind::Avm1(new)) => {
+                let (Some(old), Some(new)) =
+                    (XmlSocket::cast(old.into()), XmlSocket::cast(new.into()))
+                else {
+                    return false;
+                };
+
+                let read_buffer = std::mem::take(&mut *old.read_buffer());
+                new.read_buffer().extend(read_buffer);
+
+                old.take_handle();
+                new.set_handle(handle)
+            }
+            _ => {
+                tracing::warn!("Refusing to rebind a socket to an object of another kind");
+                return false;
+            }
+        };
+
+        socket.target = new_target;
+        let connected = socket.connected.get();
+
+        let _span = socket_span(handle).entered();
+        tracing::debug!("rebound socket to a new object");
+
+        // NOTE: The new object may have had a connection of its own, which nothing refers to anymore.
+        if let Some(previous_handle) = previous_handle.filter(|previous| *previous != handle) {
+            self.close(previous_handle);
+        }
+
+        if connected {
+            // NOTE: The new object hasn't seen the connection being established, so tell it now.
+            let _ = self
+                .sender
+                .try_send(SocketAction::Connect(handle, ConnectionState::Connected));
+        }
+
+        true
+    }
+
+    /// Fails a connection that was rejected by policy, without involving the backend.
+    fn block_connection(&self, handle: SocketHandle) {
+        tracing::warn!("Connection was blocked by policy");
+
+        let _ = self.sender.try_send(SocketAction::Connect(
+            handle,
+            ConnectionState::SecurityBlocked,
+        ));
+    }
+
+    /// Returns a future that resolves once the backend reports whether the given socket connected.
+    ///
+    /// This is meant for host code: it resolves during [`Sockets::update_sockets`], alongside the
+    /// usual events. It resolves to `None` if the socket is closed before that, or doesn't exist.
+    pub fn connection_result(
+        &self,
+        handle: SocketHandle,
+    ) -> impl Future<Output = Option<ConnectionState>> + 'static {
+        let (sender, receiver) = async_channel::bounded(1);
+
+        if let Some(socket) = self.sockets.get(handle) {
+            match socket.connection_state.borrow().clone() {
+                Some(state) => {
+                    let _ = sender.try_send(state);
+                }
+                None => socket.connect_waiters.borrow_mut().push(sender),
+            }
+        }
+
+        async move { receiver.recv().await.ok() }
+    }
+
+    /// Checks whether a connection to `host` and `port` can be made, without a socket for the movie.
+    ///
+    /// The connection is closed again right away, and nothing is dispatched to the movie. The
+    /// returned future resolves to the outcome reported by the backend, which makes this suitable
+    /// for preflight checks by host code.
+    pub fn probe(
+        &self,
+        backend: &mut dyn NavigatorBackend,
+        host: String,
+        port: u16,
+        timeout: Duration,
+    ) -> impl Future<Output = ConnectionState> + 'static {
+        let (sender, receiver) = unbounded();
+
+        if self.is_connect_allowed(&host, port) {
+            let (command_sender, command_receiver) = unbounded();
+            tracing::debug!(host = %host, port, "probing connection");
+
+            let options = self.options_for(&host);
+            backend.connect_socket(
+                host,
+                port,
+                timeout,
+                options,
+                SocketHandle::default(),
+                command_receiver,
+                sender,
+            );
+
+            // NOTE: Without a sender for commands, the backend closes the connection once it's made.
+            drop(command_sender);
+        } else {
+            let _ = sender.try_send(SocketAction::Connect(
+                SocketHandle::default(),
+                ConnectionState::SecurityBlocked,
+            ));
+        }
+
+        async move {
+            while let Ok(action) = receiver.recv().await {
+                if let SocketAction::Connect(_, state) = action {
+                    return state;
+                }
+            }
+
+            // The backend gave up without ever reporting the outcome.
+            ConnectionState::Failed(None)
+        }
+    }
+
+    fn resolve_connection(&mut self, handle: SocketHandle, state: ConnectionState) {
+        if let Some(attempt) = self
+            .connection_history
+            .iter_mut()
+            .rev()
+            .find(|attempt| attempt.handle == handle)
+        {
+            attempt.outcome = Some(state.clone());
+        }
+
+        if let Some(socket) = self.sockets.get(handle) {
+            for waiter in socket.connect_waiters.take() {
+                let _ = waiter.try_send(state.clone());
+            }
+
+            socket.connection_state.replace(Some(state));
+        }
+    }
+
+    /// Returns where the given socket is in its lifecycle.
+    pub fn connection_status(&self, handle: SocketHandle) -> SocketStatus {
+        let Some(socket) = self.sockets.get(handle) else {
+            return SocketStatus::Closed;
+        };
+
+        match socket.connection_state.borrow().clone() {
+            None => SocketStatus::Connecting,
+            Some(ConnectionState::Connected) => SocketStatus::Connected,
+            Some(state) => SocketStatus::Failed(state),
+        }
+    }
+
+    pub fn is_connected(&self, handle: SocketHandle) -> bool {
+        if let Some(socket) = self.sockets.get(handle) {
+            socket.connected.get()
+        } else {
+            false
+        }
+    }
+
+    /// Returns the path MTU of the connection, when the backend was able to report it.
+    pub fn path_mtu(&self, handle: SocketHandle) -> Option<u16> {
+        self.sockets
+            .get(handle)
+            .and_then(|socket| socket.path_mtu.get())
+    }
+
+    /// Returns the address the connection was made to, when the backend was able to report it.
+    pub fn remote_address(&self, handle: SocketHandle) -> Option<SocketAddr> {
+        self.sockets
+            .get(handle)
+            .and_then(|socket| socket.remote_address.get())
+    }
+
+    /// Returns whether the connection was made over IPv4 or IPv6, when the backend reported the
+    /// address it connected to.
+    ///
+    /// Backends that try several addresses report the one that won, so this tells which family
+    /// a dual-stack connection ended up using.
+    pub fn remote_family(&self, handle: SocketHandle) -> Option<IpFamily> {
+        self.remote_address(handle)
+            .map(|address| IpFamily::from(address.ip()))
+    }
+
+    /// Returns the local address the connection is bound to, when the backend was able to report it.
+    pub fn local_address(&self, handle: SocketHandle) -> Option<SocketAddr> {
+        self.sockets
+            .get(handle)
+            .and_then(|socket| socket.local_address.get())
+    }
+
+    /// Sends data over the given socket, or holds it back if the send rate limit was reached.
+    ///
+    /// Fails with [`SendError::NoSuchSocket`] if the handle doesn't belong to a socket, such as
+    /// after it was closed. Whether the socket is connected yet isn't checked: data sent while
+    /// still connecting goes to the backend all the same.
+    pub fn send(&mut self, handle: SocketHandle, data: Vec<u8>) -> Result<(), SendError> {
+        let Some(socket) = self.sockets.get(handle) else {
+            return Err(SendError::NoSuchSocket);
+        };
+
+        if self.send_rate_limiter.is_none() {
+            self.send_now(handle, data);
+            return Ok(());
+        }
+
+        socket.held_back.borrow_mut().extend(data);
+        self.send_held_back(handle);
+        Ok(())
+    }
+
+    /// Like [`Sockets::send`], for callers that only have borrowed data.
+    ///
+    /// The data is copied once, straight into the message for the backend or the held back data.
+    pub fn send_slice(&mut self, handle: SocketHandle, data: &[u8]) -> Result<(), SendError> {
+        let Some(socket) = self.sockets.get(handle) else {
+            return Err(SendError::NoSuchSocket);
+        };
+
+        if self.send_rate_limiter.is_none() {
+            self.send_now(handle, data.to_vec());
+            return Ok(());
+        }
+
+        socket.held_back.borrow_mut().extend_from_slice(data);
+        self.send_held_back(handle);
+        Ok(())
+    }
+
+    /// Sends a message over an AVM1 `XMLSocket`, terminated the same way received messages are.
+    pub fn send_string(&mut self, handle: SocketHandle, message: &str) -> Result<(), SendError> {
+        let mut data = Vec::with_capacity(message.len() + 1);
+        data.extend_from_slice(message.as_bytes());
+        data.push(XML_SOCKET_DELIMITER);
+
+        self.send(handle, data)
+    }
+
+    /// Sends everything written to the write buffer of an AVM2 socket.
+    ///
+    /// Data over the send rate limit stays in the write buffer, and is sent on later frames.
+    pub fn flush(&mut self, handle: SocketHandle) {
+        let Some(socket) = self.sockets.get(handle) else {
+            return;
+        };
+        let SocketKind::Avm2(target) = socket.target else {
+            return;
+        };
+
+        socket.flushed_bytes.set(target.write_buffer().len());
+        self.send_flushed(handle);
+    }
+
+    /// Flushes every AVM2 socket, and hands the data held back on every socket to the backend.
+    ///
+    /// Embedders can call this at the end of a tick, so that sends don't wait for the next update.
+    /// The send rate limit still applies, so data over it is sent on later frames.
+    pub fn flush_all(&mut self) {
+        let handles: Vec<_> = self.sockets.keys().collect();
+
+        for handle in handles {
+            // NOTE: Held back data was sent first, so it goes out first.
+            self.send_held_back(handle);
+            self.flush(handle);
+        }
+    }
+
+    /// Sends as much of the data held back by the send rate limit as it currently allows.
+    fn send_held_back(&mut self, handle: SocketHandle) {
+        let Some(socket) = self.sockets.get(handle) else {
+            return;
+        };
+
+        let mut held_back = socket.held_back.borrow_mut();
+        let allowed = match &mut self.send_rate_limiter {
+            Some(limiter) => limiter.take(held_back.len(), Instant::now()),
+            None => held_back.len(),
+        };
+        if allowed == 0 {
+            return;
+        }
+        let data = held_back.drain(..allowed).collect();
+        drop(held_back);
+
+        self.send_now(handle, data);
+    }
+
+    /// Sends as much of the flushed part of an AVM2 write buffer as the send rate limit allows.
+    fn send_flushed(&mut self, handle: SocketHandle) {
+        let Some(socket) = self.sockets.get(handle) else {
+            return;
+        };
+        let SocketKind::Avm2(target) = socket.target else {
+            return;
+        };
+
+        let flushed = socket.flushed_bytes.get();
+        if flushed > 0 && socket.sender.borrow().is_closed() {
+            // NOTE: The data would be lost, so it stays in the write buffer where it can still be read.
+            tracing::debug!(
+                ?handle,
+                len = flushed,
+                "not flushing, as the backend is gone"
+            );
+            return;
+        }
+        let allowed = match &mut self.send_rate_limiter {
+            Some(limiter) => limiter.take(flushed, Instant::now()),
+            None => flushed,
+        };
+        if allowed == 0 {
+            return;
+        }
+        socket.flushed_bytes.set(flushed - allowed);
+        let data = target.write_buffer().drain(..allowed).collect();
+
+        self.send_now(handle, data);
+    }
+
+    /// Retries sending the data that was held back by the send rate limit, in handle order.
+    fn send_throttled_data(&mut self) {
+        let handles: Vec<_> = self.sockets.keys().collect();
+        for handle in handles {
+            self.send_flushed(handle);
+            self.send_held_back(handle);
+        }
+    }
+
+    fn send_now(&mut self, handle: SocketHandle, data: Vec<u8>) {
+        let _span = socket_span(handle).entered();
+        tracing::debug!(len = data.len(), "sending data");
+        tracing::trace!(data = ?data, "sent payload");
+
+        let data = match self.sockets.get(handle) {
+            Some(socket) => socket.encode(data),
+            None => data,
+        };
+        self.count_sent(handle, &data);
+
+        match self.max_send_chunk_size {
+            Some(max) if data.len() > max.get() => {
+                tracing::debug!(max = max.get(), "splitting oversized send");
+
+                for chunk in data.chunks(max.get()) {
+                    self.send_command(handle, SocketCommand::Send(chunk.to_vec()));
+                }
+            }
+            _ => self.send_command(handle, SocketCommand::Send(data)),
+        }
+    }
+
+    /// Sends latency-sensitive data, which the backend writes before any data queued with [`Sockets::send`].
+    ///
+    /// Data sent through either method keeps its order relative to other data sent the same way.
+    /// Priority data is never held back by the send rate limit, but still counts towards it.
+    pub fn send_priority(&mut self, handle: SocketHandle, data: Vec<u8>) -> Result<(), SendError> {
+        let Some(socket) = self.sockets.get(handle) else {
+            return Err(SendError::NoSuchSocket);
+        };
+
+        let _span = socket_span(handle).entered();
+        tracing::debug!(len = data.len(), "sending priority data");
+        tracing::trace!(data = ?data, "sent payload");
+
+        if let Some(limiter) = &mut self.send_rate_limiter {
+            limiter.take_all(data.len(), Instant::now());
+        }
+
+        let data = socket.encode(data);
+        self.count_sent(handle, &data);
+        self.send_command(handle, SocketCommand::SendPriority(data));
+        Ok(())
+    }
+
+    fn count_sent(&self, handle: SocketHandle, data: &[u8]) {
+        if let Some(socket) = self.sockets.get(handle) {
+            socket.write_drained.set(false);
+            socket
+                .bytes_sent
+                .set(socket.bytes_sent.get() + data.len() as u64);
+
+            socket
+                .unacked_bytes
+                .set(socket.unacked_bytes.get() + data.len() as u64);
+
+            #[cfg(feature = "socket_debug")]
+            if let Some(queued_bytes) = &socket.queued_bytes {
+                queued_bytes.add(data.len() as u64);
+            }
+
+            let mut stats = socket.stats.get();
+            stats.bytes_sent += data.len() as u64;
+            stats.messages_sent += 1;
+            socket.stats.set(stats);
+
+            // NOTE: Only the first of several sends is timed, as we can't tell which one
+            //       the next received data answers.
+            if self.track_latency && socket.awaiting_data_since.get().is_none() {
+                socket.awaiting_data_since.set(Some(Instant::now()));
+            }
+        }
+    }
+
+    fn count_received(&self, handle: SocketHandle, len: usize) {
+        if let Some(socket) = self.sockets.get(handle) {
+            let mut stats = socket.stats.get();
+            stats.bytes_received += len as u64;
+            stats.messages_received += 1;
+            stats.peak_buffered_bytes =
+                stats.peak_buffered_bytes.max(socket.buffered_bytes() + len);
+            socket.stats.set(stats);
+
+            if let Some(sent_at) = socket.awaiting_data_since.take() {
+                let mut latency = socket.latency.get();
+                latency.record(sent_at.elapsed());
+                socket.latency.set(latency);
+            }
+        }
+    }
+
+    /// Returns whether everything sent over the given socket was flushed to the OS, as far as
+    /// the backend reported it with [`SocketAction::WriteDrained`].
+    ///
+    /// This lets host code close a socket without truncating data that is still in flight.
+    /// Returns `false` for sockets that don't exist, or whose backend never reports it.
+    pub fn is_write_drained(&self, handle: SocketHandle) -> bool {
+        self.sockets
+            .get(handle)
+            .is_some_and(|socket| socket.write_drained.get())
+    }
+
+    /// Returns how many bytes handed to the backend it hasn't written out yet.
+    ///
+    /// This shows whether data is stuck on our side of the connection. Returns `None` for sockets
+    /// that don't exist, or whose backend wasn't given a count to keep up to date.
+    #[cfg(feature = "socket_debug")]
+    pub fn queued_bytes(&self, handle: SocketHandle) -> Option<u64> {
+        self.sockets
+            .get(handle)
+            .and_then(|socket| socket.queued_bytes.as_ref())
+            .map(QueuedBytes::get)
+    }
+
+    /// Installs a codec that transforms everything sent and received over the given socket,
+    /// or removes it with `None`.
+    ///
+    /// Data that was already buffered or handed to the backend isn't transformed.
+    pub fn set_codec(&mut self, handle: SocketHandle, codec: Option<Box<dyn SocketCodec>>) {
+        if let Some(socket) = self.sockets.get(handle) {
+            socket.codec.replace(codec);
+        }
+    }
+
+    /// Returns the traffic counters of the given socket.
+    pub fn stats(&self, handle: SocketHandle) -> Option<SocketStats> {
+        self.sockets.get(handle).map(|socket| socket.stats.get())
+    }
+
+    /// Returns up to `len` bytes from the front of the read buffer of the given socket,
+    /// without consuming them.
+    ///
+    /// Returns `None` if the socket doesn't exist.
+    pub fn peek_read(&self, handle: SocketHandle, len: usize) -> Option<Vec<u8>> {
+        self.sockets
+            .get(handle)
+            .map(|socket| socket.peek_read_buffer(len))
+    }
+
+    /// The latency histogram of the given socket, which stays empty unless enabled with
+    /// [`Sockets::set_latency_tracking`].
+    pub fn latency_stats(&self, handle: SocketHandle) -> Option<LatencyStats> {
+        self.sockets.get(handle).map(|socket| socket.latency.get())
+    }
+
+    /// Zeroes the traffic counters of the given socket, leaving the connection and its buffers alone.
+    pub fn reset_stats(&mut self, handle: SocketHandle) {
+        if let Some(socket) = self.sockets.get(handle) {
+            socket.stats.set(SocketStats::default());
+        }
+    }
+
+    /// Asks the backend to enable or disable Nagle's algorithm on a live connection.
+    pub fn set_no_delay(&mut self, handle: SocketHandle, no_delay: bool) {
+        self.send_command(handle, SocketCommand::SetNoDelay(no_delay));
+    }
+
+    /// Signals end-of-stream to the peer, without closing the read half of the connection.
+    ///
+    /// Data keeps arriving until the peer closes the connection, which fires the usual close event.
+    pub fn shutdown_write(&mut self, handle: SocketHandle) {
+        if let Some(socket) = self.sockets.get(handle) {
+            socket.write_shut_down.set(true);
+        }
+        self.send_command(handle, SocketCommand::ShutdownWrite);
+    }
+
+    /// Returns whether data can still be sent over the given socket, which isn't the case once
+    /// its write half was shut down, or before it connected and after it closed.
+    pub fn can_write(&self, handle: SocketHandle) -> bool {
+        self.sockets
+            .get(handle)
+            .is_some_and(|socket| socket.connected.get() && !socket.write_shut_down.get())
+    }
+
+    fn send_command(&mut self, handle: SocketHandle, command: SocketCommand) {
+        if let Some(Socket { sender, .. }) = self.sockets.get_mut(handle) {
+            #[cfg(feature = "socket_debug")]
+            let Some(command) = (match &mut self.simulated_lag {
+                Some(lag) => lag.delay_command(handle, &sender.borrow(), command, Instant::now()),
+                None => Some(command),
+            }) else {
+                return;
+            };
+
+            // We use an unbounded socket, so this should only ever error if the channel is closed
+            // (the receiver was dropped)
+            if let Err(e) = sender.borrow().try_send(command) {
+                match e.into_inner() {
+                    SocketCommand::Send(data) | SocketCommand::SendPriority(data) => {
+                        tracing::debug!(
+                            ?handle,
+                            len = data.len(),
+                            "dropped data, as the backend is gone"
+                        );
+                    }
+                    command => tracing::error!("Failed to send command to socket: {:?}", command),
+                }
+            }
+        }
+    }
+
+    /// Closes all sockets when navigating away from the current movie.
+    ///
+    /// If a navigation drain timeout is configured, this flushes the write buffers first.
+    pub fn close_for_navigation(context: &mut UpdateContext<'_, 'gc>) {
+        match context.sockets.navigation_drain_timeout {
+            Some(timeout) => Self::drain_for_navigation(context, timeout),
+            None => context.sockets.close_all(),
+        }
+    }
+
+    /// Flushes the write buffers of all sockets and closes them once their backend wrote
+    /// everything out, waiting at most `timeout` for that before closing them anyway.
+    ///
+    /// The old movie may never be updated again, so this handles the backends' actions itself,
+    /// where it can block. On wasm, where it can't, the connections are closed by later calls
+    /// to [`Sockets::update_sockets`] instead, still within the timeout.
+    pub fn drain_for_navigation(context: &mut UpdateContext<'_, 'gc>, timeout: Duration) {
+        let sockets = &mut context.sockets;
+        for (_, socket) in sockets.sockets.iter() {
+            if let SocketKind::Avm2(target) = socket.target {
+                // NOTE: This makes the close hand over the whole buffer, as if it was flushed.
+                socket.flushed_bytes.set(target.write_buffer().len());
+            }
+        }
+
+        for handle in sockets.active_handles() {
+            sockets.close_after_flush(handle, timeout);
+            sockets.discard_deferred_actions(handle);
+        }
+
+        #[cfg(not(target_family = "wasm"))]
+        {
+            let deadline = Instant::now() + timeout;
+            while !context.sockets.draining.is_empty() {
+                let now = Instant::now();
+                if now >= deadline {
+                    break;
+                }
+                Self::update_sockets_blocking(context, deadline - now);
+            }
+            context.sockets.close_undrained_sockets(deadline);
+        }
+    }
+
+    /// Returns how many actions the backends queued that weren't handled by [`Sockets::update_sockets`] yet.
+    ///
+    /// A steadily growing count means the movie isn't keeping up with the network.
+    pub fn pending_action_count(&self) -> usize {
+        self.receiver.len() + self.deferred_actions.len()
+    }
+
+    /// Remembers a connection the movie asked for, forgetting the oldest one if too many are remembered.
+    fn record_attempt(&mut self, handle: SocketHandle, (host, port): (String, u16)) {
+        if self.connection_history.len() == MAX_CONNECTION_HISTORY {
+            self.connection_history.pop_front();
+        }
+
+        self.connection_history.push_back(ConnectionAttempt {
+            handle,
+            host,
+            port,
+            outcome: None,
+        });
+    }
+
+    /// Lists the latest connections the movie asked for during this session, oldest first, so the
+    /// embedder can show what the movie is doing on the network.
+    ///
+    /// Only the latest 256 attempts are remembered.
+    pub fn connection_history(&self) -> impl Iterator<Item = &ConnectionAttempt> {
+        self.connection_history.iter()
+    }
+
+    /// Lists the handles of all open sockets.
+    pub fn active_handles(&self) -> Vec<SocketHandle> {
+        self.sockets.keys().collect()
+    }
+
+    /// Lists all open sockets with their object kind, host and tag, for debugging tools.
+    ///
+    /// Sockets whose backend already stopped are skipped, as they are being torn down.
+    pub fn describe_sockets(
+        &self,
+    ) -> impl Iterator<Item = (SocketHandle, SocketKindDescriptor, &str, Option<&str>)> + '_ {
+        self.sockets
+            .iter()
+            .filter(|(_, socket)| !socket.sender.borrow().is_closed())
+            .map(|(handle, socket)| {
+                (
+                    handle,
+                    socket.target.descriptor(),
+                    socket.host.as_str(),
+                    socket.tag.as_deref(),
+                )
+            })
+    }
+
+    /// Labels the given socket for debugging tools (like "chat server"), or removes the label with `None`.
+    ///
+    /// The tag is listed by [`Sockets::describe_sockets`], and has no effect on the connection.
+    pub fn set_tag(&mut self, handle: SocketHandle, tag: Option<String>) {
+        if let Some(socket) = self.sockets.get_mut(handle) {
+            socket.tag = tag;
+        }
+    }
+
+    pub fn close_all(&mut self) {
+        for handle in self.active_handles() {
+            self.close(handle);
+        }
+    }
+
+    pub fn close(&mut self, handle: SocketHandle) {
+        let _span = socket_span(handle).entered();
+        tracing::debug!("closing socket");
+
+        match self.close_drain_timeout {
+            Some(timeout) => self.close_after_flush(handle, timeout),
+            None => {
+                if let Some(socket) = self.sockets.remove(handle) {
+                    Self::close_internal(socket);
+                }
+            }
+        }
+
+        // NOTE: When content reconnects, the old socket is closed while the backend may have
+        //       already queued actions for it. Those are dropped once they're handled, as the
+        //       handle no longer resolves, so stale partial data can never end up in the read
+        //       buffer meant for the new connection.
+        self.discard_deferred_actions(handle);
+    }
+
+    /// Closes every connection the given AS object still owns, like when it's discarded, so the
+    /// caller doesn't need to track handles.
+    ///
+    /// Returns whether any connection was closed.
+    pub(crate) fn close_by_target(&mut self, target: SocketKind<'gc>) -> bool {
+        let handles: Vec<_> = self
+            .sockets
+            .iter()
+            .filter(|(_, socket)| socket.target.is_same_object(&target))
+            .map(|(handle, _)| handle)
+            .collect();
+
+        for handle in &handles {
+            self.close(*handle);
+        }
+        !handles.is_empty()
+    }
+
+    /// Resets the connection (sending a TCP RST where the backend supports it) instead of closing
+    /// it gracefully. Pending writes are dropped, rather than drained.
+    ///
+    /// If `dispatch_close` is set, the close event fires on the next update with
+    /// [`CloseReason::Aborted`], like for sockets closed because of a policy.
+    pub fn abort(&mut self, handle: SocketHandle, dispatch_close: bool) {
+        let _span = socket_span(handle).entered();
+        tracing::debug!("aborting socket");
+
+        let Some(target) = self.sockets.get(handle).map(|socket| socket.target) else {
+            return;
+        };
+
+        self.send_command(handle, SocketCommand::Abort);
+        self.discard_deferred_actions(handle);
+
+        if dispatch_close {
+            if let Some(socket) = self.sockets.get(handle) {
+                socket.connected.set(false);
+                socket.connected_at.set(None);
+            }
+            Self::clear_buffers(target);
+
+            let _ = self
+                .sender
+                .try_send(SocketAction::Close(handle, CloseReason::Aborted));
+        } else {
+            if let Some(socket) = self.sockets.remove(handle) {
+                Self::close_internal(socket);
+            }
+
+            if let SocketKind::Avm2(target) = target {
+                target.set_close_reason(Some(CloseReason::Aborted));
+            }
+        }
+    }
+
+    /// Hands the data held back by the send rate limit to the backend, and asks it to acknowledge
+    /// once everything was written out. Until then, or until `timeout` passes, the connection is
+    /// kept open, while the socket is already closed for the movie.
+    fn close_after_flush(&mut self, handle: SocketHandle, timeout: Duration) {
+        let Some(socket) = self.sockets.get(handle) else {
+            return;
+        };
+
+        if socket.connected.get() {
+            let flushed = match socket.target {
+                SocketKind::Avm2(target) => {
+                    let flushed = socket.flushed_bytes.replace(0);
+                    target.write_buffer().drain(..flushed).collect()
+                }
+                SocketKind::Avm1(_) => vec![],
+            };
+            let held_back = std::mem::take(&mut *socket.held_back.borrow_mut());
+
+            // NOTE: The connection is going away, so the rate limit doesn't matter anymore.
+            //       Held back data was sent first, so it goes out first.
+            for data in [held_back, flushed] {
+                if !data.is_empty() {
+                    self.send_now(handle, data);
+                }
+            }
+
+            self.send_command(handle, SocketCommand::Flush);
+        }
+
+        let Some(socket) = self.sockets.remove(handle) else {
+            return;
+        };
+
+        // NOTE: Connections that never opened have nothing to flush.
+        if !socket.connected.get() || socket.sender.borrow().is_closed() {
+            Self::close_internal(socket);
+            return;
+        }
+
+        let Socket { sender, target, .. } = socket;
+        self.draining.push(DrainingSocket {
+            handle,
+            sender: sender.into_inner(),
+            deadline: Instant::now() + timeout,
+        });
+        Self::clear_buffers(target);
+    }
+
+    /// Closes the connection of a socket whose backend acknowledged the flush asked for when it was
+    /// closed. Returns `false` if the socket wasn't waiting for that.
+    fn finish_draining(&mut self, handle: SocketHandle) -> bool {
+        let draining = self.draining.len();
+        // NOTE: Dropping the sender makes the backend close the connection.
+        self.draining.retain(|socket| socket.handle != handle);
+        self.draining.len() != draining
+    }
+
+    /// Closes the connections of sockets whose backends didn't acknowledge a flush in time.
+    fn close_undrained_sockets(&mut self, now: Instant) {
+        self.draining.retain(|socket| {
+            if now < socket.deadline {
+                return true;
+            }
+
+            let _span = socket_span(socket.handle).entered();
+            tracing::warn!("Backend didn't write out all data in time, closing the socket anyway");
+            false
+        });
+    }
+
+    /// Removes the actions held over from previous updates that target the given handle.
+    ///
+    /// Actions still in the channel are left alone, as they're dropped when handled anyway.
+    fn discard_deferred_actions(&mut self, handle: SocketHandle) {
+        self.deferred_actions
+            .retain(|action| action.handle() != handle);
+    }
+
+    fn close_internal(socket: Socket) {
+        let Socket { sender, target, .. } = socket;
+
+        drop(sender); // NOTE: By dropping the sender, the reading task will close automatically.
+
+        Self::clear_buffers(target);
+    }
+
+    /// Clears the buffers of a socket whose connection was closed.
+    fn clear_buffers(target: SocketKind) {
+        match target {
+            SocketKind::Avm1(target) => {
+                let target = XmlSocket::cast(target.into()).expect("target should be XmlSocket");
+
+                target.read_buffer().clear();
+            }
+            SocketKind::Avm2(target) => {
+                target.read_buffer().clear();
+                target.write_buffer().clear();
+            }
+        }
+    }
+
+    /// Force-closes all connections that have been open for longer than the maximum lifetime.
+    ///
+    /// The sockets are closed through the action queue, so their close events fire on the next update.
+    fn close_expired_sockets(&mut self, now: Instant) {
+        let Some(max_lifetime) = self.max_connection_lifetime else {
+            return;
+        };
+
+        for (handle, socket) in self.sockets.iter() {
+            let Some(connected_at) = socket.connected_at.get() else {
+                continue;
+            };
+
+            if now.duration_since(connected_at) > max_lifetime {
+                let _span = socket_span(handle).entered();
+                tracing::warn!("Socket exceeded its maximum lifetime, closing it");
+
+                // Make sure we only queue the close once.
+                socket.connected_at.set(None);
+                let _ = self
+                    .sender
+                    .try_send(SocketAction::Close(handle, CloseReason::Policy));
+            }
+        }
+    }
+
+    /// Makes room for `incoming` more buffered bytes on the given socket, if a memory budget is set.
+    ///
+    /// Sockets chosen to make room are closed through the action queue, like expired ones.
+    /// Returns `false` if the incoming data should be refused.
+    fn reserve_buffer_space(&mut self, handle: SocketHandle, incoming: usize) -> bool {
+        let Some(budget) = self.memory_budget else {
+            return true;
+        };
+
+        let usage: Vec<_> = self
+            .sockets
+            .iter()
+            .map(|(handle, socket)| (handle, socket.buffered_bytes()))
+            .collect();
+        let total: usize = usage.iter().map(|(_, buffered)| buffered).sum();
 
-/// Manages the collection of Sockets.
-pub struct Sockets<'gc> {
-    sockets: SlotMap<SocketHandle, Socket<'gc>>,
+        if total + incoming <= budget {
+            return true;
+        }
 
-    receiver: Receiver<SocketAction>,
-    sender: Sender<SocketAction>,
-}
+        let to_close = match &mut self.on_budget_exceeded {
+            Some(callback) => callback(total, &usage),
+            None => usage
+                .iter()
+                .max_by_key(|(_, buffered)| *buffered)
+                .map(|(handle, _)| vec![*handle])
+                .unwrap_or_default(),
+        };
 
-unsafe impl<'gc> Collect for Sockets<'gc> {
-    fn trace(&self, cc: &gc_arena::Collection) {
-        for (_, socket) in self.sockets.iter() {
-            socket.trace(cc)
+        if to_close.is_empty() {
+            let _span = socket_span(handle).entered();
+            tracing::warn!(
+                total,
+                incoming,
+                budget,
+                "Socket memory budget exceeded, refusing data"
+            );
+            return false;
+        }
+
+        for closed in &to_close {
+            if let Some(socket) = self.sockets.get(*closed) {
+                let _span = socket_span(*closed).entered();
+                tracing::warn!("Socket memory budget exceeded, closing socket");
+
+                socket.clear_read_buffer();
+                let _ = self
+                    .sender
+                    .try_send(SocketAction::Close(*closed, CloseReason::Policy));
+            }
         }
+
+        !to_close.contains(&handle)
     }
-}
 
-impl<'gc> Sockets<'gc> {
-    pub fn empty() -> Self {
-        let (sender, receiver) = unbounded();
+    /// Records that an action was deliberately not acted upon, so it is never silently dropped.
+    fn log_ignored_action(&mut self, kind: &'static str, reason: &str) {
+        if self.ignored_action_kinds.insert(kind) {
+            tracing::warn!(action = kind, reason, "ignoring socket action");
+        } else {
+            tracing::debug!(action = kind, reason, "ignoring socket action");
+        }
+    }
 
-        Self {
-            sockets: SlotMap::with_key(),
-            receiver,
-            sender,
+    pub fn update_sockets(context: &mut UpdateContext<'_, 'gc>) {
+        context.sockets.close_expired_sockets(Instant::now());
+        context.sockets.close_undrained_sockets(Instant::now());
+        context.sockets.feed_replay(Instant::now());
+        context.sockets.send_throttled_data();
+        #[cfg(feature = "socket_debug")]
+        if let Some(lag) = &mut context.sockets.simulated_lag {
+            lag.send_due(Instant::now());
         }
+
+        Self::dispatch_replaced_closes(context);
+        let actions = context.sockets.next_actions();
+        Self::handle_actions(context, actions);
     }
 
-    pub fn connect_avm2(
-        &mut self,
-        backend: &mut dyn NavigatorBackend,
-        target: SocketObject<'gc>,
-        host: String,
-        port: u16,
-    ) {
-        let (sender, receiver) = unbounded();
+    /// Like [`Sockets::update_sockets`], but first waits up to `timeout` for the backends to send an action.
+    ///
+    /// This is meant for test harnesses and headless players that step sockets synchronously.
+    /// Returns whether any action was handled.
+    #[cfg(not(target_family = "wasm"))]
+    pub fn update_sockets_blocking(
+        context: &mut UpdateContext<'_, 'gc>,
+        timeout: Duration,
+    ) -> bool {
+        /// How long to sleep at first while waiting for the backends, doubled on every miss.
+        const MIN_BACKOFF: Duration = Duration::from_micros(50);
+        /// The longest sleep between polls, which bounds how late a replayed action is fed.
+        const MAX_BACKOFF: Duration = Duration::from_millis(1);
 
-        let socket = Socket::new(SocketKind::Avm2(target), sender);
-        let handle = self.sockets.insert(socket);
+        context.sockets.close_expired_sockets(Instant::now());
+        context.sockets.close_undrained_sockets(Instant::now());
+        Self::dispatch_replaced_closes(context);
 
-        // NOTE: This call will send SocketAction::Connect to sender with connection status.
-        backend.connect_socket(
-            host,
-            port,
-            Duration::from_millis(target.timeout().into()),
-            handle,
-            receiver,
-            self.sender.clone(),
-        );
+        let deadline = Instant::now() + timeout;
+        let mut backoff = MIN_BACKOFF;
+        while context.sockets.deferred_actions.is_empty() {
+            context.sockets.feed_replay(Instant::now());
+
+            match context.sockets.receiver.try_recv() {
+                Ok(action) => context.sockets.deferred_actions.push_back(action),
+                Err(TryRecvError::Closed) => {
+                    context.sockets.recreate_action_channel();
+                    return false;
+                }
+                Err(TryRecvError::Empty) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return false;
+                    }
 
-        if let Some(existing_handle) = target.set_handle(handle) {
-            // As written in the AS3 docs, we are supposed to close the existing connection,
-            // when a new one is created.
-            self.close(existing_handle)
+                    std::thread::sleep(backoff.min(deadline - now));
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
         }
+
+        // Everything else that's already queued is handled without waiting any further.
+
+        let actions = context.sockets.next_actions();
+        Self::handle_actions(context, actions);
+        true
     }
 
-    pub fn connect_avm1(
-        &mut self,
-        backend: &mut dyn NavigatorBackend,
-        target: Avm1Object<'gc>,
-        host: String,
-        port: u16,
-    ) {
-        let (sender, receiver) = unbounded();
+    /// Takes the actions to handle in this update, holding back those over `max_events_per_update`.
+    fn next_actions(&mut self) -> Vec<SocketAction> {
+        let mut actions: Vec<_> = self.deferred_actions.drain(..).collect();
+        actions.extend(self.take_actions());
 
-        let xml_socket = match XmlSocket::cast(target.into()) {
-            Some(xml_socket) => xml_socket,
-            None => return,
+        let Some(max) = self.max_events_per_update.map(NonZeroUsize::get) else {
+            return actions;
         };
+        if actions.len() <= max {
+            return actions;
+        }
 
-        let socket = Socket::new(SocketKind::Avm1(target), sender);
-        let handle = self.sockets.insert(socket);
-
-        // NOTE: This call will send SocketAction::Connect to sender with connection status.
-        backend.connect_socket(
-            host,
-            port,
-            Duration::from_millis(xml_socket.timeout().into()),
-            handle,
-            receiver,
-            self.sender.clone(),
-        );
+        // NOTE: We take the first action of every socket, then the second one and so on.
+        //       That way, every socket handles a prefix of its actions, keeping their order.
+        let mut ranks = HashMap::new();
+        let mut turns: Vec<_> = actions
+            .iter()
+            .enumerate()
+            .map(|(index, action)| {
+                let rank = ranks.entry(action.handle()).or_insert(0);
+                *rank += 1;
+                (*rank, index)
+            })
+            .collect();
+        turns.sort_unstable();
 
-        if let Some(existing_handle) = xml_socket.set_handle(handle) {
-            // NOTE: AS2 docs don't specify what happens when connect is called with open connection,
-            //       but we will close the existing connection anyway.
-            self.close(existing_handle)
+        let mut selected = vec![false; actions.len()];
+        for (_, index) in &turns[..max] {
+            selected[*index] = true;
         }
-    }
 
-    pub fn is_connected(&self, handle: SocketHandle) -> bool {
-        if let Some(socket) = self.sockets.get(handle) {
-            socket.connected.get()
-        } else {
-            false
+        let mut handled = Vec::with_capacity(max);
+        for (action, selected) in actions.into_iter().zip(selected) {
+            if selected {
+                handled.push(action);
+            } else {
+                self.deferred_actions.push_back(action);
+            }
         }
+
+        tracing::debug!(
+            deferred = self.deferred_actions.len(),
+            "deferring socket actions to the next update"
+        );
+
+        handled
     }
 
-    pub fn send(&mut self, handle: SocketHandle, data: Vec<u8>) {
-        if let Some(Socket { sender, .. }) = self.sockets.get_mut(handle) {
-            // We use an unbounded socket, so this should only ever error if the channel is closed
-            // (the receiver was dropped)
-            if let Err(e) = sender.borrow().try_send(data) {
-                tracing::error!("Failed to send data to socket: {:?}", e);
+    /// Takes all actions the backends queued so far.
+    fn take_actions(&mut self) -> Vec<SocketAction> {
+        let mut actions = vec![];
+
+        loop {
+            match self.receiver.try_recv() {
+                Ok(action) => actions.push(action),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Closed) => {
+                    self.recreate_action_channel();
+                    break;
+                }
             }
         }
-    }
 
-    pub fn close_all(&mut self) {
-        for (_, socket) in self.sockets.drain() {
-            Self::close_internal(socket);
+        #[cfg(feature = "socket_debug")]
+        if let Some(lag) = &mut self.simulated_lag {
+            return lag.delay_actions(actions, Instant::now());
         }
+
+        actions
     }
 
-    pub fn close(&mut self, handle: SocketHandle) {
-        if let Some(socket) = self.sockets.remove(handle) {
-            Self::close_internal(socket);
-        }
+    /// Replaces the channel backends report actions over, after it was closed.
+    ///
+    /// We own both ends, so this only happens if a backend closed its end explicitly, which closes
+    /// the channel for every socket. Open sockets can't report anything anymore, so they are closed,
+    /// while new connections work again.
+    fn recreate_action_channel(&mut self) {
+        tracing::error!("Socket action channel was closed, closing all sockets and recreating it");
+
+        let (sender, receiver) = unbounded();
+        self.sender = sender;
+        self.receiver = receiver;
+
+        self.close_all();
     }
 
-    fn close_internal(socket: Socket) {
-        let Socket {
-            sender,
-            target,
-            connected: _,
-        } = socket;
+    /// Holds back actions that arrived before the backend reported whether the connection
+    /// succeeded, so that scripts never see data before the connect event.
+    ///
+    /// Returns the action if it should be handled right away.
+    fn hold_early_action(&self, action: SocketAction) -> Option<SocketAction> {
+        if matches!(action, SocketAction::Connect(..)) {
+            return Some(action);
+        }
 
-        drop(sender); // NOTE: By dropping the sender, the reading task will close automatically.
+        let Some(socket) = self.sockets.get(action.handle()) else {
+            return Some(action);
+        };
 
-        // Clear the buffers if the connection was closed.
-        match target {
-            SocketKind::Avm1(target) => {
-                let target = XmlSocket::cast(target.into()).expect("target should be XmlSocket");
+        let mut early_actions = socket.early_actions.borrow_mut();
+        // NOTE: Once data is held back, everything after it has to wait too, to keep the order.
+        let is_early = socket.connection_state.borrow().is_none()
+            && (matches!(action, SocketAction::Data(..)) || !early_actions.is_empty());
 
-                target.read_buffer().clear();
-            }
-            SocketKind::Avm2(target) => {
-                target.read_buffer().clear();
-                target.write_buffer().clear();
-            }
+        if is_early {
+            tracing::debug!(
+                action = action.kind(),
+                "holding back socket action until connect"
+            );
+            early_actions.push(action);
+            None
+        } else {
+            Some(action)
         }
     }
 
-    pub fn update_sockets(context: &mut UpdateContext<'_, 'gc>) {
-        let mut actions = vec![];
+    fn take_early_actions(&self, handle: SocketHandle) -> Vec<SocketAction> {
+        self.sockets
+            .get(handle)
+            .map(|socket| socket.early_actions.take())
+            .unwrap_or_default()
+    }
 
-        while let Ok(action) = context.sockets.receiver.try_recv() {
-            actions.push(action)
+    /// Fires the close events of open connections that were replaced since the last update.
+    fn dispatch_replaced_closes(context: &mut UpdateContext<'_, 'gc>) {
+        for target in std::mem::take(&mut context.sockets.replaced) {
+            match target {
+                SocketKind::Avm2(target) => {
+                    let mut activation = Avm2Activation::from_nothing(context.reborrow());
+
+                    let event_type = target.event_type(activation.gc(), "close");
+                    let close_evt =
+                        EventObject::bare_default_event(&mut activation.context, event_type);
+                    Avm2::dispatch_event(&mut activation.context, close_evt, target.into());
+                }
+                SocketKind::Avm1(target) => {
+                    let mut activation = Avm1Activation::from_stub(
+                        context.reborrow(),
+                        ActivationIdentifier::root("[XMLSocket]"),
+                    );
+
+                    call_xml_socket_handler(target, "onClose", &[], &mut activation);
+                }
+            }
         }
+    }
 
+    fn handle_actions(context: &mut UpdateContext<'_, 'gc>, actions: Vec<SocketAction>) {
         for action in actions {
+            let _span = socket_span(action.handle()).entered();
+
+            let Some(action) = context.sockets.hold_early_action(action) else {
+                continue;
+            };
+
+            let kind = action.kind();
+            tracing::debug!(action = kind, "handling socket action");
+
+            context.sockets.record_action(&action);
+
+            if let SocketAction::Connect(handle, state) = &action {
+                context.sockets.resolve_connection(*handle, state.clone());
+
+                if *state != ConnectionState::Connected {
+                    // The connection never existed, so neither did anything it received.
+                    let dropped = context.sockets.take_early_actions(*handle);
+                    if !dropped.is_empty() {
+                        context
+                            .sockets
+                            .log_ignored_action("data", "connection attempt failed");
+                    }
+                }
+            }
+
             match action {
                 SocketAction::Connect(handle, ConnectionState::Connected) => {
                     let target = match context.sockets.sockets.get(handle) {
                         Some(socket) => {
                             socket.connected.set(true);
+                            socket.connected_at.set(Some(Instant::now()));
+                            socket.reserve_read_buffer(context.sockets.read_buffer_capacity);
                             socket.target
                         }
                         // Socket must have been closed before we could send event.
@@ -228,8 +3067,11 @@ impl<'gc> Sockets<'gc> {
                         SocketKind::Avm2(target) => {
                             let mut activation = Avm2Activation::from_nothing(context.reborrow());
 
-                            let connect_evt =
-                                EventObject::bare_default_event(&mut activation.context, "connect");
+                            let event_type = target.event_type(activation.gc(), "connect");
+                            let connect_evt = EventObject::bare_default_event(
+                                &mut activation.context,
+                                event_type,
+                            );
                             Avm2::dispatch_event(
                                 &mut activation.context,
                                 connect_evt,
@@ -242,19 +3084,28 @@ impl<'gc> Sockets<'gc> {
                                 ActivationIdentifier::root("[XMLSocket]"),
                             );
 
-                            let _ = target.call_method(
-                                "onConnect".into(),
+                            call_xml_socket_handler(
+                                target,
+                                "onConnect",
                                 &[true.into()],
                                 &mut activation,
-                                ExecutionReason::Special,
                             );
                         }
                     }
+
+                    let early_actions = context.sockets.take_early_actions(handle);
+                    if !early_actions.is_empty() {
+                        Self::handle_actions(context, early_actions);
+                    }
                 }
                 SocketAction::Connect(
                     handle,
-                    ConnectionState::Failed | ConnectionState::TimedOut,
+                    state @ (ConnectionState::Failed(_)
+                    | ConnectionState::TimedOut
+                    | ConnectionState::DnsError),
                 ) => {
+                    context.sockets.report_diagnostic(handle, &state);
+
                     let target = match context.sockets.sockets.get(handle) {
                         Some(socket) => socket.target,
                         // Socket must have been closed before we could send event.
@@ -265,21 +3116,25 @@ impl<'gc> Sockets<'gc> {
                         SocketKind::Avm2(target) => {
                             let mut activation = Avm2Activation::from_nothing(context.reborrow());
 
-                            let io_error_evt = activation
-                                .avm2()
-                                .classes()
-                                .ioerrorevent
-                                .construct(
-                                    &mut activation,
-                                    &[
-                                        "ioError".into(),
-                                        false.into(),
-                                        false.into(),
-                                        "Error #2031: Socket Error.".into(),
-                                        2031.into(),
-                                    ],
-                                )
-                                .expect("IOErrorEvent should be constructed");
+                            let message =
+                                AvmString::new_utf8(activation.gc(), io_error_message(&state));
+
+                            let event_type = target.event_type(activation.gc(), "ioError");
+                            let class = activation.avm2().classes().ioerrorevent;
+                            let Some(io_error_evt) = construct_socket_event(
+                                &mut activation,
+                                class,
+                                &[
+                                    event_type.into(),
+                                    false.into(),
+                                    false.into(),
+                                    message.into(),
+                                    2031.into(),
+                                ],
+                                handle,
+                            ) else {
+                                continue;
+                            };
 
                             Avm2::dispatch_event(
                                 &mut activation.context,
@@ -294,16 +3149,27 @@ impl<'gc> Sockets<'gc> {
                                 ActivationIdentifier::root("[XMLSocket]"),
                             );
 
-                            let _ = target.call_method(
-                                "onConnect".into(),
+                            if state == ConnectionState::DnsError {
+                                // NOTE: This is enforced in connect_avm1() function.
+                                let xml_socket = XmlSocket::cast(target.into())
+                                    .expect("target should be XmlSocket");
+
+                                xml_socket.set_last_error(Some("dns"));
+                            }
+
+                            call_xml_socket_handler(
+                                target,
+                                "onConnect",
                                 &[false.into()],
                                 &mut activation,
-                                ExecutionReason::Special,
                             );
                         }
                     }
                 }
-                SocketAction::Data(handle, mut data) => {
+                SocketAction::Connect(
+                    handle,
+                    ConnectionState::SecurityDenied | ConnectionState::SecurityBlocked,
+                ) => {
                     let target = match context.sockets.sockets.get(handle) {
                         Some(socket) => socket.target,
                         // Socket must have been closed before we could send event.
@@ -314,31 +3180,122 @@ impl<'gc> Sockets<'gc> {
                         SocketKind::Avm2(target) => {
                             let mut activation = Avm2Activation::from_nothing(context.reborrow());
 
-                            let bytes_loaded = data.len();
-                            target.read_buffer().extend(data);
+                            let event_type = target.event_type(activation.gc(), "securityError");
+                            let class = activation.avm2().classes().securityerrorevent;
+                            let Some(security_error_evt) = construct_socket_event(
+                                &mut activation,
+                                class,
+                                &[
+                                    event_type.into(),
+                                    false.into(),
+                                    false.into(),
+                                    "Error #2048: Security sandbox violation.".into(),
+                                    2048.into(),
+                                ],
+                                handle,
+                            ) else {
+                                continue;
+                            };
 
-                            let progress_evt = activation
-                                .avm2()
-                                .classes()
-                                .progressevent
-                                .construct(
-                                    &mut activation,
-                                    &[
-                                        "socketData".into(),
-                                        false.into(),
-                                        false.into(),
-                                        bytes_loaded.into(),
-                                        //NOTE: bytesTotal is not used by socketData event.
-                                        0.into(),
-                                    ],
-                                )
-                                .expect("ProgressEvent should be constructed");
+                            Avm2::dispatch_event(
+                                &mut activation.context,
+                                security_error_evt,
+                                target.into(),
+                            );
+                        }
+                        // NOTE: AVM1 has no security error event, so this is reported as a failed connection.
+                        SocketKind::Avm1(target) => {
+                            let mut activation = Avm1Activation::from_stub(
+                                context.reborrow(),
+                                ActivationIdentifier::root("[XMLSocket]"),
+                            );
+
+                            call_xml_socket_handler(
+                                target,
+                                "onConnect",
+                                &[false.into()],
+                                &mut activation,
+                            );
+                        }
+                    }
+                }
+                SocketAction::Data(handle, mut data) => {
+                    tracing::trace!(len = data.len(), data = ?data, "received payload");
+
+                    if data.is_empty() {
+                        // NOTE: Backends report EOF with SocketAction::Close, so an empty
+                        //       frame carries nothing worth an event, or counting as a message.
+                        tracing::trace!("ignoring empty payload");
+                        continue;
+                    }
+
+                    let target = match context.sockets.sockets.get(handle) {
+                        // NOTE: An aborted socket stays around until its close event fires, but
+                        //       anything the backend received before the abort is stale.
+                        Some(socket) if !socket.connected.get() => {
+                            context
+                                .sockets
+                                .log_ignored_action(kind, "socket was aborted");
+                            continue;
+                        }
+                        Some(socket) => {
+                            if let Some(codec) = &mut *socket.codec.borrow_mut() {
+                                data = codec.decode(data);
+
+                                if data.is_empty() {
+                                    // The codec is waiting for the rest of a frame.
+                                    continue;
+                                }
+                            }
+
+                            socket.target
+                        }
+                        // Socket must have been closed before we could send event.
+                        None => continue,
+                    };
+
+                    if !context.sockets.reserve_buffer_space(handle, data.len()) {
+                        continue;
+                    }
+
+                    context.sockets.count_received(handle, data.len());
+
+                    match target {
+                        SocketKind::Avm2(target) => {
+                            let mut activation = Avm2Activation::from_nothing(context.reborrow());
+
+                            let bytes_loaded = target.receive(data);
+                            if bytes_loaded == 0 {
+                                // Not even one whole record has arrived yet.
+                                continue;
+                            }
+
+                            let event_type = target.event_type(activation.gc(), "socketData");
+                            let class = activation.avm2().classes().progressevent;
+                            let Some(progress_evt) = construct_socket_event(
+                                &mut activation,
+                                class,
+                                &[
+                                    event_type.into(),
+                                    false.into(),
+                                    false.into(),
+                                    bytes_loaded.into(),
+                                    //NOTE: bytesTotal is not used by socketData event,
+                                    //      unless the application declared what it expects.
+                                    target.expected_total().unwrap_or(0).into(),
+                                ],
+                                handle,
+                            ) else {
+                                continue;
+                            };
 
                             Avm2::dispatch_event(
                                 &mut activation.context,
                                 progress_evt,
                                 target.into(),
                             );
+
+                            Self::check_read_watermark(&mut activation, handle, target);
                         }
                         SocketKind::Avm1(target) => {
                             let mut activation = Avm1Activation::from_stub(
@@ -350,57 +3307,158 @@ impl<'gc> Sockets<'gc> {
                             let xml_socket =
                                 XmlSocket::cast(target.into()).expect("target should be XmlSocket");
 
-                            // Check if the current received packet includes a null byte.
-                            if let Some((index, _)) = data.iter().enumerate().find(|(_, &b)| b == 0)
-                            {
-                                // Received payload contains a null byte, so take data from sockets read buffer and append message data ontop.
-                                let mut buffer = xml_socket
-                                    .read_buffer()
-                                    .drain(..)
-                                    .chain(data.drain(..index))
-                                    .collect::<Vec<_>>();
-
-                                // Now we loop to check for more null bytes.
-                                loop {
-                                    // Remove null byte.
-                                    data.drain(..1);
-
-                                    // Create message from the buffer.
-                                    let message =
-                                        AvmString::new_utf8_bytes(activation.gc(), &buffer);
-
-                                    // Call the event handler.
-                                    let _ = target.call_method(
-                                        "onData".into(),
-                                        &[message.into()],
-                                        &mut activation,
-                                        ExecutionReason::Special,
-                                    );
-
-                                    // Check if we have another null byte in the same payload.
-                                    if let Some((index, _)) =
-                                        data.iter().enumerate().find(|(_, &b)| b == 0)
-                                    {
-                                        // Because data in XmlSocket::read_buffer() has already been consumed
-                                        // we do not need to access it again.
-                                        buffer = data.drain(..index).collect::<Vec<_>>();
-                                    } else {
-                                        // No more messages in the payload, so exit the loop.
-                                        break;
-                                    }
-                                }
+                            let messages = {
+                                let mut buffer = xml_socket.read_buffer();
+                                let delimiter = xml_socket.delimiter();
+                                // NOTE: What is buffered contains no whole delimiter, but it
+                                //       may end with the start of one.
+                                let scan_from = buffer
+                                    .len()
+                                    .saturating_sub(delimiter.len().saturating_sub(1));
+                                buffer.extend(data);
+                                extract_messages(&mut buffer, &delimiter, scan_from)
+                            };
 
-                                // Check if we have leftover bytes.
-                                if !data.is_empty() {
-                                    // We had leftover bytes, so append them to XmlSocket internal read buffer,
-                                    // to be used when the next packet arrives.
-                                    xml_socket.read_buffer().extend(data);
-                                }
+                            for message in messages {
+                                let message = AvmString::new_utf8_bytes(activation.gc(), &message);
+
+                                call_xml_socket_handler(
+                                    target,
+                                    "onData",
+                                    &[message.into()],
+                                    &mut activation,
+                                );
+                            }
+
+                            // NOTE: A server that never sends a null byte would make us buffer forever,
+                            //       so we give up on the connection once the pending message is too large.
+                            if xml_socket.read_buffer().len() > xml_socket.max_message_size() {
+                                tracing::warn!(
+                                    "XMLSocket message exceeded {} bytes, closing the connection",
+                                    xml_socket.max_message_size()
+                                );
+
+                                xml_socket.set_last_error(Some("message too large"));
+                                activation.context.sockets.close(handle);
+
+                                call_xml_socket_handler(target, "onClose", &[], &mut activation);
                             }
                         }
                     }
                 }
-                SocketAction::Close(handle) => {
+                SocketAction::PathMtu(handle, mtu) => match context.sockets.sockets.get(handle) {
+                    Some(socket) => socket.path_mtu.set(Some(mtu)),
+                    None => context
+                        .sockets
+                        .log_ignored_action(kind, "socket is already closed"),
+                },
+                SocketAction::LocalAddress(handle, address) => {
+                    match context.sockets.sockets.get(handle) {
+                        Some(socket) => socket.local_address.set(Some(address)),
+                        None => context
+                            .sockets
+                            .log_ignored_action(kind, "socket is already closed"),
+                    }
+                }
+                SocketAction::RemoteAddress(handle, address) => {
+                    match context.sockets.sockets.get(handle) {
+                        Some(socket) => socket.remote_address.set(Some(address)),
+                        None => context
+                            .sockets
+                            .log_ignored_action(kind, "socket is already closed"),
+                    }
+                }
+                SocketAction::OutputProgress(handle, bytes_pending) => {
+                    let (target, bytes_total) = match context.sockets.sockets.get(handle) {
+                        Some(socket) => (socket.target, socket.bytes_sent.get()),
+                        None => {
+                            context
+                                .sockets
+                                .log_ignored_action(kind, "socket is already closed");
+                            continue;
+                        }
+                    };
+
+                    let target = match target {
+                        SocketKind::Avm2(target) => target,
+                        SocketKind::Avm1(_) => {
+                            context
+                                .sockets
+                                .log_ignored_action(kind, "XMLSocket has no equivalent event");
+                            continue;
+                        }
+                    };
+
+                    let mut activation = Avm2Activation::from_nothing(context.reborrow());
+
+                    let event_type = target.event_type(activation.gc(), "outputProgress");
+                    let class = activation.avm2().classes().outputprogressevent;
+                    let Some(progress_evt) = construct_socket_event(
+                        &mut activation,
+                        class,
+                        &[
+                            event_type.into(),
+                            false.into(),
+                            false.into(),
+                            (bytes_pending as f64).into(),
+                            (bytes_total as f64).into(),
+                        ],
+                        handle,
+                    ) else {
+                        continue;
+                    };
+
+                    Avm2::dispatch_event(&mut activation.context, progress_evt, target.into());
+                }
+                SocketAction::Sent(handle, written) => {
+                    let Some(socket) = context.sockets.sockets.get(handle) else {
+                        context
+                            .sockets
+                            .log_ignored_action(kind, "socket is already closed");
+                        continue;
+                    };
+
+                    let unacked = socket.unacked_bytes.get();
+                    socket.unacked_bytes.set(unacked.saturating_sub(written));
+
+                    if unacked > 0 && socket.unacked_bytes.get() == 0 {
+                        if let Some(callback) = &mut context.sockets.on_send_queue_drained {
+                            callback(handle);
+                        }
+                    }
+                }
+                SocketAction::Flushed(handle) => {
+                    if !context.sockets.finish_draining(handle) {
+                        context
+                            .sockets
+                            .log_ignored_action(kind, "socket isn't waiting for a flush");
+                    }
+                }
+                SocketAction::WriteDrained(handle) => {
+                    let Some(socket) = context.sockets.sockets.get(handle) else {
+                        context
+                            .sockets
+                            .log_ignored_action(kind, "socket is already closed");
+                        continue;
+                    };
+
+                    // NOTE: Data sent since the backend reported this is still on its way.
+                    if socket.unacked_bytes.get() == 0 {
+                        socket.write_drained.set(true);
+                    }
+                }
+                SocketAction::Resolved(handle, addresses) => {
+                    match context.sockets.sockets.get(handle) {
+                        Some(socket) => {
+                            let host = socket.host.clone();
+                            context.sockets.cache_resolution(host, addresses);
+                        }
+                        None => context
+                            .sockets
+                            .log_ignored_action(kind, "socket is already closed"),
+                    }
+                }
+                SocketAction::Close(handle, reason) => {
                     let target = match context.sockets.sockets.remove(handle) {
                         Some(socket) => {
                             socket.connected.set(false);
@@ -416,10 +3474,18 @@ impl<'gc> Sockets<'gc> {
 
                             // Clear the buffers if the connection was closed.
                             target.read_buffer().clear();
+                            target.partial_record().clear();
                             target.write_buffer().clear();
 
-                            let close_evt =
-                                EventObject::bare_default_event(&mut activation.context, "close");
+                            // NOTE: The close event itself carries no reason in Flash,
+                            //       so we expose it through a Ruffle-specific property instead.
+                            target.set_close_reason(Some(reason));
+
+                            let event_type = target.event_type(activation.gc(), "close");
+                            let close_evt = EventObject::bare_default_event(
+                                &mut activation.context,
+                                event_type,
+                            );
                             Avm2::dispatch_event(&mut activation.context, close_evt, target.into());
                         }
                         SocketKind::Avm1(target) => {
@@ -434,16 +3500,63 @@ impl<'gc> Sockets<'gc> {
 
                             socket.read_buffer().clear();
 
-                            let _ = target.call_method(
-                                "onClose".into(),
-                                &[],
-                                &mut activation,
-                                ExecutionReason::Special,
-                            );
+                            if reason != CloseReason::Graceful {
+                                socket.set_last_error(Some(reason.as_str()));
+                            }
+
+                            call_xml_socket_handler(target, "onClose", &[], &mut activation);
                         }
                     }
                 }
             }
         }
+
+        context.sockets.apply_read_backpressure();
+    }
+
+    /// Dispatches `readWatermark` if the read buffer of an AVM2 socket just reached the watermark.
+    ///
+    /// The socket is only rearmed once the buffer is drained below half the watermark, so a
+    /// buffer hovering around it doesn't dispatch an event on every read.
+    fn check_read_watermark(
+        activation: &mut Avm2Activation<'_, 'gc>,
+        handle: SocketHandle,
+        target: SocketObject<'gc>,
+    ) {
+        let Some(watermark) = activation.context.sockets.read_watermark else {
+            return;
+        };
+        let Some(socket) = activation.context.sockets.sockets.get(handle) else {
+            // The socketData listener closed the socket.
+            return;
+        };
+
+        let buffered = target.read_buffer().len();
+        if buffered < watermark.get() / 2 {
+            socket.above_read_watermark.set(false);
+            return;
+        }
+        if buffered < watermark.get() || socket.above_read_watermark.replace(true) {
+            return;
+        }
+
+        let event_type = target.event_type(activation.gc(), "readWatermark");
+        let class = activation.avm2().classes().progressevent;
+        let Some(progress_evt) = construct_socket_event(
+            activation,
+            class,
+            &[
+                event_type.into(),
+                false.into(),
+                false.into(),
+                buffered.into(),
+                watermark.get().into(),
+            ],
+            handle,
+        ) else {
+            return;
+        };
+
+        Avm2::dispatch_event(&mut activation.context, progress_evt, target.into());
     }
 }
@@ -0,0 +1,181 @@
+//! Stands in for the socket machinery when the `socket` feature is disabled.
+//!
+//! Movies can still create sockets, but every connection is refused like a blocking security
+//! policy would, so content sees the same events it sees when a connection isn't allowed.
+
+use super::{
+    call_xml_socket_handler, construct_socket_event, SendError, SocketHandle, SocketKind,
+    SocketStatus,
+};
+use crate::{
+    avm1::{Activation as Avm1Activation, ActivationIdentifier, Object as Avm1Object},
+    avm2::{object::SocketObject, Activation as Avm2Activation, Avm2},
+    backend::navigator::NavigatorBackend,
+    context::UpdateContext,
+};
+use gc_arena::Collect;
+use slotmap::SlotMap;
+use std::net::SocketAddr;
+
+/// Refuses every connection, without ever involving the backend.
+#[derive(Collect)]
+#[collect(no_drop)]
+pub struct Sockets<'gc> {
+    /// Objects whose connection was refused, which are told so on the next update.
+    refused: Vec<SocketKind<'gc>>,
+
+    /// The handle the events of refused connections are constructed with.
+    ///
+    /// It comes from a slot map of its own, so it can't be mistaken for the handle of a socket.
+    #[collect(require_static)]
+    refused_handle: SocketHandle,
+}
+
+impl<'gc> Sockets<'gc> {
+    pub fn empty() -> Self {
+        let mut handles = SlotMap::with_key();
+
+        Self {
+            refused: vec![],
+            refused_handle: handles.insert(()),
+        }
+    }
+
+    pub fn connect_avm2(
+        &mut self,
+        _backend: &mut dyn NavigatorBackend,
+        target: SocketObject<'gc>,
+        _host: String,
+        _port: u16,
+    ) {
+        tracing::warn!("Refusing to connect, as socket support is disabled");
+        self.refused.push(SocketKind::Avm2(target));
+    }
+
+    pub fn connect_avm1(
+        &mut self,
+        _backend: &mut dyn NavigatorBackend,
+        target: Avm1Object<'gc>,
+        _host: String,
+        _port: u16,
+    ) {
+        tracing::warn!("Refusing to connect, as socket support is disabled");
+        self.refused.push(SocketKind::Avm1(target));
+    }
+
+    /// Like any refused connection, this fires `onConnect(false)` on the next update.
+    pub fn reject_avm1(&mut self, target: Avm1Object<'gc>, _host: String) {
+        self.refused.push(SocketKind::Avm1(target));
+    }
+
+    /// Every connection is refused already, so the ports don't matter.
+    pub fn set_restricted_ports(&mut self, _ports: impl IntoIterator<Item = u16>) {}
+
+    pub fn connection_status(&self, _handle: SocketHandle) -> SocketStatus {
+        SocketStatus::Closed
+    }
+
+    pub fn is_connected(&self, _handle: SocketHandle) -> bool {
+        false
+    }
+
+    pub fn can_write(&self, _handle: SocketHandle) -> bool {
+        false
+    }
+
+    pub fn local_address(&self, _handle: SocketHandle) -> Option<SocketAddr> {
+        None
+    }
+
+    pub fn send_string(&mut self, _handle: SocketHandle, _message: &str) -> Result<(), SendError> {
+        Err(SendError::NoSuchSocket)
+    }
+
+    pub fn flush(&mut self, _handle: SocketHandle) {}
+
+    pub fn close(&mut self, _handle: SocketHandle) {}
+
+    pub fn close_for_navigation(context: &mut UpdateContext<'_, 'gc>) {
+        context.sockets.refused.clear();
+    }
+
+    pub fn update_sockets(context: &mut UpdateContext<'_, 'gc>) {
+        let refused_handle = context.sockets.refused_handle;
+
+        for target in std::mem::take(&mut context.sockets.refused) {
+            match target {
+                SocketKind::Avm2(target) => {
+                    let mut activation = Avm2Activation::from_nothing(context.reborrow());
+
+                    let event_type = target.event_type(activation.gc(), "securityError");
+                    let class = activation.avm2().classes().securityerrorevent;
+                    let Some(security_error_evt) = construct_socket_event(
+                        &mut activation,
+                        class,
+                        &[
+                            event_type.into(),
+                            false.into(),
+                            false.into(),
+                            "Error #2048: Security sandbox violation.".into(),
+                            2048.into(),
+                        ],
+                        refused_handle,
+                    ) else {
+                        continue;
+                    };
+
+                    Avm2::dispatch_event(
+                        &mut activation.context,
+                        security_error_evt,
+                        target.into(),
+                    );
+                }
+                // NOTE: AVM1 has no security error event, so this is reported as a failed connection.
+                SocketKind::Avm1(target) => {
+                    let mut activation = Avm1Activation::from_stub(
+                        context.reborrow(),
+                        ActivationIdentifier::root("[XMLSocket]"),
+                    );
+
+                    call_xml_socket_handler(target, "onConnect", &[false.into()], &mut activation);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::avm1::test_utils::with_avm;
+    use crate::socket::test_support::SocketTestHarness;
+
+    #[test]
+    fn connections_fail_with_a_security_error() {
+        with_avm(19, |activation, _root| {
+            let mut activation = Avm2Activation::from_nothing(activation.context.reborrow());
+            let target = activation
+                .avm2()
+                .classes()
+                .socket
+                .construct(&mut activation, &[])
+                .expect("Socket should be constructed")
+                .as_socket()
+                .expect("Socket should be a SocketObject");
+            SocketTestHarness::record_avm2(&mut activation, target);
+
+            let UpdateContext {
+                sockets, navigator, ..
+            } = &mut activation.context;
+            sockets.connect_avm2(*navigator, target, "localhost".to_string(), 8080);
+            assert_eq!(target.handle(), None);
+
+            Sockets::update_sockets(&mut activation.context);
+            assert_eq!(
+                SocketTestHarness::take_events(),
+                ["securityError(Error #2048: Security sandbox violation.)"]
+            );
+            Ok(())
+        });
+    }
+}
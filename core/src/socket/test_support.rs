@@ -0,0 +1,186 @@
+//! Helpers shared by the socket tests, with and without the `socket` feature.
+
+#[cfg(feature = "socket")]
+use crate::avm1::{
+    function::{Executable, FunctionObject},
+    Activation as Avm1Activation, Object as Avm1Object, TObject as Avm1TObject, Value as Avm1Value,
+};
+use crate::avm2::globals::flash::events::event_dispatcher::add_event_listener;
+use crate::avm2::method::{Method, NativeMethodImpl};
+use crate::avm2::object::{FunctionObject as Avm2FunctionObject, SocketObject};
+use crate::avm2::scope::ScopeChain;
+use crate::avm2::{
+    Activation as Avm2Activation, Error as Avm2Error, Object as Avm2Object, TObject as Avm2TObject,
+    Value as Avm2Value,
+};
+use crate::string::AvmString;
+use std::cell::RefCell;
+
+/// Adds a native function as a listener for events of the given type, so tests can observe them.
+pub(super) fn add_native_event_listener<'gc>(
+    activation: &mut Avm2Activation<'_, 'gc>,
+    target: Avm2Object<'gc>,
+    event_type: &'static str,
+    listener: NativeMethodImpl,
+) -> Result<(), Avm2Error<'gc>> {
+    let method = Method::from_builtin(listener, "nativeEventListener", activation.gc());
+    let scope = ScopeChain::new(activation.domain());
+    let listener = Avm2FunctionObject::from_function(activation, method, scope)?;
+    let event_type = AvmString::new_utf8(activation.gc(), event_type);
+
+    add_event_listener(
+        activation,
+        target,
+        &[
+            event_type.into(),
+            Avm2Object::from(listener).into(),
+            false.into(),
+            0.into(),
+        ],
+    )?;
+    Ok(())
+}
+
+/// Sets an AVM1 event handler, like `onData`, on the given object.
+#[cfg(feature = "socket")]
+pub(super) fn set_avm1_handler<'gc>(
+    activation: &mut Avm1Activation<'_, 'gc>,
+    object: Avm1Object<'gc>,
+    name: &'static str,
+    handler: crate::avm1::function::NativeFunction,
+) {
+    let handler = FunctionObject::function(
+        activation.gc(),
+        Executable::Native(handler),
+        activation.context.avm1.prototypes().function,
+        activation.context.avm1.prototypes().function,
+    );
+    object
+        .set(name, handler.into(), activation)
+        .expect("handler should be set");
+}
+
+thread_local! {
+    static RECORDED_EVENTS: RefCell<Vec<String>> = const { RefCell::new(vec![]) };
+}
+
+/// Records the AS events fired by [`Sockets::update_sockets`](super::Sockets::update_sockets), so tests can assert the exact
+/// sequence a scripted set of actions produces.
+///
+/// AVM1 events are recorded as handler calls like `onData(<a/>)`, AVM2 events by their type
+/// and details like `socketData(4)`.
+pub(super) struct SocketTestHarness;
+
+impl SocketTestHarness {
+    const AVM2_EVENTS: [&'static str; 7] = [
+        "connect",
+        "close",
+        "socketData",
+        "ioError",
+        "securityError",
+        "outputProgress",
+        "readWatermark",
+    ];
+
+    /// Starts recording the handler calls of an AVM1 `XMLSocket`.
+    #[cfg(feature = "socket")]
+    pub(super) fn record_avm1<'gc>(
+        activation: &mut Avm1Activation<'_, 'gc>,
+        object: Avm1Object<'gc>,
+    ) {
+        let handlers: [(&str, crate::avm1::function::NativeFunction); 3] = [
+            ("onConnect", |activation, _this, args| {
+                Self::record_avm1_call("onConnect", activation, args)
+            }),
+            ("onData", |activation, _this, args| {
+                Self::record_avm1_call("onData", activation, args)
+            }),
+            ("onClose", |activation, _this, args| {
+                Self::record_avm1_call("onClose", activation, args)
+            }),
+        ];
+
+        for (name, handler) in handlers {
+            set_avm1_handler(activation, object, name, handler);
+        }
+    }
+
+    #[cfg(feature = "socket")]
+    fn record_avm1_call<'gc>(
+        name: &str,
+        activation: &mut Avm1Activation<'_, 'gc>,
+        args: &[Avm1Value<'gc>],
+    ) -> Result<Avm1Value<'gc>, crate::avm1::Error<'gc>> {
+        let mut recorded = vec![];
+        for arg in args {
+            recorded.push(arg.coerce_to_string(activation)?.to_string());
+        }
+
+        Self::push(format!("{name}({})", recorded.join(", ")));
+        Ok(Avm1Value::Undefined)
+    }
+
+    /// Starts recording the socket events dispatched to an AVM2 `Socket`.
+    pub(super) fn record_avm2<'gc>(
+        activation: &mut Avm2Activation<'_, 'gc>,
+        target: SocketObject<'gc>,
+    ) {
+        for event_type in Self::AVM2_EVENTS {
+            add_native_event_listener(
+                activation,
+                target.into(),
+                event_type,
+                Self::record_avm2_event,
+            )
+            .unwrap();
+        }
+    }
+
+    pub(super) fn record_avm2_event<'gc>(
+        activation: &mut Avm2Activation<'_, 'gc>,
+        _this: Avm2Object<'gc>,
+        args: &[Avm2Value<'gc>],
+    ) -> Result<Avm2Value<'gc>, crate::avm2::Error<'gc>> {
+        let event = args[0].as_object().expect("listeners receive an event");
+        let event_type = event
+            .get_public_property("type", activation)?
+            .coerce_to_string(activation)?
+            .to_string();
+        if event_type == "outputProgress" {
+            let bytes_pending = event
+                .get_public_property("bytesPending", activation)?
+                .coerce_to_string(activation)?;
+            let bytes_total = event
+                .get_public_property("bytesTotal", activation)?
+                .coerce_to_string(activation)?;
+            Self::push(format!("{event_type}({bytes_pending}/{bytes_total})"));
+            return Ok(Avm2Value::Undefined);
+        }
+
+        let detail = match event_type.as_str() {
+            "socketData" | "readWatermark" => Some("bytesLoaded"),
+            "ioError" | "securityError" => Some("text"),
+            _ => None,
+        };
+
+        match detail {
+            Some(detail) => {
+                let detail = event
+                    .get_public_property(detail, activation)?
+                    .coerce_to_string(activation)?;
+                Self::push(format!("{event_type}({detail})"));
+            }
+            None => Self::push(event_type),
+        }
+        Ok(Avm2Value::Undefined)
+    }
+
+    fn push(event: String) {
+        RECORDED_EVENTS.with(|events| events.borrow_mut().push(event));
+    }
+
+    /// Returns the events recorded so far, and forgets them.
+    pub(super) fn take_events() -> Vec<String> {
+        RECORDED_EVENTS.with(|events| events.take())
+    }
+}
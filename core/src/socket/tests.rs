@@ -0,0 +1,2751 @@
+use super::test_support::{add_native_event_listener, set_avm1_handler, SocketTestHarness};
+use super::*;
+use crate::avm1::globals::xml_socket;
+use crate::avm1::test_utils::{with_avm, with_avm_and_navigator};
+use crate::avm1::ScriptObject;
+use crate::avm2::globals::flash::net::socket::{self as avm2_socket, get_close_reason};
+use crate::backend::navigator::{LoopbackNavigatorBackend, LoopbackSocket};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// The connections made through the loopback backend of a test, newest last.
+type LoopbackSockets = RefCell<Vec<LoopbackSocket>>;
+
+/// Runs the test with a loopback backend, whose connections the test takes as it makes them.
+fn with_loopback<F>(test: F)
+where
+    F: for<'gc> FnOnce(
+        &mut Avm1Activation<'_, 'gc>,
+        &LoopbackSockets,
+    ) -> Result<(), crate::avm1::Error<'gc>>,
+{
+    let navigator = LoopbackNavigatorBackend::new();
+    let loopback_sockets = navigator.sockets();
+
+    with_avm_and_navigator(19, navigator, |activation, _root| {
+        test(activation, loopback_sockets)
+    })
+}
+
+/// Runs the test with an AVM1 `XMLSocket` that isn't connected yet.
+fn with_xml_socket_object<F>(test: F)
+where
+    F: for<'gc> FnOnce(
+        &mut Avm1Activation<'_, 'gc>,
+        Avm1Object<'gc>,
+        &LoopbackSockets,
+    ) -> Result<(), crate::avm1::Error<'gc>>,
+{
+    with_loopback(|activation, loopback_sockets| {
+        let object = new_xml_socket(activation);
+        test(activation, object, loopback_sockets)
+    })
+}
+
+/// Runs the test with an AVM1 `XMLSocket` that is connecting through a loopback backend.
+fn with_xml_socket<F>(test: F)
+where
+    F: for<'gc> FnOnce(&mut Avm1Activation<'_, 'gc>, Avm1Object<'gc>, &LoopbackSocket),
+{
+    with_xml_socket_object(|activation, object, loopback_sockets| {
+        let socket = connect_xml_socket(activation, object, loopback_sockets);
+        test(activation, object, &socket);
+        Ok(())
+    })
+}
+
+/// Runs the test with an AVM2 `Socket` that isn't connected yet.
+fn with_avm2_socket_object<F>(test: F)
+where
+    F: for<'gc> FnOnce(&mut Avm2Activation<'_, 'gc>, SocketObject<'gc>, &LoopbackSockets),
+{
+    with_loopback(|activation, loopback_sockets| {
+        let mut activation = Avm2Activation::from_nothing(activation.context.reborrow());
+        let target = new_avm2_socket(&mut activation);
+        test(&mut activation, target, loopback_sockets);
+        Ok(())
+    })
+}
+
+/// Runs the test with an AVM2 `Socket` that is connecting through a loopback backend.
+fn with_avm2_socket<F>(test: F)
+where
+    F: for<'gc> FnOnce(&mut Avm2Activation<'_, 'gc>, SocketObject<'gc>, &LoopbackSocket),
+{
+    with_avm2_socket_object(|activation, target, loopback_sockets| {
+        let socket = connect_avm2_socket(activation, target, loopback_sockets);
+        test(activation, target, &socket);
+    })
+}
+
+fn new_xml_socket<'gc>(activation: &mut Avm1Activation<'_, 'gc>) -> Avm1Object<'gc> {
+    let object: Avm1Object<'_> = ScriptObject::new(activation.gc(), None).into();
+    xml_socket::constructor(activation, object, &[]).expect("XMLSocket should be constructed");
+    object
+}
+
+fn new_avm2_socket<'gc>(activation: &mut Avm2Activation<'_, 'gc>) -> SocketObject<'gc> {
+    activation
+        .avm2()
+        .classes()
+        .socket
+        .construct(activation, &[])
+        .expect("Socket should be constructed")
+        .as_socket()
+        .expect("Socket should be a SocketObject")
+}
+
+/// Connects an AVM1 `XMLSocket` to localhost:8080, returning the connection it made.
+fn connect_xml_socket<'gc>(
+    activation: &mut Avm1Activation<'_, 'gc>,
+    object: Avm1Object<'gc>,
+    loopback_sockets: &LoopbackSockets,
+) -> LoopbackSocket {
+    let UpdateContext {
+        sockets, navigator, ..
+    } = &mut activation.context;
+    sockets.connect_avm1(*navigator, object, "localhost".to_string(), 8080);
+
+    take_loopback_socket(loopback_sockets)
+}
+
+/// Connects an AVM2 `Socket` to localhost:8080, returning the connection it made.
+fn connect_avm2_socket<'gc>(
+    activation: &mut Avm2Activation<'_, 'gc>,
+    target: SocketObject<'gc>,
+    loopback_sockets: &LoopbackSockets,
+) -> LoopbackSocket {
+    let UpdateContext {
+        sockets, navigator, ..
+    } = &mut activation.context;
+    sockets.connect_avm2(*navigator, target, "localhost".to_string(), 8080);
+
+    take_loopback_socket(loopback_sockets)
+}
+
+/// Takes the connection that was made last.
+fn take_loopback_socket(loopback_sockets: &LoopbackSockets) -> LoopbackSocket {
+    loopback_sockets
+        .borrow_mut()
+        .pop()
+        .expect("socket should be connecting")
+}
+
+fn pending_actions(sockets: &Sockets<'_>) -> Vec<SocketAction> {
+    let mut actions = vec![];
+
+    while let Ok(action) = sockets.receiver.try_recv() {
+        actions.push(action);
+    }
+
+    actions
+}
+
+#[test]
+fn reconnect_discards_pending_data_of_old_handle() {
+    with_xml_socket_object(|activation, object, loopback_sockets| {
+        let args = ["localhost".into(), 8080.into()];
+        xml_socket::connect(activation, object, &args)?;
+        let first = take_loopback_socket(loopback_sockets);
+        first.connect();
+        Sockets::update_sockets(&mut activation.context);
+        SocketTestHarness::record_avm1(activation, object);
+
+        // The backend queues data for the old connection right before content reconnects.
+        first.receive(b"<partial");
+        xml_socket::connect(activation, object, &args)?;
+        let second = take_loopback_socket(loopback_sockets);
+        assert_ne!(first.handle, second.handle);
+
+        second.connect();
+        second.receive(b"<msg/>\0");
+        Sockets::update_sockets(&mut activation.context);
+
+        assert_eq!(
+            SocketTestHarness::take_events(),
+            ["onClose()", "onConnect(true)", "onData(<msg/>)"]
+        );
+        let xml_socket = XmlSocket::cast(object.into()).unwrap();
+        assert!(xml_socket.read_buffer().is_empty());
+        Ok(())
+    });
+}
+
+#[test]
+fn path_mtu_is_surfaced() {
+    with_xml_socket(|activation, _object, socket| {
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(activation.context.sockets.path_mtu(socket.handle), None);
+
+        socket.push(SocketAction::PathMtu(socket.handle, 1400));
+        Sockets::update_sockets(&mut activation.context);
+
+        assert_eq!(
+            activation.context.sockets.path_mtu(socket.handle),
+            Some(1400)
+        );
+    });
+}
+
+#[test]
+fn dns_error_sets_last_error() {
+    with_xml_socket(|activation, object, socket| {
+        socket.push(SocketAction::Connect(
+            socket.handle,
+            ConnectionState::DnsError,
+        ));
+        Sockets::update_sockets(&mut activation.context);
+
+        assert_eq!(
+            object.get("lastError", activation).unwrap(),
+            crate::avm1::Value::String("dns".into())
+        );
+    });
+}
+
+#[test]
+fn navigation_delivers_pending_writes() {
+    with_loopback(|activation, loopback_sockets| {
+        let object = new_xml_socket(activation);
+        let avm1 = connect_xml_socket(activation, object, loopback_sockets);
+        let (target, avm2) = {
+            let mut activation = Avm2Activation::from_nothing(activation.context.reborrow());
+            let target = new_avm2_socket(&mut activation);
+            (
+                target,
+                connect_avm2_socket(&mut activation, target, loopback_sockets),
+            )
+        };
+        avm1.connect();
+        avm2.connect();
+        Sockets::update_sockets(&mut activation.context);
+
+        let sockets = &mut activation.context.sockets;
+        sockets.send(avm1.handle, b"bye\0".to_vec()).unwrap();
+        // Unflushed data is sent too.
+        target.write_bytes(b"bye");
+        let timeout = Duration::from_millis(20);
+        sockets.set_navigation_drain_timeout(Some(timeout));
+        // Only the AVM1 backend acknowledges the flush, the AVM2 one never does.
+        avm1.push(SocketAction::Flushed(avm1.handle));
+
+        let started = Instant::now();
+        Sockets::close_for_navigation(&mut activation.context);
+        assert!(started.elapsed() >= timeout);
+
+        assert!(activation.context.sockets.active_handles().is_empty());
+        assert_eq!(
+            avm1.commands(),
+            [SocketCommand::Send(b"bye\0".to_vec()), SocketCommand::Flush]
+        );
+        assert_eq!(
+            avm2.commands(),
+            [SocketCommand::Send(b"bye".to_vec()), SocketCommand::Flush]
+        );
+        // Navigation doesn't wait for a later update to close the sockets.
+        assert!(avm1.is_closed());
+        assert!(avm2.is_closed());
+        Ok(())
+    });
+}
+
+#[test]
+fn loopback_data_is_delivered_to_on_data() {
+    with_xml_socket(|activation, object, socket| {
+        SocketTestHarness::record_avm1(activation, object);
+
+        socket.connect();
+        socket.receive(b"<a/>\0<b");
+        socket.receive(b"/>\0<c");
+        Sockets::update_sockets(&mut activation.context);
+
+        assert_eq!(
+            SocketTestHarness::take_events(),
+            ["onConnect(true)", "onData(<a/>)", "onData(<b/>)"]
+        );
+
+        activation
+            .context
+            .sockets
+            .send_string(socket.handle, "<reply/>")
+            .unwrap();
+        assert_eq!(socket.written(), b"<reply/>\0");
+    });
+}
+
+#[test]
+fn oversized_message_closes_xml_socket() {
+    with_xml_socket(|activation, object, socket| {
+        SocketTestHarness::record_avm1(activation, object);
+        let xml_socket = XmlSocket::cast(object.into()).unwrap();
+        xml_socket.set_max_message_size(8);
+
+        socket.connect();
+        socket.receive(b"<abcde");
+        Sockets::update_sockets(&mut activation.context);
+        assert!(activation.context.sockets.is_connected(socket.handle));
+
+        socket.receive(b"fgh/>");
+        Sockets::update_sockets(&mut activation.context);
+
+        assert!(!activation.context.sockets.is_connected(socket.handle));
+        assert!(xml_socket.read_buffer().is_empty());
+        assert_eq!(
+            SocketTestHarness::take_events(),
+            ["onConnect(true)", "onClose()"]
+        );
+        assert_eq!(
+            object.get("lastError", activation).unwrap(),
+            crate::avm1::Value::String("message too large".into())
+        );
+    });
+}
+
+#[test]
+fn no_delay_is_propagated() {
+    with_xml_socket(|activation, _object, socket| {
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+
+        activation.context.sockets.set_no_delay(socket.handle, true);
+        activation
+            .context
+            .sockets
+            .send(socket.handle, b"ping\0".to_vec())
+            .unwrap();
+        activation
+            .context
+            .sockets
+            .set_no_delay(socket.handle, false);
+
+        assert_eq!(
+            socket.commands(),
+            vec![
+                SocketCommand::SetNoDelay(true),
+                SocketCommand::Send(b"ping\0".to_vec()),
+                SocketCommand::SetNoDelay(false),
+            ]
+        );
+    });
+}
+
+#[test]
+fn close_reason_is_exposed_to_avm2() {
+    for reason in [CloseReason::Graceful, CloseReason::Reset] {
+        with_avm2_socket(|activation, target, socket| {
+            socket.connect();
+            Sockets::update_sockets(&mut activation.context);
+            assert_eq!(
+                get_close_reason(activation, target.into(), &[]).unwrap(),
+                Avm2Value::Null
+            );
+
+            socket.push(SocketAction::Close(socket.handle, reason));
+            Sockets::update_sockets(&mut activation.context);
+
+            assert_eq!(target.close_reason(), Some(reason));
+            assert_eq!(
+                get_close_reason(activation, target.into(), &[]).unwrap(),
+                Avm2Value::String(reason.as_str().into())
+            );
+        });
+    }
+}
+
+#[test]
+fn shutdown_write_keeps_reading() {
+    with_avm2_socket(|activation, target, socket| {
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+
+        activation.context.sockets.shutdown_write(socket.handle);
+        assert_eq!(socket.commands(), vec![SocketCommand::ShutdownWrite]);
+
+        socket.receive(b"response");
+        Sockets::update_sockets(&mut activation.context);
+
+        assert!(activation.context.sockets.is_connected(socket.handle));
+        assert_eq!(&*target.read_buffer(), b"response");
+
+        socket.close();
+        Sockets::update_sockets(&mut activation.context);
+
+        assert!(!activation.context.sockets.is_connected(socket.handle));
+        assert_eq!(target.close_reason(), Some(CloseReason::Graceful));
+    });
+}
+
+#[test]
+fn long_lived_socket_is_force_closed() {
+    with_avm2_socket(|activation, target, socket| {
+        activation
+            .context
+            .sockets
+            .set_max_connection_lifetime(Some(Duration::from_secs(60 * 60)));
+
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+        assert!(activation.context.sockets.is_connected(socket.handle));
+
+        let later = Instant::now() + Duration::from_secs(2 * 60 * 60);
+        activation.context.sockets.close_expired_sockets(later);
+        Sockets::update_sockets(&mut activation.context);
+
+        assert!(!activation.context.sockets.is_connected(socket.handle));
+        assert_eq!(target.close_reason(), Some(CloseReason::Policy));
+    });
+}
+
+#[test]
+fn dns_cache_is_used_until_cleared() {
+    with_xml_socket_object(|activation, object, loopback_sockets| {
+        let addresses = vec![IpAddr::from([127, 0, 0, 1])];
+        let socket = connect_xml_socket(activation, object, loopback_sockets);
+        assert_eq!(socket.options.resolved_addresses, None);
+
+        socket.push(SocketAction::Resolved(socket.handle, addresses.clone()));
+        Sockets::update_sockets(&mut activation.context);
+
+        let entries = activation.context.sockets.dns_cache_entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].0, "localhost");
+        assert_eq!(entries[0].1, addresses);
+
+        let socket = connect_xml_socket(activation, object, loopback_sockets);
+        assert_eq!(socket.options.resolved_addresses, Some(addresses));
+
+        activation.context.sockets.clear_dns_cache();
+        assert!(activation.context.sockets.dns_cache_entries().is_empty());
+
+        let socket = connect_xml_socket(activation, object, loopback_sockets);
+        assert_eq!(socket.options.resolved_addresses, None);
+        Ok(())
+    });
+}
+
+#[test]
+fn stale_dns_cache_entries_are_resolved_again() {
+    with_xml_socket_object(|activation, object, loopback_sockets| {
+        let ttl = Duration::from_millis(20);
+        activation.context.sockets.set_dns_cache_ttl(ttl);
+
+        let socket = connect_xml_socket(activation, object, loopback_sockets);
+        socket.push(SocketAction::Resolved(
+            socket.handle,
+            vec![IpAddr::from([127, 0, 0, 1])],
+        ));
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(activation.context.sockets.dns_cache_entries().len(), 1);
+
+        std::thread::sleep(ttl);
+        assert!(activation.context.sockets.dns_cache_entries().is_empty());
+        let socket = connect_xml_socket(activation, object, loopback_sockets);
+        assert_eq!(socket.options.resolved_addresses, None);
+
+        let addresses = vec![IpAddr::from([127, 0, 0, 2])];
+        socket.push(SocketAction::Resolved(socket.handle, addresses.clone()));
+        Sockets::update_sockets(&mut activation.context);
+        let socket = connect_xml_socket(activation, object, loopback_sockets);
+        assert_eq!(socket.options.resolved_addresses, Some(addresses));
+        Ok(())
+    });
+}
+
+#[test]
+fn dns_cache_forgets_the_oldest_entries_when_full() {
+    with_xml_socket(|activation, _object, socket| {
+        let sockets = &mut activation.context.sockets;
+        for i in 0..MAX_DNS_CACHE_ENTRIES {
+            sockets.cache_resolution(format!("host{i}"), vec![]);
+        }
+        socket.push(SocketAction::Resolved(
+            socket.handle,
+            vec![IpAddr::from([127, 0, 0, 1])],
+        ));
+        Sockets::update_sockets(&mut activation.context);
+
+        let entries = activation.context.sockets.dns_cache_entries();
+        assert_eq!(entries.len(), MAX_DNS_CACHE_ENTRIES);
+        assert!(entries.iter().any(|(host, _, _)| host == "localhost"));
+    });
+}
+
+#[test]
+fn connect_timeout_is_clamped() {
+    assert_eq!(connect_timeout(0), DEFAULT_CONNECT_TIMEOUT);
+    assert_eq!(connect_timeout(1), MIN_CONNECT_TIMEOUT);
+    assert_eq!(connect_timeout(3000), Duration::from_secs(3));
+    assert_eq!(connect_timeout(u32::MAX), MAX_CONNECT_TIMEOUT);
+}
+
+#[test]
+fn output_progress_is_dispatched_to_avm2() {
+    with_avm2_socket(|activation, target, socket| {
+        SocketTestHarness::record_avm2(activation, target);
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(SocketTestHarness::take_events(), ["connect"]);
+
+        activation
+            .context
+            .sockets
+            .send(socket.handle, b"0123456789".to_vec())
+            .unwrap();
+        assert_eq!(socket.written(), b"0123456789");
+
+        socket.push(SocketAction::OutputProgress(socket.handle, 4));
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(SocketTestHarness::take_events(), ["outputProgress(4/10)"]);
+
+        let sockets = &activation.context.sockets;
+        assert_eq!(sockets.sockets[socket.handle].bytes_sent.get(), 10);
+        assert!(pending_actions(sockets).is_empty());
+    });
+}
+
+#[test]
+fn budget_callback_chooses_socket_to_close() {
+    with_loopback(|activation, loopback_sockets| {
+        let first = new_xml_socket(activation);
+        let second = new_xml_socket(activation);
+        let first_socket = connect_xml_socket(activation, first, loopback_sockets);
+        let second_socket = connect_xml_socket(activation, second, loopback_sockets);
+        first_socket.connect();
+        second_socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+
+        let reported = Rc::new(RefCell::new(vec![]));
+        let reported2 = reported.clone();
+        let first_handle = first_socket.handle;
+        let sockets = &mut activation.context.sockets;
+        sockets.set_memory_budget(Some(10));
+        sockets.set_on_budget_exceeded(Some(Box::new(
+            move |total: usize, usage: &[(SocketHandle, usize)]| {
+                reported2.borrow_mut().push((total, usage.to_vec()));
+                vec![first_handle]
+            },
+        )));
+
+        // Neither packet is terminated, so both stay buffered.
+        first_socket.receive(b"<first");
+        second_socket.receive(b"<second");
+        Sockets::update_sockets(&mut activation.context);
+
+        let reported = reported.borrow();
+        assert_eq!(reported.len(), 1);
+        assert_eq!(reported[0].0, 6);
+        assert!(reported[0].1.contains(&(first_socket.handle, 6)));
+        assert!(reported[0].1.contains(&(second_socket.handle, 0)));
+
+        let first_xml = XmlSocket::cast(first.into()).unwrap();
+        let second_xml = XmlSocket::cast(second.into()).unwrap();
+        assert!(first_xml.read_buffer().is_empty());
+        assert_eq!(second_xml.read_buffer().as_slice(), b"<second");
+        assert_eq!(
+            pending_actions(&activation.context.sockets),
+            vec![SocketAction::Close(
+                first_socket.handle,
+                CloseReason::Policy
+            )]
+        );
+        Ok(())
+    });
+}
+
+#[test]
+fn data_is_delivered_in_whole_records() {
+    with_avm2_socket(|activation, target, socket| {
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+
+        target.set_record_size(64);
+        socket.receive(&[7; 150]);
+        Sockets::update_sockets(&mut activation.context);
+
+        assert_eq!(target.read_buffer().len(), 128);
+        assert_eq!(target.partial_record().len(), 22);
+
+        target.set_record_size(0);
+        assert_eq!(target.read_buffer().len(), 150);
+        assert!(target.partial_record().is_empty());
+    });
+}
+
+#[test]
+fn connect_filter_blocks_without_backend() {
+    with_xml_socket_object(|activation, object, loopback_sockets| {
+        activation
+            .context
+            .sockets
+            .set_connect_filter(Some(Box::new(|host: &str, port: u16| {
+                host != "localhost" || port != 8080
+            })));
+
+        let UpdateContext {
+            sockets, navigator, ..
+        } = &mut activation.context;
+        sockets.connect_avm1(*navigator, object, "localhost".to_string(), 8080);
+
+        assert!(loopback_sockets.borrow().is_empty());
+        let handle = XmlSocket::cast(object.into()).unwrap().handle().unwrap();
+        assert_eq!(
+            pending_actions(&activation.context.sockets),
+            vec![SocketAction::Connect(
+                handle,
+                ConnectionState::SecurityBlocked
+            )]
+        );
+
+        let UpdateContext {
+            sockets, navigator, ..
+        } = &mut activation.context;
+        sockets.connect_avm1(*navigator, object, "localhost".to_string(), 8081);
+        assert_eq!(loopback_sockets.borrow().len(), 1);
+        Ok(())
+    });
+}
+
+#[test]
+fn active_handles_only_lists_open_sockets() {
+    with_xml_socket_object(|activation, object, loopback_sockets| {
+        let first = connect_xml_socket(activation, object, loopback_sockets);
+        assert_eq!(
+            activation.context.sockets.active_handles(),
+            vec![first.handle]
+        );
+
+        // Reconnecting closes the first socket, and its handle must not be reused as is.
+        let second = connect_xml_socket(activation, object, loopback_sockets);
+        assert_ne!(first.handle, second.handle);
+        assert_eq!(
+            activation.context.sockets.active_handles(),
+            vec![second.handle]
+        );
+
+        activation.context.sockets.close_all();
+        assert!(activation.context.sockets.active_handles().is_empty());
+        Ok(())
+    });
+}
+
+/// Counts the warnings logged by this module.
+struct WarningCounter(Arc<AtomicUsize>);
+
+impl tracing::Subscriber for WarningCounter {
+    fn enabled(&self, _metadata: &tracing::Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+        tracing::span::Id::from_u64(1)
+    }
+
+    fn record(&self, _span: &tracing::span::Id, _values: &tracing::span::Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &tracing::span::Id, _follows: &tracing::span::Id) {}
+
+    fn event(&self, event: &tracing::Event<'_>) {
+        let metadata = event.metadata();
+        if *metadata.level() == tracing::Level::WARN && metadata.target() == module_path!() {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn enter(&self, _span: &tracing::span::Id) {}
+
+    fn exit(&self, _span: &tracing::span::Id) {}
+}
+
+#[test]
+fn ignored_actions_are_logged() {
+    let warnings = Arc::new(AtomicUsize::new(0));
+    let subscriber = WarningCounter(warnings.clone());
+
+    tracing::subscriber::with_default(subscriber, || {
+        with_xml_socket(|activation, _object, socket| {
+            socket.connect();
+            // XMLSocket has no output progress event, so these are ignored.
+            socket.push(SocketAction::OutputProgress(socket.handle, 0));
+            socket.push(SocketAction::OutputProgress(socket.handle, 0));
+            socket.close();
+            Sockets::update_sockets(&mut activation.context);
+
+            let sockets = &activation.context.sockets;
+            assert!(sockets.ignored_action_kinds.contains("output_progress"));
+            // Processing carried on past the ignored actions.
+            assert!(sockets.active_handles().is_empty());
+        });
+    });
+
+    // Repeated actions of the same kind are only warned about once.
+    assert_eq!(warnings.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn priority_data_uses_its_own_lane() {
+    with_xml_socket(|activation, _object, socket| {
+        let sockets = &mut activation.context.sockets;
+        sockets.send(socket.handle, b"bulk".to_vec()).unwrap();
+        sockets
+            .send_priority(socket.handle, b"control".to_vec())
+            .unwrap();
+
+        assert_eq!(
+            socket.commands(),
+            vec![
+                SocketCommand::Send(b"bulk".to_vec()),
+                SocketCommand::SendPriority(b"control".to_vec()),
+            ]
+        );
+    });
+}
+
+#[test]
+fn tcp_user_timeout_is_propagated() {
+    with_xml_socket_object(|activation, object, loopback_sockets| {
+        let socket = connect_xml_socket(activation, object, loopback_sockets);
+        assert_eq!(socket.options.tcp_user_timeout, None);
+
+        activation
+            .context
+            .sockets
+            .set_tcp_user_timeout(Some(Duration::from_secs(10)));
+        let socket = connect_xml_socket(activation, object, loopback_sockets);
+        assert_eq!(
+            socket.options.tcp_user_timeout,
+            Some(Duration::from_secs(10))
+        );
+        Ok(())
+    });
+}
+
+#[test]
+fn connection_result_resolves_for_host_code() {
+    for state in [ConnectionState::Connected, ConnectionState::Failed(None)] {
+        with_xml_socket(|activation, _object, socket| {
+            let result = activation.context.sockets.connection_result(socket.handle);
+
+            socket.push(SocketAction::Connect(socket.handle, state.clone()));
+            Sockets::update_sockets(&mut activation.context);
+
+            assert_eq!(futures::executor::block_on(result), Some(state.clone()));
+
+            // Asking again after the fact resolves right away.
+            let result = activation.context.sockets.connection_result(socket.handle);
+            assert_eq!(futures::executor::block_on(result), Some(state));
+        });
+    }
+}
+
+#[test]
+fn restricted_ports_are_blocked_unless_overridden() {
+    with_xml_socket_object(|activation, object, loopback_sockets| {
+        let UpdateContext {
+            sockets, navigator, ..
+        } = &mut activation.context;
+        sockets.connect_avm1(*navigator, object, "localhost".to_string(), 25);
+        assert_eq!(take_loopback_socket(loopback_sockets).port, 25);
+
+        let UpdateContext {
+            sockets, navigator, ..
+        } = &mut activation.context;
+        sockets.set_restricted_ports(DEFAULT_RESTRICTED_PORTS.iter().copied());
+        sockets.connect_avm1(*navigator, object, "localhost".to_string(), 25);
+
+        assert!(loopback_sockets.borrow().is_empty());
+        let handle = XmlSocket::cast(object.into()).unwrap().handle().unwrap();
+        assert_eq!(
+            pending_actions(&activation.context.sockets),
+            vec![SocketAction::Connect(
+                handle,
+                ConnectionState::SecurityBlocked
+            )]
+        );
+
+        let UpdateContext {
+            sockets, navigator, ..
+        } = &mut activation.context;
+        sockets.set_restricted_ports([]);
+        sockets.connect_avm1(*navigator, object, "localhost".to_string(), 25);
+        assert_eq!(loopback_sockets.borrow().len(), 1);
+        Ok(())
+    });
+}
+
+#[test]
+fn stats_can_be_reset_without_closing() {
+    with_xml_socket(|activation, object, socket| {
+        socket.connect();
+        socket.receive(b"<partial");
+        Sockets::update_sockets(&mut activation.context);
+        activation
+            .context
+            .sockets
+            .send(socket.handle, b"hello\0".to_vec())
+            .unwrap();
+
+        let sockets = &mut activation.context.sockets;
+        assert_eq!(
+            sockets.stats(socket.handle),
+            Some(SocketStats {
+                bytes_sent: 6,
+                bytes_received: 8,
+                messages_sent: 1,
+                messages_received: 1,
+                peak_buffered_bytes: 8,
+            })
+        );
+
+        sockets.reset_stats(socket.handle);
+        assert_eq!(sockets.stats(socket.handle), Some(SocketStats::default()));
+        assert!(sockets.is_connected(socket.handle));
+
+        // The buffered data survives, and counting restarts from zero.
+        let xml_socket = XmlSocket::cast(object.into()).unwrap();
+        assert_eq!(xml_socket.read_buffer().as_slice(), b"<partial");
+
+        socket.receive(b"/>");
+        Sockets::update_sockets(&mut activation.context);
+        let stats = activation.context.sockets.stats(socket.handle).unwrap();
+        assert_eq!(stats.bytes_received, 2);
+        assert_eq!(stats.messages_received, 1);
+        assert_eq!(stats.bytes_sent, 0);
+    });
+}
+
+#[test]
+fn blocking_update_waits_for_the_first_action() {
+    with_xml_socket(|activation, _object, socket| {
+        assert!(!Sockets::update_sockets_blocking(
+            &mut activation.context,
+            Duration::from_millis(10)
+        ));
+
+        let sender = activation.context.sockets.sender.clone();
+        let handle = socket.handle;
+        let backend = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            sender
+                .send_blocking(SocketAction::Connect(handle, ConnectionState::Connected))
+                .unwrap();
+        });
+
+        assert!(Sockets::update_sockets_blocking(
+            &mut activation.context,
+            Duration::from_secs(5)
+        ));
+        assert!(activation.context.sockets.is_connected(handle));
+        backend.join().unwrap();
+    });
+}
+
+#[test]
+fn read_backpressure_pauses_and_resumes() {
+    with_avm2_socket(|activation, target, socket| {
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+        activation.context.sockets.set_read_water_marks(Some(8), 4);
+
+        socket.receive(&[0; 10]);
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(socket.commands(), vec![SocketCommand::PauseReading]);
+
+        // Still above the low-water mark, so reading stays paused.
+        target.read_bytes(5).unwrap();
+        Sockets::update_sockets(&mut activation.context);
+        assert!(socket.commands().is_empty());
+
+        target.read_bytes(3).unwrap();
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(socket.commands(), vec![SocketCommand::ResumeReading]);
+    });
+}
+
+#[test]
+fn send_queue_drained_callback_fires_once() {
+    with_xml_socket(|activation, _object, socket| {
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+
+        let drained = Rc::new(RefCell::new(vec![]));
+        let drained2 = drained.clone();
+        let sockets = &mut activation.context.sockets;
+        sockets.set_on_send_queue_drained(Some(Box::new(move |handle| {
+            drained2.borrow_mut().push(handle);
+        })));
+
+        for message in [b"one\0", b"two\0", b"six\0"] {
+            sockets.send(socket.handle, message.to_vec()).unwrap();
+        }
+
+        socket.push(SocketAction::Sent(socket.handle, 6));
+        Sockets::update_sockets(&mut activation.context);
+        assert!(drained.borrow().is_empty());
+
+        socket.push(SocketAction::Sent(socket.handle, 6));
+        // Acks past an empty queue don't count as draining it again.
+        socket.push(SocketAction::Sent(socket.handle, 0));
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(*drained.borrow(), vec![socket.handle]);
+    });
+}
+
+#[test]
+fn reconnect_keeps_the_target() {
+    with_xml_socket_object(|activation, object, loopback_sockets| {
+        let old = connect_xml_socket(activation, object, loopback_sockets);
+        old.connect();
+        Sockets::update_sockets(&mut activation.context);
+
+        let UpdateContext {
+            sockets, navigator, ..
+        } = &mut activation.context;
+        let new_handle = sockets
+            .reconnect(*navigator, old.handle, "localhost".to_string(), 8081)
+            .expect("socket should be open");
+
+        let new = take_loopback_socket(loopback_sockets);
+        assert_eq!(new.handle, new_handle);
+        assert_eq!(new.port, 8081);
+        assert_eq!(
+            XmlSocket::cast(object.into()).unwrap().handle(),
+            Some(new_handle)
+        );
+        assert_eq!(
+            activation.context.sockets.active_handles(),
+            vec![new_handle]
+        );
+
+        new.connect();
+        Sockets::update_sockets(&mut activation.context);
+        assert!(activation.context.sockets.is_connected(new_handle));
+
+        // The old handle can't be used to reconnect anymore.
+        let UpdateContext {
+            sockets, navigator, ..
+        } = &mut activation.context;
+        assert_eq!(
+            sockets.reconnect(*navigator, old.handle, "localhost".to_string(), 8081),
+            None
+        );
+        Ok(())
+    });
+}
+
+#[test]
+fn connection_status_tells_pending_from_failed() {
+    with_xml_socket(|activation, _object, socket| {
+        let sockets = &activation.context.sockets;
+        assert_eq!(
+            sockets.connection_status(socket.handle),
+            SocketStatus::Connecting
+        );
+
+        socket.push(SocketAction::Connect(
+            socket.handle,
+            ConnectionState::TimedOut,
+        ));
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(
+            activation.context.sockets.connection_status(socket.handle),
+            SocketStatus::Failed(ConnectionState::TimedOut)
+        );
+
+        socket.close();
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(
+            activation.context.sockets.connection_status(socket.handle),
+            SocketStatus::Closed
+        );
+    });
+
+    with_xml_socket(|activation, _object, socket| {
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(
+            activation.context.sockets.connection_status(socket.handle),
+            SocketStatus::Connected
+        );
+    });
+}
+
+#[test]
+fn keepalive_is_propagated() {
+    with_xml_socket_object(|activation, object, loopback_sockets| {
+        let socket = connect_xml_socket(activation, object, loopback_sockets);
+        assert_eq!(socket.options.keepalive, None);
+
+        activation
+            .context
+            .sockets
+            .set_keepalive(Some(Duration::from_secs(30)));
+        let socket = connect_xml_socket(activation, object, loopback_sockets);
+        assert_eq!(socket.options.keepalive, Some(Duration::from_secs(30)));
+        Ok(())
+    });
+}
+
+#[test]
+fn no_delay_option_comes_from_the_socket_object() {
+    with_avm2_socket_object(|activation, target, loopback_sockets| {
+        let socket = connect_avm2_socket(activation, target, loopback_sockets);
+        assert!(!socket.options.no_delay);
+
+        target.set_no_delay(true);
+        let socket = connect_avm2_socket(activation, target, loopback_sockets);
+        assert!(socket.options.no_delay);
+    });
+}
+
+#[test]
+fn global_send_rate_limit_holds_back_data() {
+    with_avm2_socket(|activation, target, socket| {
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+
+        let sockets = &mut activation.context.sockets;
+        sockets.set_global_max_bytes_per_sec(Some(4));
+
+        target.write_bytes(b"0123456789");
+        sockets.flush(socket.handle);
+        assert_eq!(socket.written(), b"0123");
+        assert_eq!(*target.write_buffer(), b"456789");
+
+        // Unflushed data stays behind the data that is held back.
+        target.write_bytes(b"ab");
+        let limiter = sockets.send_rate_limiter.as_mut().unwrap();
+        limiter.refilled_at -= Duration::from_secs(1);
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(socket.written(), b"4567");
+        assert_eq!(*target.write_buffer(), b"89ab");
+
+        let sockets = &mut activation.context.sockets;
+        sockets.set_global_max_bytes_per_sec(None);
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(socket.written(), b"89");
+        assert_eq!(*target.write_buffer(), b"ab");
+    });
+}
+
+#[test]
+fn throwing_handlers_do_not_stop_other_actions() {
+    let warnings = Arc::new(AtomicUsize::new(0));
+    let subscriber = WarningCounter(warnings.clone());
+
+    tracing::subscriber::with_default(subscriber, || {
+        with_xml_socket(|activation, object, socket| {
+            SocketTestHarness::record_avm1(activation, object);
+            set_avm1_handler(activation, object, "onData", |_activation, _this, _args| {
+                Err(crate::avm1::Error::ThrownValue("broken".into()))
+            });
+
+            socket.connect();
+            socket.receive(b"<a/>\0<b/>\0");
+            socket.close();
+            Sockets::update_sockets(&mut activation.context);
+
+            assert_eq!(
+                SocketTestHarness::take_events(),
+                ["onConnect(true)", "onClose()"]
+            );
+        });
+    });
+
+    assert_eq!(warnings.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn avm2_reads_respect_endian() {
+    use crate::avm2::bytearray::Endian;
+    use crate::avm2::globals::flash::net::socket::{read_int, set_endian};
+
+    with_avm2_socket(|activation, target, socket| {
+        socket.connect();
+        socket.receive(&[0x01, 0x02, 0x03, 0x04, 0x01, 0x02, 0x03, 0x04]);
+        Sockets::update_sockets(&mut activation.context);
+
+        assert_eq!(target.endian(), Endian::Big);
+        let value = read_int(activation, target.into(), &[]).unwrap();
+        assert_eq!(value, Avm2Value::Integer(0x01020304));
+
+        set_endian(activation, target.into(), &["littleEndian".into()]).unwrap();
+        assert_eq!(target.endian(), Endian::Little);
+        let value = read_int(activation, target.into(), &[]).unwrap();
+        assert_eq!(value, Avm2Value::Integer(0x04030201));
+    });
+}
+
+#[test]
+fn peeks_respect_endian_and_leave_the_buffer_alone() {
+    use crate::avm2::bytearray::{ByteArrayError, Endian};
+
+    with_avm2_socket(|_activation, target, _socket| {
+        target
+            .read_buffer()
+            .extend_from_slice(&[0x3F, 0xF0, 0, 0, 0, 0, 0, 0]);
+
+        assert_eq!(target.peek_unsigned_short(), Ok(0x3FF0));
+        assert_eq!(target.peek_unsigned_int(), Ok(0x3FF00000));
+        assert_eq!(target.peek_double(), Ok(1.0));
+        assert_eq!(target.read_buffer().len(), 8);
+
+        target.set_endian(Endian::Little);
+        assert_eq!(target.peek_unsigned_short(), Ok(0xF03F));
+        assert_eq!(target.peek_unsigned_int(), Ok(0xF03F));
+        assert_eq!(
+            target.peek_double(),
+            Ok(f64::from_le_bytes([0x3F, 0xF0, 0, 0, 0, 0, 0, 0]))
+        );
+        assert_eq!(target.read_buffer().len(), 8);
+
+        // Reads consume exactly what the matching peek looked at.
+        assert_eq!(target.read_unsigned_short(), Ok(0xF03F));
+        assert_eq!(target.read_buffer().len(), 6);
+        target.set_endian(Endian::Big);
+        assert_eq!(target.read_unsigned_int(), Ok(0));
+        assert_eq!(target.read_buffer().len(), 2);
+
+        // A buffer that's too short is left untouched.
+        assert_eq!(target.peek_unsigned_int(), Err(ByteArrayError::EndOfFile));
+        assert_eq!(target.read_double(), Err(ByteArrayError::EndOfFile));
+        assert_eq!(target.read_buffer().len(), 2);
+    });
+}
+
+#[test]
+fn local_close_is_recorded_without_an_event() {
+    use crate::avm2::globals::flash::net::socket::close;
+
+    with_avm2_socket(|activation, target, socket| {
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+
+        close(activation, target.into(), &[]).unwrap();
+        assert_eq!(target.close_reason(), Some(CloseReason::Local));
+        assert!(!activation.context.sockets.is_connected(socket.handle));
+
+        // The backend closing its end afterwards must not look like a remote close.
+        socket.close();
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(
+            get_close_reason(activation, target.into(), &[]).unwrap(),
+            Avm2Value::String("local".into())
+        );
+    });
+}
+
+#[test]
+fn describe_sockets_skips_stopped_backends() {
+    with_loopback(|activation, loopback_sockets| {
+        let first = new_xml_socket(activation);
+        let second = new_xml_socket(activation);
+        let first_socket = connect_xml_socket(activation, first, loopback_sockets);
+        let second_socket = connect_xml_socket(activation, second, loopback_sockets);
+
+        let described: Vec<_> = activation.context.sockets.describe_sockets().collect();
+        assert_eq!(
+            described,
+            [
+                (
+                    first_socket.handle,
+                    SocketKindDescriptor::XmlSocket,
+                    "localhost",
+                    None
+                ),
+                (
+                    second_socket.handle,
+                    SocketKindDescriptor::XmlSocket,
+                    "localhost",
+                    None
+                ),
+            ]
+        );
+
+        // Dropping the loopback socket is like the backend task ending.
+        let second_handle = second_socket.handle;
+        drop(second_socket);
+        let described: Vec<_> = activation
+            .context
+            .sockets
+            .describe_sockets()
+            .map(|(handle, _, _, _)| handle)
+            .collect();
+        assert_eq!(described, [first_socket.handle]);
+        assert!(activation
+            .context
+            .sockets
+            .active_handles()
+            .contains(&second_handle));
+        Ok(())
+    });
+}
+
+#[test]
+fn amf_objects_can_span_data_chunks() {
+    use crate::avm2::globals::flash::net::socket::{read_object, write_object};
+
+    with_avm2_socket(|activation, target, socket| {
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+
+        write_object(activation, target.into(), &["hello".into()]).unwrap();
+        let encoded = std::mem::take(&mut *target.write_buffer());
+        assert_eq!(encoded, b"\x06\x0bhello");
+
+        socket.receive(&encoded[..3]);
+        Sockets::update_sockets(&mut activation.context);
+        assert!(matches!(
+            read_object(activation, target.into(), &[]),
+            Err(crate::avm2::Error::AvmError(_))
+        ));
+        assert_eq!(target.read_buffer().len(), 3);
+
+        socket.receive(&encoded[3..]);
+        Sockets::update_sockets(&mut activation.context);
+        let value = read_object(activation, target.into(), &[]).unwrap();
+        assert_eq!(value, Avm2Value::String("hello".into()));
+        assert!(target.read_buffer().is_empty());
+    });
+}
+
+#[test]
+fn corrupt_amf_objects_are_invalid() {
+    use crate::avm2::globals::flash::net::socket::read_object;
+
+    with_avm2_socket(|activation, target, socket| {
+        socket.connect();
+        // 0x20 isn't an AMF3 type marker.
+        socket.receive(b"\x20\x06\x0bhello");
+        Sockets::update_sockets(&mut activation.context);
+
+        // Unlike a value that is cut short, this can't ever become valid.
+        assert!(matches!(
+            read_object(activation, target.into(), &[]),
+            Err(crate::avm2::Error::RustError(_))
+        ));
+        assert_eq!(target.read_buffer().len(), 8);
+    });
+}
+
+#[test]
+fn pending_connection_is_cancelled_by_a_new_connect() {
+    with_xml_socket_object(|activation, object, loopback_sockets| {
+        SocketTestHarness::record_avm1(activation, object);
+
+        let first = connect_xml_socket(activation, object, loopback_sockets);
+        assert!(!activation.context.sockets.is_connected(first.handle));
+        let second = connect_xml_socket(activation, object, loopback_sockets);
+
+        // The first attempt finishing late must not fire another connect event.
+        first.connect();
+        second.connect();
+        Sockets::update_sockets(&mut activation.context);
+
+        assert_eq!(SocketTestHarness::take_events(), ["onConnect(true)"]);
+        assert!(activation.context.sockets.is_connected(second.handle));
+        assert_eq!(activation.context.sockets.active_handles(), [second.handle]);
+        Ok(())
+    });
+}
+
+#[test]
+fn recorded_actions_can_be_replayed() {
+    fn recorder(capture: &Rc<RefCell<Vec<(Duration, SocketAction)>>>) -> SocketActionRecorder {
+        let capture = capture.clone();
+        Box::new(move |elapsed, action: &SocketAction| {
+            capture.borrow_mut().push((elapsed, action.clone()))
+        })
+    }
+
+    let capture = Rc::new(RefCell::new(vec![]));
+
+    with_xml_socket(|activation, _object, socket| {
+        let sockets = &mut activation.context.sockets;
+        sockets.start_recording(recorder(&capture), true);
+
+        socket.connect();
+        socket.receive(b"<a/>\0");
+        socket.close();
+        Sockets::update_sockets(&mut activation.context);
+    });
+
+    let replayed = Rc::new(RefCell::new(vec![]));
+
+    with_xml_socket_object(|activation, object, loopback_sockets| {
+        SocketTestHarness::record_avm1(activation, object);
+
+        // Closing a connection first makes sure the replay doesn't reuse captured handles.
+        let unrelated = connect_xml_socket(activation, object, loopback_sockets);
+        activation.context.sockets.close(unrelated.handle);
+
+        let UpdateContext {
+            sockets, navigator, ..
+        } = &mut activation.context;
+        sockets.start_replay(capture.take(), false);
+        sockets.start_recording(recorder(&replayed), false);
+        sockets.connect_avm1(*navigator, object, "localhost".to_string(), 8080);
+        assert!(loopback_sockets.borrow().is_empty());
+
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(
+            SocketTestHarness::take_events(),
+            ["onConnect(true)", "onData(<a/>)", "onClose()"]
+        );
+
+        let handle = XmlSocket::cast(object.into()).unwrap().handle().unwrap();
+        assert_ne!(handle, unrelated.handle);
+        let actions: Vec<_> = replayed
+            .take()
+            .into_iter()
+            .map(|(_, action)| action)
+            .collect();
+        assert_eq!(
+            actions,
+            [
+                SocketAction::Connect(handle, ConnectionState::Connected),
+                // The recording of the replay leaves out the payload.
+                SocketAction::Data(handle, vec![0; 5]),
+                SocketAction::Close(handle, CloseReason::Graceful),
+            ]
+        );
+        Ok(())
+    });
+}
+
+#[test]
+fn io_error_message_includes_the_backend_error() {
+    assert_eq!(
+        io_error_message(&ConnectionState::Failed(Some(
+            "Connection refused (os error 111)".to_string()
+        ))),
+        "Error #2031: Socket Error. Connection refused (os error 111)"
+    );
+    assert_eq!(
+        io_error_message(&ConnectionState::Failed(None)),
+        "Error #2031: Socket Error."
+    );
+    assert_eq!(
+        io_error_message(&ConnectionState::TimedOut),
+        "Error #2031: Socket Error."
+    );
+}
+
+#[test]
+fn peek_read_does_not_consume_data() {
+    with_avm2_socket(|activation, object, socket| {
+        let sockets = &activation.context.sockets;
+        assert_eq!(sockets.peek_read(socket.handle, 4), Some(vec![]));
+
+        object.read_buffer().extend(b"hello");
+        assert_eq!(sockets.peek_read(socket.handle, 4), Some(b"hell".to_vec()));
+        assert_eq!(
+            sockets.peek_read(socket.handle, 16),
+            Some(b"hello".to_vec())
+        );
+        assert_eq!(object.read_buffer().len(), 5);
+    });
+
+    with_xml_socket(|activation, object, socket| {
+        let xml_socket = XmlSocket::cast(object.into()).unwrap();
+        xml_socket.read_buffer().extend(b"<a/>");
+
+        let sockets = &activation.context.sockets;
+        assert_eq!(sockets.peek_read(socket.handle, 2), Some(b"<a".to_vec()));
+        assert_eq!(xml_socket.read_buffer().len(), 4);
+
+        activation.context.sockets.close(socket.handle);
+        assert_eq!(activation.context.sockets.peek_read(socket.handle, 2), None);
+    });
+}
+
+#[test]
+fn reconnecting_xml_socket_closes_the_old_connection() {
+    with_xml_socket_object(|activation, object, loopback_sockets| {
+        SocketTestHarness::record_avm1(activation, object);
+
+        let args = ["localhost".into(), 8080.into()];
+        xml_socket::connect(activation, object, &args)?;
+        let first = take_loopback_socket(loopback_sockets);
+        first.connect();
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(SocketTestHarness::take_events(), ["onConnect(true)"]);
+
+        let xml_socket = XmlSocket::cast(object.into()).unwrap();
+        xml_socket.read_buffer().extend(b"<partial");
+
+        xml_socket::connect(activation, object, &args)?;
+        let second = take_loopback_socket(loopback_sockets);
+        assert!(xml_socket.read_buffer().is_empty());
+        assert_eq!(activation.context.sockets.active_handles(), [second.handle]);
+
+        // onClose isn't called from within connect, but on the next update.
+        assert!(SocketTestHarness::take_events().is_empty());
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(SocketTestHarness::take_events(), ["onClose()"]);
+        Ok(())
+    });
+}
+
+#[test]
+fn replacing_an_xml_socket_that_never_opened_fires_no_close() {
+    with_xml_socket_object(|activation, object, loopback_sockets| {
+        SocketTestHarness::record_avm1(activation, object);
+        let args = ["localhost".into(), 8080.into()];
+
+        // Retrying after a failed connection.
+        xml_socket::connect(activation, object, &args)?;
+        let first = take_loopback_socket(loopback_sockets);
+        first.push(SocketAction::Connect(
+            first.handle,
+            ConnectionState::Failed(None),
+        ));
+        Sockets::update_sockets(&mut activation.context);
+        xml_socket::connect(activation, object, &args)?;
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(SocketTestHarness::take_events(), ["onConnect(false)"]);
+
+        // Replacing a connection that is still pending.
+        xml_socket::connect(activation, object, &args)?;
+        let third = take_loopback_socket(loopback_sockets);
+        third.connect();
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(SocketTestHarness::take_events(), ["onConnect(true)"]);
+        Ok(())
+    });
+}
+
+#[test]
+fn data_arriving_before_connect_waits_for_the_connect_event() {
+    with_xml_socket(|activation, object, socket| {
+        SocketTestHarness::record_avm1(activation, object);
+
+        socket.receive(b"<a/>\0");
+        socket.receive(b"<b/>\0");
+        Sockets::update_sockets(&mut activation.context);
+        assert!(SocketTestHarness::take_events().is_empty());
+
+        socket.connect();
+        socket.receive(b"<c/>\0");
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(
+            SocketTestHarness::take_events(),
+            [
+                "onConnect(true)",
+                "onData(<a/>)",
+                "onData(<b/>)",
+                "onData(<c/>)"
+            ]
+        );
+    });
+}
+
+#[test]
+fn data_arriving_before_a_failed_connect_is_dropped() {
+    with_avm2_socket(|activation, object, socket| {
+        socket.receive(b"hello");
+        Sockets::update_sockets(&mut activation.context);
+        socket.push(SocketAction::Connect(
+            socket.handle,
+            ConnectionState::Failed(None),
+        ));
+        Sockets::update_sockets(&mut activation.context);
+
+        assert!(object.read_buffer().is_empty());
+    });
+}
+
+#[test]
+fn send_slice_sends_borrowed_data() {
+    with_xml_socket(|activation, _object, socket| {
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+
+        let sockets = &mut activation.context.sockets;
+        let message = b"<msg/>\0";
+        sockets.send_slice(socket.handle, message).unwrap();
+        assert_eq!(socket.written(), message);
+
+        sockets.set_global_max_bytes_per_sec(Some(4));
+        sockets.send_slice(socket.handle, message).unwrap();
+        assert_eq!(socket.written(), b"<msg");
+    });
+}
+
+#[test]
+fn write_drained_is_reset_by_new_data() {
+    with_xml_socket(|activation, _object, socket| {
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+
+        let sockets = &mut activation.context.sockets;
+        sockets.send(socket.handle, b"one\0".to_vec()).unwrap();
+        assert!(!sockets.is_write_drained(socket.handle));
+
+        socket.push(SocketAction::Sent(socket.handle, 4));
+        socket.push(SocketAction::WriteDrained(socket.handle));
+        Sockets::update_sockets(&mut activation.context);
+        assert!(activation.context.sockets.is_write_drained(socket.handle));
+
+        // A drain reported while newer data is still unacknowledged doesn't cover that data.
+        let sockets = &mut activation.context.sockets;
+        sockets.send(socket.handle, b"two\0".to_vec()).unwrap();
+        socket.push(SocketAction::WriteDrained(socket.handle));
+        Sockets::update_sockets(&mut activation.context);
+        assert!(!activation.context.sockets.is_write_drained(socket.handle));
+    });
+}
+
+#[test]
+fn records_can_span_data_chunks() {
+    with_avm2_socket(|activation, target, socket| {
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+
+        target.set_record_size(4);
+        socket.receive(b"ab");
+        Sockets::update_sockets(&mut activation.context);
+        assert!(target.read_buffer().is_empty());
+
+        socket.receive(b"cdef");
+        socket.receive(b"ghij");
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(*target.read_buffer(), b"abcdefgh");
+        assert_eq!(*target.partial_record(), b"ij");
+    });
+}
+
+#[test]
+fn pending_action_count_does_not_consume_actions() {
+    with_xml_socket(|activation, _object, socket| {
+        assert_eq!(activation.context.sockets.pending_action_count(), 0);
+
+        socket.connect();
+        socket.receive(b"<a/>\0");
+        assert_eq!(activation.context.sockets.pending_action_count(), 2);
+        assert_eq!(activation.context.sockets.pending_action_count(), 2);
+
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(activation.context.sockets.pending_action_count(), 0);
+    });
+}
+
+#[test]
+fn ports_are_validated_without_wrapping() {
+    assert_eq!(validate_port(8080.0), Some(8080));
+    assert_eq!(validate_port(8080.7), Some(8080));
+    assert_eq!(validate_port(0.0), Some(0));
+    assert_eq!(validate_port(65535.0), Some(65535));
+    assert_eq!(validate_port(65536.0), None);
+    assert_eq!(validate_port(65616.0), None);
+    assert_eq!(validate_port(-1.0), None);
+    assert_eq!(validate_port(f64::NAN), None);
+    assert_eq!(validate_port(f64::INFINITY), None);
+}
+
+#[test]
+fn xml_socket_with_invalid_port_fails_to_connect() {
+    with_xml_socket_object(|activation, object, loopback_sockets| {
+        SocketTestHarness::record_avm1(activation, object);
+
+        xml_socket::connect(activation, object, &["localhost".into(), 70000.into()])?;
+        assert!(loopback_sockets.borrow().is_empty());
+
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(SocketTestHarness::take_events(), ["onConnect(false)"]);
+        Ok(())
+    });
+}
+
+#[test]
+fn rebind_keeps_the_connection_and_buffers() {
+    with_xml_socket(|activation, object, socket| {
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+
+        let old = XmlSocket::cast(object.into()).unwrap();
+        old.read_buffer().extend(b"<partial");
+
+        let new_object = new_xml_socket(activation);
+        SocketTestHarness::record_avm1(activation, new_object);
+
+        assert!(activation
+            .context
+            .sockets
+            .rebind_avm1(socket.handle, new_object));
+        Sockets::update_sockets(&mut activation.context);
+
+        let new = XmlSocket::cast(new_object.into()).unwrap();
+        assert_eq!(new.handle(), Some(socket.handle));
+        assert_eq!(old.handle(), None);
+        assert_eq!(*new.read_buffer(), b"<partial");
+        assert_eq!(SocketTestHarness::take_events(), ["onConnect(true)"]);
+        assert!(activation.context.sockets.is_connected(socket.handle));
+    });
+}
+
+#[test]
+fn rebind_rejects_objects_of_another_kind() {
+    with_avm2_socket(|activation, _target, socket| {
+        let object: Avm1Object<'_> = ScriptObject::new(activation.gc(), None).into();
+
+        assert!(!activation
+            .context
+            .sockets
+            .rebind_avm1(socket.handle, object));
+    });
+}
+
+#[test]
+fn oversized_sends_are_split() {
+    with_xml_socket(|activation, _object, socket| {
+        let sockets = &mut activation.context.sockets;
+        sockets.set_max_send_chunk_size(NonZeroUsize::new(4));
+        sockets.send(socket.handle, b"0123456789".to_vec()).unwrap();
+        sockets.send(socket.handle, b"abcd".to_vec()).unwrap();
+
+        assert_eq!(
+            socket.commands(),
+            vec![
+                SocketCommand::Send(b"0123".to_vec()),
+                SocketCommand::Send(b"4567".to_vec()),
+                SocketCommand::Send(b"89".to_vec()),
+                SocketCommand::Send(b"abcd".to_vec()),
+            ]
+        );
+    });
+}
+
+#[test]
+fn closed_action_channel_is_recreated() {
+    with_xml_socket(|activation, _object, socket| {
+        activation.context.sockets.sender.close();
+        Sockets::update_sockets(&mut activation.context);
+
+        // The backend behind the socket reports over the closed channel, so it was given up on.
+        assert!(activation.context.sockets.active_handles().is_empty());
+        assert!(!activation.context.sockets.sender.is_closed());
+    });
+}
+
+#[test]
+fn event_cap_takes_turns_between_sockets() {
+    with_loopback(|activation, loopback_sockets| {
+        let busy_object = new_xml_socket(activation);
+        let busy = connect_xml_socket(activation, busy_object, loopback_sockets);
+        let quiet_object = new_xml_socket(activation);
+        let quiet = connect_xml_socket(activation, quiet_object, loopback_sockets);
+
+        busy.connect();
+        quiet.connect();
+        Sockets::update_sockets(&mut activation.context);
+
+        activation
+            .context
+            .sockets
+            .set_max_events_per_update(NonZeroUsize::new(2));
+        for _ in 0..3 {
+            busy.receive(b"<busy/>\0");
+        }
+        quiet.receive(b"<quiet/>\0");
+
+        let busy_xml = XmlSocket::cast(busy_object.into()).unwrap();
+        let quiet_xml = XmlSocket::cast(quiet_object.into()).unwrap();
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(activation.context.sockets.pending_action_count(), 2);
+        assert_eq!(
+            activation
+                .context
+                .sockets
+                .stats(busy.handle)
+                .unwrap()
+                .messages_received,
+            1
+        );
+        assert_eq!(
+            activation
+                .context
+                .sockets
+                .stats(quiet.handle)
+                .unwrap()
+                .messages_received,
+            1
+        );
+
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(activation.context.sockets.pending_action_count(), 0);
+        assert_eq!(
+            activation
+                .context
+                .sockets
+                .stats(busy.handle)
+                .unwrap()
+                .messages_received,
+            3
+        );
+        assert!(busy_xml.read_buffer().is_empty());
+        assert!(quiet_xml.read_buffer().is_empty());
+        Ok(())
+    });
+}
+
+/// Flips the lowest bit of every byte, and only decodes whole pairs of bytes.
+#[derive(Default)]
+struct PairCodec {
+    partial: Vec<u8>,
+}
+
+impl SocketCodec for PairCodec {
+    fn encode(&mut self, data: Vec<u8>) -> Vec<u8> {
+        data.into_iter().map(|byte| byte ^ 1).collect()
+    }
+
+    fn decode(&mut self, data: Vec<u8>) -> Vec<u8> {
+        self.partial.extend(data);
+        let ready = self.partial.len() - self.partial.len() % 2;
+        self.partial.drain(..ready).map(|byte| byte ^ 1).collect()
+    }
+}
+
+#[test]
+fn codec_transforms_both_directions() {
+    with_avm2_socket(|activation, target, socket| {
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+
+        let sockets = &mut activation.context.sockets;
+        sockets.set_codec(socket.handle, Some(Box::<PairCodec>::default()));
+
+        sockets.send(socket.handle, b"ab".to_vec()).unwrap();
+        assert_eq!(socket.written(), b"`c");
+
+        socket.receive(b"`");
+        Sockets::update_sockets(&mut activation.context);
+        assert!(target.read_buffer().is_empty());
+
+        socket.receive(b"cd");
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(*target.read_buffer(), b"ab");
+    });
+}
+
+#[test]
+fn local_address_is_available_on_connect() {
+    with_avm2_socket(|activation, _target, socket| {
+        let address = SocketAddr::from(([127, 0, 0, 1], 50123));
+        socket.push(SocketAction::LocalAddress(socket.handle, address));
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+
+        assert_eq!(
+            activation.context.sockets.local_address(socket.handle),
+            Some(address)
+        );
+    });
+}
+
+#[test]
+fn extract_messages_from_an_empty_buffer() {
+    let mut buffer = vec![];
+    assert!(extract_messages(&mut buffer, &[0], 0).is_empty());
+    assert!(buffer.is_empty());
+}
+
+#[test]
+fn extract_messages_without_a_delimiter() {
+    let mut buffer = b"partial".to_vec();
+    assert!(extract_messages(&mut buffer, &[0], 0).is_empty());
+    assert_eq!(buffer, b"partial");
+}
+
+#[test]
+fn extract_messages_with_a_leading_delimiter() {
+    let mut buffer = b"\0hello\0rest".to_vec();
+    assert_eq!(
+        extract_messages(&mut buffer, &[0], 0),
+        vec![b"".to_vec(), b"hello".to_vec()]
+    );
+    assert_eq!(buffer, b"rest");
+}
+
+#[test]
+fn extract_messages_with_back_to_back_delimiters() {
+    let mut buffer = b"a\0\0\0b\0".to_vec();
+    assert_eq!(
+        extract_messages(&mut buffer, &[0], 0),
+        vec![b"a".to_vec(), b"".to_vec(), b"".to_vec(), b"b".to_vec()]
+    );
+    assert!(buffer.is_empty());
+}
+
+#[cfg(feature = "socket_debug")]
+#[test]
+fn queued_bytes_are_shared_with_the_backend() {
+    with_avm2_socket(|activation, _target, socket| {
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+
+        activation
+            .context
+            .sockets
+            .send(socket.handle, b"Hello!".to_vec())
+            .unwrap();
+        assert_eq!(
+            activation.context.sockets.queued_bytes(socket.handle),
+            Some(6)
+        );
+
+        socket.options.queued_bytes.remove(4);
+        assert_eq!(
+            activation.context.sockets.queued_bytes(socket.handle),
+            Some(2)
+        );
+    });
+}
+
+#[test]
+fn fallback_hosts_are_passed_to_the_backend() {
+    with_avm2_socket_object(|activation, target, loopback_sockets| {
+        activation
+            .context
+            .sockets
+            .set_connect_filter(Some(Box::new(|host: &str, _port: u16| {
+                host != "blocked.example.com"
+            })));
+
+        let UpdateContext {
+            sockets, navigator, ..
+        } = &mut activation.context;
+        sockets.connect_avm2_with_fallbacks(
+            *navigator,
+            target,
+            "localhost".to_string(),
+            8080,
+            vec![
+                ("blocked.example.com".to_string(), 8080),
+                ("backup.example.com".to_string(), 8081),
+            ],
+        );
+        let socket = take_loopback_socket(loopback_sockets);
+        assert_eq!(
+            socket.options.fallback_hosts,
+            vec![("backup.example.com".to_string(), 8081)]
+        );
+
+        let address = SocketAddr::from(([192, 0, 2, 1], 8081));
+        socket.push(SocketAction::RemoteAddress(socket.handle, address));
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(
+            activation.context.sockets.remote_address(socket.handle),
+            Some(address)
+        );
+    });
+}
+
+#[test]
+fn probe_reports_the_outcome_without_a_socket() {
+    with_loopback(|activation, loopback_sockets| {
+        let UpdateContext {
+            sockets, navigator, ..
+        } = &mut activation.context;
+        let result = sockets.probe(
+            *navigator,
+            "localhost".to_string(),
+            8080,
+            Duration::from_secs(5),
+        );
+
+        let socket = take_loopback_socket(loopback_sockets);
+        assert_eq!((socket.host.as_str(), socket.port), ("localhost", 8080));
+        socket.connect();
+        assert_eq!(
+            futures::executor::block_on(result),
+            ConnectionState::Connected
+        );
+        assert!(activation.context.sockets.sockets.is_empty());
+
+        activation
+            .context
+            .sockets
+            .set_connect_filter(Some(Box::new(|_host: &str, _port: u16| false)));
+        let UpdateContext {
+            sockets, navigator, ..
+        } = &mut activation.context;
+        let result = sockets.probe(
+            *navigator,
+            "localhost".to_string(),
+            8080,
+            Duration::from_secs(5),
+        );
+        assert!(loopback_sockets.borrow().is_empty());
+        assert_eq!(
+            futures::executor::block_on(result),
+            ConnectionState::SecurityBlocked
+        );
+        Ok(())
+    });
+}
+
+#[test]
+fn failed_flush_keeps_the_write_buffer() {
+    with_avm2_socket_object(|activation, target, loopback_sockets| {
+        let socket = connect_avm2_socket(activation, target, loopback_sockets);
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+
+        // The backend going away drops its end of the channel.
+        let handle = socket.handle;
+        drop(socket);
+
+        target.write_buffer().extend(b"Hello");
+        activation.context.sockets.flush(handle);
+        assert_eq!(*target.write_buffer(), b"Hello");
+    });
+}
+
+#[test]
+fn sending_over_a_closed_socket_fails() {
+    with_avm2_socket(|activation, _target, socket| {
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+
+        let sockets = &mut activation.context.sockets;
+        sockets.close(socket.handle);
+        assert_eq!(
+            sockets.send(socket.handle, b"lost".to_vec()),
+            Err(SendError::NoSuchSocket)
+        );
+        assert_eq!(
+            sockets.send_priority(socket.handle, b"lost".to_vec()),
+            Err(SendError::NoSuchSocket)
+        );
+        assert!(socket.written().is_empty());
+    });
+}
+
+#[test]
+fn harness_records_the_avm1_event_sequence() {
+    with_xml_socket(|activation, object, socket| {
+        SocketTestHarness::record_avm1(activation, object);
+
+        socket.connect();
+        socket.receive(b"<a/>\0<b");
+        socket.receive(b"/>\0\0");
+        socket.close();
+        Sockets::update_sockets(&mut activation.context);
+
+        assert_eq!(
+            SocketTestHarness::take_events(),
+            [
+                "onConnect(true)",
+                "onData(<a/>)",
+                "onData(<b/>)",
+                "onData()",
+                "onClose()"
+            ]
+        );
+    });
+}
+
+#[test]
+fn harness_records_the_avm2_event_sequence() {
+    with_avm2_socket(|activation, target, socket| {
+        SocketTestHarness::record_avm2(activation, target);
+
+        socket.connect();
+        socket.receive(b"data");
+        socket.close();
+        Sockets::update_sockets(&mut activation.context);
+
+        assert_eq!(
+            SocketTestHarness::take_events(),
+            ["connect", "socketData(4)", "close"]
+        );
+    });
+
+    with_avm2_socket(|activation, target, socket| {
+        SocketTestHarness::record_avm2(activation, target);
+
+        socket.push(SocketAction::Connect(
+            socket.handle,
+            ConnectionState::Failed(Some("Connection refused".to_string())),
+        ));
+        Sockets::update_sockets(&mut activation.context);
+
+        assert_eq!(
+            SocketTestHarness::take_events(),
+            ["ioError(Error #2031: Socket Error. Connection refused)"]
+        );
+    });
+}
+
+#[test]
+fn bind_device_is_propagated() {
+    with_xml_socket_object(|activation, object, loopback_sockets| {
+        let socket = connect_xml_socket(activation, object, loopback_sockets);
+        assert_eq!(socket.options.bind_device, None);
+
+        activation
+            .context
+            .sockets
+            .set_bind_device(Some("eth1".to_string()));
+        let socket = connect_xml_socket(activation, object, loopback_sockets);
+        assert_eq!(socket.options.bind_device.as_deref(), Some("eth1"));
+        Ok(())
+    });
+}
+
+#[test]
+fn replacing_an_open_connection_fires_close_if_enabled() {
+    with_avm2_socket_object(|activation, target, loopback_sockets| {
+        SocketTestHarness::record_avm2(activation, target);
+        let host = AvmString::new_utf8(activation.gc(), "localhost");
+        let args = [host.into(), 8080.into()];
+
+        // By default, the replaced connection just goes away.
+        avm2_socket::connect(activation, target.into(), &args).unwrap();
+        let first = take_loopback_socket(loopback_sockets);
+        first.connect();
+        Sockets::update_sockets(&mut activation.context);
+        avm2_socket::connect(activation, target.into(), &args).unwrap();
+        let second = take_loopback_socket(loopback_sockets);
+        second.connect();
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(SocketTestHarness::take_events(), ["connect", "connect"]);
+
+        activation
+            .context
+            .sockets
+            .set_dispatch_close_on_replace(true);
+        avm2_socket::connect(activation, target.into(), &args).unwrap();
+        let third = take_loopback_socket(loopback_sockets);
+        assert!(!activation.context.sockets.is_connected(second.handle));
+        // The close event isn't fired from within `connect`.
+        assert!(SocketTestHarness::take_events().is_empty());
+
+        // A connection that is still pending never opened, so it goes away without an event.
+        avm2_socket::connect(activation, target.into(), &args).unwrap();
+        let fourth = take_loopback_socket(loopback_sockets);
+        assert!(!activation.context.sockets.is_connected(third.handle));
+
+        fourth.connect();
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(SocketTestHarness::take_events(), ["close", "connect"]);
+    });
+}
+
+#[test]
+fn close_hands_held_back_data_to_the_backend() {
+    with_avm2_socket(|activation, target, socket| {
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+
+        let sockets = &mut activation.context.sockets;
+        sockets.set_global_max_bytes_per_sec(Some(4));
+        sockets.set_close_drain_timeout(Some(Duration::from_millis(10)));
+
+        target.write_bytes(b"0123456789");
+        sockets.flush(socket.handle);
+        target.write_bytes(b"ab");
+        assert_eq!(socket.written(), b"0123");
+
+        sockets.close(socket.handle);
+        assert_eq!(socket.written(), b"456789");
+    });
+}
+
+#[test]
+fn close_waits_for_the_backend_to_acknowledge_the_flush() {
+    with_avm2_socket(|activation, target, socket| {
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+
+        let sockets = &mut activation.context.sockets;
+        sockets.set_close_drain_timeout(Some(Duration::from_secs(60)));
+        target.write_bytes(b"bye");
+        sockets.flush(socket.handle);
+        sockets.close(socket.handle);
+
+        assert_eq!(
+            socket.commands(),
+            [SocketCommand::Send(b"bye".to_vec()), SocketCommand::Flush]
+        );
+        assert!(!sockets.is_connected(socket.handle));
+        assert!(!socket.is_closed());
+
+        // Actions the backend reports meanwhile don't reach the movie anymore.
+        socket.receive(b"late");
+        socket.push(SocketAction::Flushed(socket.handle));
+        Sockets::update_sockets(&mut activation.context);
+        assert!(socket.is_closed());
+        assert!(target.read_buffer().is_empty());
+    });
+}
+
+#[test]
+fn close_gives_up_on_the_flush_after_the_timeout() {
+    with_avm2_socket(|activation, _target, socket| {
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+
+        let sockets = &mut activation.context.sockets;
+        sockets.set_close_drain_timeout(Some(Duration::ZERO));
+        sockets.close(socket.handle);
+        assert_eq!(socket.commands(), [SocketCommand::Flush]);
+        assert!(!socket.is_closed());
+
+        Sockets::update_sockets(&mut activation.context);
+        assert!(socket.is_closed());
+    });
+}
+
+#[test]
+fn pending_connections_are_closed_without_a_flush() {
+    with_avm2_socket(|activation, _target, socket| {
+        let sockets = &mut activation.context.sockets;
+        sockets.set_close_drain_timeout(Some(Duration::from_secs(60)));
+        sockets.close(socket.handle);
+
+        assert!(socket.commands().is_empty());
+        assert!(socket.is_closed());
+    });
+}
+
+#[test]
+fn tags_are_listed_with_the_sockets() {
+    with_xml_socket(|activation, _object, socket| {
+        let sockets = &mut activation.context.sockets;
+        sockets.set_tag(socket.handle, Some("chat server".to_string()));
+
+        let tags: Vec<_> = sockets
+            .describe_sockets()
+            .map(|(_, _, _, tag)| tag)
+            .collect();
+        assert_eq!(tags, [Some("chat server")]);
+
+        sockets.set_tag(socket.handle, None);
+        let tags: Vec<_> = sockets
+            .describe_sockets()
+            .map(|(_, _, _, tag)| tag)
+            .collect();
+        assert_eq!(tags, [None]);
+    });
+}
+
+#[test]
+fn abort_resets_the_connection() {
+    with_avm2_socket(|activation, target, socket| {
+        SocketTestHarness::record_avm2(activation, target);
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+        let _ = SocketTestHarness::take_events();
+
+        target.write_bytes(b"pending");
+        socket.receive(b"stale");
+        activation.context.sockets.abort(socket.handle, false);
+
+        assert_eq!(socket.commands().last(), Some(&SocketCommand::Abort));
+        assert!(!activation.context.sockets.is_connected(socket.handle));
+        assert!(target.write_buffer().is_empty());
+        assert_eq!(target.close_reason(), Some(CloseReason::Aborted));
+
+        // Like a local close, no event fires unless asked for.
+        Sockets::update_sockets(&mut activation.context);
+        assert!(SocketTestHarness::take_events().is_empty());
+    });
+}
+
+#[test]
+fn abort_can_dispatch_close() {
+    with_avm2_socket(|activation, target, socket| {
+        SocketTestHarness::record_avm2(activation, target);
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+        let _ = SocketTestHarness::take_events();
+
+        socket.receive(b"stale");
+        activation.context.sockets.abort(socket.handle, true);
+        assert_eq!(socket.commands().last(), Some(&SocketCommand::Abort));
+        assert!(!activation.context.sockets.is_connected(socket.handle));
+
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(SocketTestHarness::take_events(), ["close"]);
+        assert_eq!(target.close_reason(), Some(CloseReason::Aborted));
+    });
+}
+
+#[test]
+fn pool_idle_timeout_is_propagated() {
+    with_avm2_socket(|_activation, _target, socket| {
+        assert_eq!(socket.options.pool_idle_timeout, None);
+    });
+
+    with_xml_socket_object(|activation, object, loopback_sockets| {
+        activation
+            .context
+            .sockets
+            .set_connection_pool_idle_timeout(Some(Duration::from_secs(30)));
+        let socket = connect_xml_socket(activation, object, loopback_sockets);
+        assert_eq!(
+            socket.options.pool_idle_timeout,
+            Some(Duration::from_secs(30))
+        );
+        Ok(())
+    });
+}
+
+#[test]
+fn failed_connections_are_reported_to_the_diagnostic_sink() {
+    with_avm2_socket(|activation, _target, socket| {
+        let (sink, diagnostics) = std::sync::mpsc::channel();
+        activation.context.sockets.set_diagnostic_sink(Some(sink));
+
+        socket.push(SocketAction::Connect(
+            socket.handle,
+            ConnectionState::TimedOut,
+        ));
+        Sockets::update_sockets(&mut activation.context);
+
+        let diagnostic = diagnostics.try_recv().expect("failure should be reported");
+        assert_eq!(diagnostic.handle, socket.handle);
+        assert_eq!(diagnostic.host, "localhost");
+        assert_eq!(diagnostic.port, 8080);
+        assert_eq!(diagnostic.state, ConnectionState::TimedOut);
+        assert!(diagnostics.try_recv().is_err());
+    });
+
+    with_avm2_socket(|activation, _target, socket| {
+        let (sink, diagnostics) = std::sync::mpsc::channel();
+        activation.context.sockets.set_diagnostic_sink(Some(sink));
+
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+        assert!(diagnostics.try_recv().is_err());
+    });
+}
+
+#[test]
+fn extract_messages_with_a_multi_byte_delimiter() {
+    let mut buffer = b"a\r\nb\r\r\n\r".to_vec();
+    assert_eq!(
+        extract_messages(&mut buffer, b"\r\n", 0),
+        vec![b"a".to_vec(), b"b\r".to_vec()]
+    );
+    assert_eq!(buffer, b"\r");
+}
+
+#[test]
+fn extract_messages_with_an_empty_delimiter() {
+    let mut buffer = b"a\0b".to_vec();
+    assert!(extract_messages(&mut buffer, b"", 0).is_empty());
+    assert_eq!(buffer, b"a\0b");
+}
+
+#[test]
+fn xml_socket_rejects_an_empty_delimiter() {
+    with_xml_socket_object(|_activation, object, _loopback_sockets| {
+        let xml_socket = XmlSocket::cast(object.into()).expect("object should be XmlSocket");
+        assert_eq!(
+            xml_socket.set_delimiter(vec![]),
+            Err(xml_socket::EmptyDelimiter)
+        );
+        assert_eq!(&*xml_socket.delimiter(), b"\0");
+        Ok(())
+    });
+}
+
+#[test]
+fn xml_socket_delimiter_may_straddle_chunks() {
+    with_xml_socket(|activation, object, socket| {
+        SocketTestHarness::record_avm1(activation, object);
+        XmlSocket::cast(object.into())
+            .expect("object should be XmlSocket")
+            .set_delimiter(b"\r\n".to_vec())
+            .unwrap();
+
+        socket.connect();
+        socket.receive(b"<a/>\r");
+        socket.receive(b"\n<b/>\r");
+        socket.receive(b"\n");
+        Sockets::update_sockets(&mut activation.context);
+
+        assert_eq!(
+            SocketTestHarness::take_events(),
+            ["onConnect(true)", "onData(<a/>)", "onData(<b/>)"]
+        );
+    });
+}
+
+#[test]
+fn flush_all_sends_every_write_buffer() {
+    with_loopback(|activation, loopback_sockets| {
+        let mut activation = Avm2Activation::from_nothing(activation.context.reborrow());
+        let mut targets = vec![];
+
+        for port in [8080, 8081] {
+            let target = new_avm2_socket(&mut activation);
+            let UpdateContext {
+                sockets, navigator, ..
+            } = &mut activation.context;
+            sockets.connect_avm2(*navigator, target, "localhost".to_string(), port);
+
+            let socket = take_loopback_socket(loopback_sockets);
+            socket.connect();
+            targets.push((target, socket));
+        }
+        Sockets::update_sockets(&mut activation.context);
+
+        for (index, (target, _)) in targets.iter().enumerate() {
+            target.write_bytes(format!("socket {index}").as_bytes());
+        }
+        activation.context.sockets.flush_all();
+
+        for (index, (target, socket)) in targets.iter().enumerate() {
+            assert_eq!(socket.written(), format!("socket {index}").into_bytes());
+            assert!(target.write_buffer().is_empty());
+        }
+        Ok(())
+    });
+}
+
+#[test]
+fn busy_xml_socket_is_force_closed() {
+    with_xml_socket(|activation, object, socket| {
+        SocketTestHarness::record_avm1(activation, object);
+        activation
+            .context
+            .sockets
+            .set_max_connection_lifetime(Some(Duration::from_secs(60)));
+
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+
+        // Activity doesn't extend the lifetime of a connection.
+        socket.receive(b"<ping/>\0");
+        let later = Instant::now() + Duration::from_secs(2 * 60);
+        activation.context.sockets.close_expired_sockets(later);
+        Sockets::update_sockets(&mut activation.context);
+
+        assert_eq!(
+            SocketTestHarness::take_events(),
+            ["onConnect(true)", "onData(<ping/>)", "onClose()"]
+        );
+        let xml_socket = XmlSocket::cast(object.into()).expect("object should be XmlSocket");
+        assert_eq!(xml_socket.last_error(), Some("policy"));
+    });
+}
+
+#[test]
+fn subclasses_may_override_event_types() {
+    with_avm2_socket(|activation, target, socket| {
+        SocketTestHarness::record_avm2(activation, target);
+        add_native_event_listener(
+            activation,
+            target.into(),
+            "mySocketData",
+            SocketTestHarness::record_avm2_event,
+        )
+        .unwrap();
+
+        assert!(target.set_event_type("socketData", "mySocketData"));
+        // Empty and unknown overrides leave the standard types in place.
+        assert!(target.set_event_type("connect", ""));
+        assert!(!target.set_event_type("bogus", "myBogus"));
+
+        socket.connect();
+        socket.receive(b"data");
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(
+            SocketTestHarness::take_events(),
+            ["connect", "mySocketData"]
+        );
+
+        assert!(target.set_event_type("socketData", ""));
+        socket.receive(b"more");
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(SocketTestHarness::take_events(), ["socketData(4)"]);
+    });
+}
+
+#[test]
+fn latency_is_recorded_between_sends_and_data() {
+    with_xml_socket(|activation, _object, socket| {
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+
+        // Nothing is recorded unless asked for.
+        activation
+            .context
+            .sockets
+            .send(socket.handle, b"<a/>\0".to_vec())
+            .unwrap();
+        socket.receive(b"<b/>\0");
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(
+            activation.context.sockets.latency_stats(socket.handle),
+            Some(LatencyStats::default())
+        );
+
+        activation.context.sockets.set_latency_tracking(true);
+        // Data that arrives without a send before it isn't a sample.
+        socket.receive(b"<c/>\0");
+        Sockets::update_sockets(&mut activation.context);
+
+        let sockets = &mut activation.context.sockets;
+        sockets.send(socket.handle, b"<d/>\0".to_vec()).unwrap();
+        sockets.send(socket.handle, b"<e/>\0".to_vec()).unwrap();
+        socket.receive(b"<f/>\0");
+        Sockets::update_sockets(&mut activation.context);
+
+        let stats = activation
+            .context
+            .sockets
+            .latency_stats(socket.handle)
+            .unwrap();
+        assert_eq!(stats.samples, 1);
+        assert_eq!(stats.buckets.iter().sum::<u64>(), 1);
+        assert_eq!(stats.mean(), Some(stats.total));
+    });
+}
+
+#[test]
+fn remote_family_follows_the_remote_address() {
+    with_avm2_socket(|activation, _target, socket| {
+        assert_eq!(
+            activation.context.sockets.remote_family(socket.handle),
+            None
+        );
+
+        let address = SocketAddr::from(([0x2001, 0xdb8, 0, 0, 0, 0, 0, 1], 8080));
+        socket.push(SocketAction::RemoteAddress(socket.handle, address));
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(
+            activation.context.sockets.remote_family(socket.handle),
+            Some(IpFamily::V6)
+        );
+    });
+
+    let mapped: IpAddr = "::ffff:192.0.2.1".parse().unwrap();
+    assert_eq!(IpFamily::from(mapped), IpFamily::V4);
+}
+
+#[test]
+fn socket_is_not_connected_while_connecting() {
+    with_avm2_socket(|activation, target, socket| {
+        let connected = avm2_socket::get_connected(activation, target.into(), &[]).unwrap();
+        assert_eq!(connected, Avm2Value::Bool(false));
+        assert!(!activation.context.sockets.is_connected(socket.handle));
+
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+        let connected = avm2_socket::get_connected(activation, target.into(), &[]).unwrap();
+        assert_eq!(connected, Avm2Value::Bool(true));
+    });
+}
+
+#[test]
+fn read_buffer_is_reserved_on_connect() {
+    with_avm2_socket(|activation, target, socket| {
+        activation
+            .context
+            .sockets
+            .set_read_buffer_capacity(64 * 1024);
+
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+        assert!(target.read_buffer().capacity() >= 64 * 1024);
+    });
+
+    with_xml_socket(|activation, object, socket| {
+        // Huge requests are clamped, so they can't exhaust memory.
+        activation
+            .context
+            .sockets
+            .set_read_buffer_capacity(usize::MAX);
+
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+        let xml_socket = XmlSocket::cast(object.into()).expect("object should be XmlSocket");
+        let capacity = xml_socket.read_buffer().capacity();
+        assert!((MAX_READ_BUFFER_CAPACITY..2 * MAX_READ_BUFFER_CAPACITY).contains(&capacity));
+    });
+}
+
+#[test]
+fn host_rewriter_redirects_connections() {
+    with_xml_socket_object(|activation, object, loopback_sockets| {
+        activation.context.sockets.set_host_rewriter(
+            Some(Box::new(|host: &str, port: u16| {
+                (host == "game.example.com").then(|| ("staging.example.com".to_string(), port + 1))
+            })),
+            false,
+        );
+        activation
+            .context
+            .sockets
+            .set_connect_filter(Some(Box::new(|host: &str, _port: u16| {
+                host != "staging.example.com"
+            })));
+
+        let UpdateContext {
+            sockets, navigator, ..
+        } = &mut activation.context;
+        sockets.connect_avm1(*navigator, object, "game.example.com".to_string(), 8080);
+
+        let socket = take_loopback_socket(loopback_sockets);
+        assert_eq!(
+            (socket.host.as_str(), socket.port),
+            ("staging.example.com", 8081)
+        );
+
+        let UpdateContext {
+            sockets, navigator, ..
+        } = &mut activation.context;
+        sockets.connect_avm1(*navigator, object, "other.example.com".to_string(), 8080);
+        let socket = take_loopback_socket(loopback_sockets);
+        assert_eq!(
+            (socket.host.as_str(), socket.port),
+            ("other.example.com", 8080)
+        );
+        Ok(())
+    });
+}
+
+#[test]
+fn rewritten_hosts_can_be_policy_checked() {
+    with_xml_socket_object(|activation, object, loopback_sockets| {
+        activation.context.sockets.set_host_rewriter(
+            Some(Box::new(|_host: &str, port: u16| {
+                Some(("staging.example.com".to_string(), port))
+            })),
+            true,
+        );
+        activation
+            .context
+            .sockets
+            .set_connect_filter(Some(Box::new(|host: &str, _port: u16| {
+                host != "staging.example.com"
+            })));
+
+        let UpdateContext {
+            sockets, navigator, ..
+        } = &mut activation.context;
+        sockets.connect_avm1(*navigator, object, "game.example.com".to_string(), 8080);
+
+        assert!(loopback_sockets.borrow().is_empty());
+        let handle = XmlSocket::cast(object.into()).unwrap().handle().unwrap();
+        assert_eq!(
+            pending_actions(&activation.context.sockets),
+            vec![SocketAction::Connect(
+                handle,
+                ConnectionState::SecurityBlocked
+            )]
+        );
+        Ok(())
+    });
+}
+
+#[test]
+fn read_watermark_is_dispatched_once_per_crossing() {
+    with_avm2_socket(|activation, target, socket| {
+        SocketTestHarness::record_avm2(activation, target);
+        activation
+            .context
+            .sockets
+            .set_read_watermark(NonZeroUsize::new(8));
+
+        socket.connect();
+        socket.receive(b"abcd");
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(
+            SocketTestHarness::take_events(),
+            ["connect", "socketData(4)"]
+        );
+
+        socket.receive(b"efgh");
+        socket.receive(b"ijkl");
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(
+            SocketTestHarness::take_events(),
+            ["socketData(4)", "readWatermark(8)", "socketData(4)"]
+        );
+
+        // Draining the buffer below half the watermark rearms the event.
+        target.read_buffer().drain(..11);
+        socket.receive(b"mn");
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(SocketTestHarness::take_events(), ["socketData(2)"]);
+
+        socket.receive(b"opqrst");
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(
+            SocketTestHarness::take_events(),
+            ["socketData(6)", "readWatermark(9)"]
+        );
+    });
+}
+
+#[test]
+fn connection_history_lists_attempts_and_outcomes() {
+    with_xml_socket_object(|activation, object, loopback_sockets| {
+        let socket = connect_xml_socket(activation, object, loopback_sockets);
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+
+        activation
+            .context
+            .sockets
+            .set_connect_filter(Some(Box::new(|host: &str, _port: u16| {
+                host != "blocked.example.com"
+            })));
+        let UpdateContext {
+            sockets, navigator, ..
+        } = &mut activation.context;
+        sockets.connect_avm1(*navigator, object, "blocked.example.com".to_string(), 443);
+        let blocked = XmlSocket::cast(object.into()).unwrap().handle().unwrap();
+        Sockets::update_sockets(&mut activation.context);
+
+        assert_eq!(
+            activation
+                .context
+                .sockets
+                .connection_history()
+                .cloned()
+                .collect::<Vec<_>>(),
+            vec![
+                ConnectionAttempt {
+                    handle: socket.handle,
+                    host: "localhost".to_string(),
+                    port: 8080,
+                    outcome: Some(ConnectionState::Connected),
+                },
+                ConnectionAttempt {
+                    handle: blocked,
+                    host: "blocked.example.com".to_string(),
+                    port: 443,
+                    outcome: Some(ConnectionState::SecurityBlocked),
+                },
+            ]
+        );
+
+        // Only the latest attempts are remembered.
+        for _ in 0..MAX_CONNECTION_HISTORY {
+            connect_xml_socket(activation, object, loopback_sockets);
+        }
+        let history = &activation.context.sockets.connection_history;
+        assert_eq!(history.len(), MAX_CONNECTION_HISTORY);
+        assert!(history
+            .iter()
+            .all(|attempt| attempt.host == "localhost" && attempt.outcome.is_none()));
+        Ok(())
+    });
+}
+
+#[cfg(feature = "socket_debug")]
+#[test]
+fn simulated_lag_delays_data_without_reordering() {
+    with_xml_socket(|activation, object, socket| {
+        SocketTestHarness::record_avm1(activation, object);
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(SocketTestHarness::take_events(), ["onConnect(true)"]);
+
+        activation
+            .context
+            .sockets
+            .set_simulated_lag(Some((Duration::from_millis(20), Duration::ZERO)));
+        activation
+            .context
+            .sockets
+            .send(socket.handle, b"<a/>\0".to_vec())
+            .unwrap();
+        socket.receive(b"<b/>\0");
+        socket.close();
+        Sockets::update_sockets(&mut activation.context);
+        assert!(socket.written().is_empty());
+        // The close is held back behind the data.
+        assert!(SocketTestHarness::take_events().is_empty());
+
+        std::thread::sleep(Duration::from_millis(30));
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(socket.written(), b"<a/>\0");
+        assert_eq!(
+            SocketTestHarness::take_events(),
+            ["onData(<b/>)", "onClose()"]
+        );
+    });
+}
+
+#[test]
+fn empty_data_frames_are_ignored() {
+    with_avm2_socket(|activation, target, socket| {
+        SocketTestHarness::record_avm2(activation, target);
+        socket.connect();
+        socket.receive(b"");
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(SocketTestHarness::take_events(), ["connect"]);
+
+        let stats = activation.context.sockets.stats(socket.handle).unwrap();
+        assert_eq!(stats.messages_received, 0);
+    });
+
+    with_xml_socket(|activation, object, socket| {
+        SocketTestHarness::record_avm1(activation, object);
+        socket.connect();
+        socket.receive(b"<a/>");
+        socket.receive(b"");
+        socket.receive(b"\0");
+        Sockets::update_sockets(&mut activation.context);
+        assert_eq!(
+            SocketTestHarness::take_events(),
+            ["onConnect(true)", "onData(<a/>)"]
+        );
+
+        let stats = activation.context.sockets.stats(socket.handle).unwrap();
+        assert_eq!(stats.messages_received, 2);
+    });
+}
+
+#[test]
+fn close_by_target_closes_the_sockets_of_an_object() {
+    with_avm2_socket(|activation, target, socket| {
+        let other = new_avm2_socket(activation);
+        let UpdateContext {
+            sockets, navigator, ..
+        } = &mut activation.context;
+        sockets.connect_avm2(*navigator, other, "localhost".to_string(), 8081);
+        let other_handle = other.handle().unwrap();
+
+        let sockets = &mut activation.context.sockets;
+        assert!(sockets.close_by_target(SocketKind::Avm2(target)));
+        assert!(!sockets.close_by_target(SocketKind::Avm2(target)));
+        assert_eq!(sockets.active_handles(), vec![other_handle]);
+        assert!(!sockets.is_connected(socket.handle));
+    });
+}
+
+#[test]
+fn writing_fails_after_shutdown_write() {
+    with_avm2_socket(|activation, target, socket| {
+        assert!(!activation.context.sockets.can_write(socket.handle));
+
+        socket.connect();
+        Sockets::update_sockets(&mut activation.context);
+        assert!(activation.context.sockets.can_write(socket.handle));
+        avm2_socket::write_utf_bytes(activation, target.into(), &["before".into()]).unwrap();
+
+        activation.context.sockets.shutdown_write(socket.handle);
+        assert!(!activation.context.sockets.can_write(socket.handle));
+        assert!(
+            avm2_socket::write_utf_bytes(activation, target.into(), &["after".into()]).is_err()
+        );
+        assert!(avm2_socket::flush(activation, target.into(), &[]).is_err());
+
+        // The connection stays open, as only the write half is shut down.
+        assert!(activation.context.sockets.is_connected(socket.handle));
+        assert_eq!(&*target.write_buffer(), b"before");
+    });
+}
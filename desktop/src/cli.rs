@@ -119,6 +119,7 @@ pub struct Opt {
     pub config: std::path::PathBuf,
 
     /// Proxy to use when loading movies via URL.
+    /// A `socks4a://` proxy is used for sockets instead.
     #[clap(long)]
     pub proxy: Option<Url>,
 
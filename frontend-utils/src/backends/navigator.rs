@@ -6,7 +6,7 @@ use crate::content::PlayingContent;
 use async_channel::{Receiver, Sender, TryRecvError};
 use async_io::Timer;
 use async_net::TcpStream;
-use futures::future::select;
+use futures::future::{select, Either};
 use futures::{AsyncReadExt, AsyncWriteExt};
 use futures_lite::FutureExt;
 use reqwest::{cookie, header, Proxy};
@@ -16,18 +16,559 @@ use ruffle_core::backend::navigator::{
 };
 use ruffle_core::indexmap::IndexMap;
 use ruffle_core::loader::Error;
-use ruffle_core::socket::{ConnectionState, SocketAction, SocketHandle};
-use std::collections::HashSet;
+use ruffle_core::socket::{
+    CloseReason, ConnectionState, QueuedBytes, SocketAction, SocketCommand, SocketHandle,
+    SocketOptions,
+};
+use std::cell::RefCell;
+use std::collections::{HashSet, VecDeque};
 use std::fs::File;
 use std::io;
 use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr};
+use std::num::NonZeroUsize;
 use std::path::Path;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tracing::warn;
 use url::{ParseError, Url};
 
+/// Data waiting to be written to a socket, split into a priority lane and a bulk lane.
+///
+/// Messages are written one at a time and never interleaved, so priority messages
+/// only jump ahead of bulk messages that haven't started being written yet.
+#[derive(Default)]
+struct WriteQueue {
+    in_flight: Vec<u8>,
+    priority: VecDeque<Vec<u8>>,
+    bulk: VecDeque<Vec<u8>>,
+}
+
+impl WriteQueue {
+    fn push(&mut self, data: Vec<u8>, priority: bool) {
+        if data.is_empty() {
+            return;
+        }
+
+        if priority {
+            self.priority.push_back(data);
+        } else {
+            self.bulk.push_back(data);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.in_flight.len()
+            + self.priority.iter().map(Vec::len).sum::<usize>()
+            + self.bulk.iter().map(Vec::len).sum::<usize>()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.in_flight.is_empty() && self.priority.is_empty() && self.bulk.is_empty()
+    }
+
+    /// The bytes that should be written next.
+    fn next_chunk(&mut self) -> &[u8] {
+        if self.in_flight.is_empty() {
+            if let Some(next) = self.priority.pop_front().or_else(|| self.bulk.pop_front()) {
+                self.in_flight = next;
+            }
+        }
+
+        &self.in_flight
+    }
+
+    /// Drops bytes that were written from the start of [`WriteQueue::next_chunk`].
+    fn consume(&mut self, written: usize) {
+        let _ = self.in_flight.drain(..written);
+    }
+}
+
+/// Applies `TCP_USER_TIMEOUT` to a connected socket, on platforms that support it.
+fn set_tcp_user_timeout(stream: &TcpStream, timeout: Duration) {
+    #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+    if let Err(e) = socket2::SockRef::from(stream).set_tcp_user_timeout(Some(timeout)) {
+        warn!("Failed to set TCP_USER_TIMEOUT on socket: {}", e);
+    }
+
+    #[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux")))]
+    {
+        let _ = (stream, timeout);
+        tracing::debug!("TCP_USER_TIMEOUT is not supported on this platform, ignoring it");
+    }
+}
+
+/// Enables `SO_KEEPALIVE` on a connected socket, probing an idle peer every `interval`.
+fn set_keepalive(stream: &TcpStream, interval: Duration) {
+    let keepalive = socket2::TcpKeepalive::new().with_time(interval);
+    #[cfg(not(any(
+        target_os = "haiku",
+        target_os = "openbsd",
+        target_os = "redox",
+        target_os = "solaris"
+    )))]
+    let keepalive = keepalive.with_interval(interval);
+
+    if let Err(e) = socket2::SockRef::from(stream).set_tcp_keepalive(&keepalive) {
+        warn!("Failed to set SO_KEEPALIVE on socket: {}", e);
+    }
+}
+
+/// Connects to the first of the addresses that accepts the connection, bound to the given
+/// network interface with `SO_BINDTODEVICE`.
+#[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+async fn connect_bound_to_device(
+    addrs: Vec<SocketAddr>,
+    device: String,
+    timeout: Duration,
+) -> io::Result<TcpStream> {
+    let (sender, receiver) = async_channel::bounded(1);
+
+    //NOTE: The device has to be set before connecting, which async_net doesn't allow,
+    //      so the connection is made on a thread of its own.
+    std::thread::spawn(move || {
+        let mut last_error = io::Error::new(ErrorKind::NotFound, "no addresses found");
+
+        for addr in addrs {
+            let connect = || {
+                let socket = socket2::Socket::new(
+                    socket2::Domain::for_address(addr),
+                    socket2::Type::STREAM,
+                    Some(socket2::Protocol::TCP),
+                )?;
+                socket.bind_device(Some(device.as_bytes()))?;
+                socket.connect_timeout(&addr.into(), timeout)?;
+                io::Result::Ok(std::net::TcpStream::from(socket))
+            };
+
+            match connect() {
+                Ok(stream) => {
+                    let _ = sender.send_blocking(Ok(stream));
+                    return;
+                }
+                Err(e) => last_error = e,
+            }
+        }
+
+        let _ = sender.send_blocking(Err(last_error));
+    });
+
+    let stream = receiver
+        .recv()
+        .await
+        .map_err(|_| io::Error::other("connecting thread exited"))??;
+    TcpStream::try_from(stream)
+}
+
+/// How many bytes are read at once, unless the connection asks for a different amount.
+const DEFAULT_READ_CHUNK_SIZE: usize = 4096;
+
+/// Connects to the first address of the host that accepts the connection.
+///
+/// The host is only resolved if its addresses aren't known yet. Addresses resolved by the OS are
+/// reported with [`SocketAction::Resolved`] if `resolved` is given, so the player can cache them.
+async fn connect_tcp(
+    host: &str,
+    port: u16,
+    ips: Option<Vec<IpAddr>>,
+    resolver: Option<&SocketResolver>,
+    resolved: Option<(SocketHandle, &Sender<SocketAction>)>,
+    bind_device: Option<&str>,
+    timeout: Duration,
+) -> Result<TcpStream, (ConnectionState, io::Error)> {
+    // NOTE: We resolve the host ourselves, so that name resolution failures
+    //       can be told apart from refused connections.
+    let ips = ips.or_else(|| resolver.and_then(|resolve| resolve(host)));
+    let addrs = match ips {
+        Some(ips) if ips.is_empty() => {
+            return Err((
+                ConnectionState::DnsError,
+                io::Error::new(ErrorKind::NotFound, "resolver found no addresses"),
+            ))
+        }
+        Some(ips) => ips
+            .into_iter()
+            .map(|ip| SocketAddr::new(ip, port))
+            .collect(),
+        None => match async_net::resolve((host, port)).await {
+            Ok(addrs) if !addrs.is_empty() => {
+                // Literal addresses are not worth caching.
+                if let Some((handle, sender)) = resolved {
+                    if host.parse::<IpAddr>().is_err() {
+                        let ips = addrs.iter().map(SocketAddr::ip).collect();
+                        let _ = sender.try_send(SocketAction::Resolved(handle, ips));
+                    }
+                }
+                addrs
+            }
+            Ok(_) => {
+                return Err((
+                    ConnectionState::DnsError,
+                    io::Error::new(ErrorKind::NotFound, "no addresses found"),
+                ))
+            }
+            Err(e) => return Err((ConnectionState::DnsError, e)),
+        },
+    };
+
+    let stream = match bind_device {
+        #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+        Some(device) => connect_bound_to_device(addrs, device.to_string(), timeout).await,
+        #[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux")))]
+        Some(_) => {
+            static IGNORED: std::sync::Once = std::sync::Once::new();
+            IGNORED.call_once(|| {
+                tracing::debug!("SO_BINDTODEVICE is not supported on this platform, ignoring it");
+            });
+            let _ = timeout;
+            TcpStream::connect(&addrs[..]).await
+        }
+        None => TcpStream::connect(&addrs[..]).await,
+    };
+
+    stream.map_err(|e| (ConnectionState::Failed(Some(e.to_string())), e))
+}
+
+/// Asks a SOCKS4a proxy to connect to the given host and port, leaving the host for the proxy to resolve.
+async fn socks4a_connect(
+    stream: &mut TcpStream,
+    host: &str,
+    port: u16,
+    user_id: &str,
+) -> io::Result<()> {
+    if host.contains('\0') || user_id.contains('\0') {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            "SOCKS4a host and user ID can't contain null bytes",
+        ));
+    }
+
+    let mut request = vec![4, 1];
+    request.extend_from_slice(&port.to_be_bytes());
+    // NOTE: An address of 0.0.0.x tells the proxy that a host name follows the user ID.
+    request.extend_from_slice(&[0, 0, 0, 1]);
+    request.extend_from_slice(user_id.as_bytes());
+    request.push(0);
+    request.extend_from_slice(host.as_bytes());
+    request.push(0);
+    stream.write_all(&request).await?;
+
+    let mut reply = [0; 8];
+    stream.read_exact(&mut reply).await?;
+    match reply {
+        [0, 0x5A, ..] => Ok(()),
+        [0, code, ..] => Err(io::Error::new(
+            ErrorKind::ConnectionRefused,
+            format!("SOCKS4a proxy refused the connection (reply {code:#04x})"),
+        )),
+        _ => Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "SOCKS4a proxy sent an invalid reply",
+        )),
+    }
+}
+
+/// A connection that was closed by the player while still healthy, kept open for reuse.
+struct PooledConnection {
+    host: String,
+    port: u16,
+    stream: TcpStream,
+    expires_at: Instant,
+}
+
+/// Connections kept open for reuse by later connections to the same host and port.
+#[derive(Clone, Default)]
+struct ConnectionPool(Rc<RefCell<Vec<PooledConnection>>>);
+
+impl ConnectionPool {
+    fn put(&self, host: String, port: u16, stream: TcpStream, idle_timeout: Duration) {
+        self.0.borrow_mut().push(PooledConnection {
+            host,
+            port,
+            stream,
+            expires_at: Instant::now() + idle_timeout,
+        });
+    }
+
+    /// Takes a pooled connection to the given host and port, if one is still usable.
+    fn take(&self, host: &str, port: u16) -> Option<TcpStream> {
+        self.remove_expired();
+
+        let mut connections = self.0.borrow_mut();
+        while let Some(index) = connections
+            .iter()
+            .position(|connection| connection.host == host && connection.port == port)
+        {
+            let connection = connections.remove(index);
+            if is_reusable(&connection.stream) {
+                return Some(connection.stream);
+            }
+        }
+
+        None
+    }
+
+    /// Drops the connections that have been idle for too long, which closes them.
+    fn remove_expired(&self) {
+        let now = Instant::now();
+        self.0
+            .borrow_mut()
+            .retain(|connection| connection.expires_at > now);
+    }
+}
+
+/// Checks that a pooled connection wasn't closed by the peer, and has no unread data left over.
+fn is_reusable(stream: &TcpStream) -> bool {
+    let socket = socket2::SockRef::from(stream);
+    if !matches!(socket.take_error(), Ok(None)) {
+        return false;
+    }
+
+    //NOTE: The stream is non-blocking, so this returns right away if there's nothing to read.
+    let mut buffer = [std::mem::MaybeUninit::uninit(); 1];
+    match socket.peek(&mut buffer) {
+        Err(e) => e.kind() == ErrorKind::WouldBlock,
+        // The peer either closed its write half, or sent data nobody is going to read.
+        Ok(_) => false,
+    }
+}
+
+/// Moves data between a connected stream and the player, until either side closes the connection.
+///
+/// Returns `true` if the player closed the connection after all writes went through, so the
+/// stream is still healthy and could be reused.
+async fn serve_socket(
+    stream: TcpStream,
+    retry_timeouts: bool,
+    read_chunk_size: usize,
+    queued_bytes: QueuedBytes,
+    handle: SocketHandle,
+    receiver: Receiver<SocketCommand>,
+    sender: Sender<SocketAction>,
+) -> bool {
+    //NOTE: We clone the sender here as we cant share it between async tasks.
+    let sender2 = sender.clone();
+    //NOTE: This handle is used to change socket options after the stream has been split.
+    let control = stream.clone();
+    let (mut read, mut write) = stream.split();
+    //NOTE: The write half handles all commands, so it forwards read pauses over this channel.
+    let (paused_sender, paused_receiver) = async_channel::unbounded::<bool>();
+
+    let read = std::pin::pin!(async move {
+        let mut paused = false;
+        //NOTE: A read returns as soon as any data is available, so short chunks aren't held back.
+        let mut buffer = vec![0; read_chunk_size];
+
+        loop {
+            // While paused, the OS buffer fills up and applies backpressure to the peer.
+            if paused {
+                paused = paused_receiver.recv().await.unwrap_or(false);
+                continue;
+            }
+
+            // NOTE: A pause may arrive while we wait for data, so we race the two.
+            let result = async { Err(paused_receiver.recv().await.unwrap_or(false)) }
+                .or(async { Ok(read.read(&mut buffer).await) })
+                .await;
+
+            let result = match result {
+                Ok(result) => result,
+                Err(new_paused) => {
+                    paused = new_paused;
+                    continue;
+                }
+            };
+
+            match result {
+                Err(e) if e.kind() == ErrorKind::TimedOut && retry_timeouts => {} // try again later.
+                Ok(0) => {
+                    sender
+                        .try_send(SocketAction::Close(handle, CloseReason::Graceful))
+                        .expect("working channel send");
+                    drop(read);
+                    break;
+                }
+                Err(_) => {
+                    sender
+                        .try_send(SocketAction::Close(handle, CloseReason::Reset))
+                        .expect("working channel send");
+                    drop(read);
+                    break;
+                }
+                Ok(read) => {
+                    sender
+                        .try_send(SocketAction::Data(handle, buffer[..read].to_vec()))
+                        .expect("working channel send");
+                }
+            };
+        }
+    });
+
+    let write = std::pin::pin!(async move {
+        let mut pending_write = WriteQueue::default();
+        // NOTE: Once a shutdown is requested, we finish the pending write and
+        //       shut down the write half, while the read half keeps going.
+        let mut shutdown_requested = false;
+        let mut write_shut_down = false;
+        let mut abort_requested = false;
+        // NOTE: A flush is acknowledged once the pending write is empty, which covers
+        //       all data that was sent before it.
+        let mut flush_requested = false;
+
+        let handle_command = |command: SocketCommand,
+                              pending_write: &mut WriteQueue,
+                              shutdown_requested: &mut bool,
+                              abort_requested: &mut bool,
+                              flush_requested: &mut bool| {
+            match command {
+                SocketCommand::Send(_) | SocketCommand::SendPriority(_) if *shutdown_requested => {
+                    warn!("Tried to send data after the write half was shut down");
+                }
+                SocketCommand::Send(val) => pending_write.push(val, false),
+                SocketCommand::SendPriority(val) => pending_write.push(val, true),
+                SocketCommand::SetNoDelay(no_delay) => {
+                    if let Err(e) = control.set_nodelay(no_delay) {
+                        warn!("Failed to set TCP_NODELAY on socket: {}", e);
+                    }
+                }
+                SocketCommand::ShutdownWrite => *shutdown_requested = true,
+                SocketCommand::PauseReading => {
+                    let _ = paused_sender.try_send(true);
+                }
+                SocketCommand::ResumeReading => {
+                    let _ = paused_sender.try_send(false);
+                }
+                SocketCommand::Abort => *abort_requested = true,
+                SocketCommand::Flush => *flush_requested = true,
+            }
+        };
+
+        loop {
+            let close_connection = loop {
+                match receiver.try_recv() {
+                    Ok(command) => {
+                        handle_command(
+                            command,
+                            &mut pending_write,
+                            &mut shutdown_requested,
+                            &mut abort_requested,
+                            &mut flush_requested,
+                        );
+                    }
+                    Err(TryRecvError::Empty) => break false,
+                    Err(TryRecvError::Closed) => {
+                        //NOTE: Channel sender has been dropped.
+                        //      This means we have to close the connection,
+                        //      but not here, as we might have a pending write.
+                        break true;
+                    }
+                }
+            };
+
+            if abort_requested {
+                // NOTE: A zero linger timeout makes the OS send a RST when the socket is closed.
+                if let Err(e) = socket2::SockRef::from(&control).set_linger(Some(Duration::ZERO)) {
+                    warn!("Failed to reset socket: {}", e);
+                }
+                // NOTE: Pending writes are dropped, and the connection is reset
+                //       once the stream itself is dropped.
+                drop(write);
+                return false;
+            }
+
+            if flush_requested && pending_write.is_empty() {
+                if write.flush().await.is_err() {
+                    sender2
+                        .try_send(SocketAction::Close(handle, CloseReason::Reset))
+                        .expect("working channel send");
+                    drop(write);
+                    return false;
+                }
+
+                sender2
+                    .try_send(SocketAction::Flushed(handle))
+                    .expect("working channel send");
+                flush_requested = false;
+            }
+
+            if !pending_write.is_empty() {
+                match write.write(pending_write.next_chunk()).await {
+                    Err(e) if e.kind() == ErrorKind::TimedOut && retry_timeouts => {} // try again later.
+                    Err(_) => {
+                        sender2
+                            .try_send(SocketAction::Close(handle, CloseReason::Reset))
+                            .expect("working channel send");
+                        drop(write);
+                        return false;
+                    }
+                    Ok(written) => {
+                        pending_write.consume(written);
+                        queued_bytes.remove(written as u64);
+
+                        sender2
+                            .try_send(SocketAction::Sent(handle, written as u64))
+                            .expect("working channel send");
+                        sender2
+                            .try_send(SocketAction::OutputProgress(
+                                handle,
+                                pending_write.len() as u64,
+                            ))
+                            .expect("working channel send");
+
+                        if pending_write.is_empty() {
+                            if write.flush().await.is_err() {
+                                sender2
+                                    .try_send(SocketAction::Close(handle, CloseReason::Reset))
+                                    .expect("working channel send");
+                                drop(write);
+                                return false;
+                            }
+
+                            sender2
+                                .try_send(SocketAction::WriteDrained(handle))
+                                .expect("working channel send");
+                        }
+                    }
+                }
+            } else if shutdown_requested && !write_shut_down {
+                if let Err(e) = control.shutdown(std::net::Shutdown::Write) {
+                    warn!("Failed to shut down the write half of socket: {}", e);
+                }
+                write_shut_down = true;
+            } else if close_connection {
+                drop(write);
+                return !write_shut_down;
+            } else {
+                // Receiver is empty and there's no pending data,
+                // we may block here and wait for new data.
+                match receiver.recv().await {
+                    Ok(command) => {
+                        handle_command(
+                            command,
+                            &mut pending_write,
+                            &mut shutdown_requested,
+                            &mut abort_requested,
+                            &mut flush_requested,
+                        );
+                    }
+                    Err(_) => {
+                        // Ignore the error here, it will be
+                        // reported again in try_recv.
+                    }
+                }
+            }
+        }
+    });
+
+    //NOTE: If one future exits, this will take the other one down too.
+    match select(read, write).await {
+        // The peer closed the connection, or reading from it failed.
+        Either::Left(_) => false,
+        Either::Right((healthy, _)) => healthy,
+    }
+}
+
 pub trait NavigatorInterface: Clone + 'static {
     fn confirm_website_navigation(&self, url: &Url) -> bool;
 
@@ -40,6 +581,38 @@ pub trait NavigatorInterface: Clone + 'static {
     ) -> impl std::future::Future<Output = bool> + Send;
 }
 
+/// Resolves socket hosts before the OS resolver is asked.
+///
+/// Returning `None` falls back to the OS resolver, while returning no addresses fails the
+/// connection with [`ConnectionState::DnsError`].
+pub type SocketResolver = Rc<dyn Fn(&str) -> Option<Vec<IpAddr>>>;
+
+/// A proxy that socket connections are made through.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SocketProxy {
+    /// A SOCKS4a proxy, which resolves the hosts it connects to itself.
+    Socks4a {
+        host: String,
+        port: u16,
+        user_id: String,
+    },
+}
+
+impl SocketProxy {
+    /// Parses a proxy URL like `socks4a://user@proxy.example.com:1080`, returning `None` for
+    /// schemes that sockets can't be proxied with.
+    pub fn from_url(url: &Url) -> Option<Self> {
+        match url.scheme() {
+            "socks4a" => Some(Self::Socks4a {
+                host: url.host_str()?.to_string(),
+                port: url.port().unwrap_or(1080),
+                user_id: url.username().to_string(),
+            }),
+            _ => None,
+        }
+    }
+}
+
 /// Implementation of `NavigatorBackend` for non-web environments that can call
 /// out to a web browser.
 pub struct ExternalNavigatorBackend<F: FutureSpawner, I: NavigatorInterface> {
@@ -63,6 +636,12 @@ pub struct ExternalNavigatorBackend<F: FutureSpawner, I: NavigatorInterface> {
     content: Rc<PlayingContent>,
 
     interface: I,
+
+    socket_resolver: Option<SocketResolver>,
+
+    socket_proxy: Option<SocketProxy>,
+
+    connection_pool: ConnectionPool,
 }
 
 impl<F: FutureSpawner, I: NavigatorInterface> ExternalNavigatorBackend<F, I> {
@@ -102,7 +681,11 @@ impl<F: FutureSpawner, I: NavigatorInterface> ExternalNavigatorBackend<F, I> {
             builder = builder.cookie_provider(cookie_store)
         }
 
-        if let Some(proxy) = proxy {
+        let socket_proxy = proxy.as_ref().and_then(SocketProxy::from_url);
+        if socket_proxy.is_some() {
+            //NOTE: reqwest can't talk to SOCKS4a proxies, so requests are made directly.
+            tracing::info!("Only sockets are connected through the SOCKS4a proxy");
+        } else if let Some(proxy) = proxy {
             match Proxy::all(proxy.clone()) {
                 Ok(proxy) => {
                     builder = builder.proxy(proxy);
@@ -131,8 +714,24 @@ impl<F: FutureSpawner, I: NavigatorInterface> ExternalNavigatorBackend<F, I> {
             socket_mode,
             content,
             interface,
+            socket_resolver: None,
+            socket_proxy,
+            connection_pool: ConnectionPool::default(),
         }
     }
+
+    /// Sets a resolver that socket hosts are looked up with before falling back to the OS resolver.
+    pub fn set_socket_resolver(&mut self, resolver: Option<SocketResolver>) {
+        self.socket_resolver = resolver;
+    }
+
+    /// Sets a proxy that sockets connect through, instead of the one derived from the proxy URL.
+    ///
+    /// The socket resolver and the addresses cached by the player are only used for the proxy
+    /// itself, as the proxy resolves the hosts movies connect to.
+    pub fn set_socket_proxy(&mut self, proxy: Option<SocketProxy>) {
+        self.socket_proxy = proxy;
+    }
 }
 
 impl<F: FutureSpawner, I: NavigatorInterface> NavigatorBackend for ExternalNavigatorBackend<F, I> {
@@ -318,22 +917,40 @@ impl<F: FutureSpawner, I: NavigatorInterface> NavigatorBackend for ExternalNavig
         host: String,
         port: u16,
         timeout: Duration,
+        options: SocketOptions,
         handle: SocketHandle,
-        receiver: Receiver<Vec<u8>>,
+        receiver: Receiver<SocketCommand>,
         sender: Sender<SocketAction>,
     ) {
         let addr = format!("{}:{}", host, port);
+        let resolver = self.socket_resolver.clone();
         let is_allowed = self.socket_allowed.contains(&addr);
+        let socket_allowed = self.socket_allowed.clone();
         let socket_mode = self.socket_mode;
         let interface = self.interface.clone();
+        let connection_pool = self.connection_pool.clone();
+        let socket_proxy = self.socket_proxy.clone();
 
         let future = Box::pin(async move {
+            let tcp_user_timeout = options.tcp_user_timeout;
+            let pool_idle_timeout = options.pool_idle_timeout;
+            let keepalive = options.keepalive;
+            let bind_device = options.bind_device;
+            let no_delay = options.no_delay;
+            let queued_bytes = options.queued_bytes.clone();
+            let read_chunk_size = options
+                .read_chunk_size
+                .map_or(DEFAULT_READ_CHUNK_SIZE, NonZeroUsize::get);
+
             match (is_allowed, socket_mode) {
                 (false, SocketMode::Allow) | (true, _) => {} // the process is allowed to continue. just dont do anything.
                 (false, SocketMode::Deny) => {
-                    // Just fail the connection.
+                    // Just deny the connection.
                     sender
-                        .try_send(SocketAction::Connect(handle, ConnectionState::Failed))
+                        .try_send(SocketAction::Connect(
+                            handle,
+                            ConnectionState::SecurityDenied,
+                        ))
                         .expect("working channel send");
 
                     tracing::warn!(
@@ -346,9 +963,12 @@ impl<F: FutureSpawner, I: NavigatorInterface> NavigatorBackend for ExternalNavig
                     let attempt_sandbox_connect = interface.confirm_socket(&host, port).await;
 
                     if !attempt_sandbox_connect {
-                        // fail the connection.
+                        // deny the connection.
                         sender
-                            .try_send(SocketAction::Connect(handle, ConnectionState::Failed))
+                            .try_send(SocketAction::Connect(
+                                handle,
+                                ConnectionState::SecurityDenied,
+                            ))
                             .expect("working channel send");
 
                         return Ok(());
@@ -356,120 +976,177 @@ impl<F: FutureSpawner, I: NavigatorInterface> NavigatorBackend for ExternalNavig
                 }
             }
 
-            let host2 = host.clone();
+            let mut connected = connection_pool.take(&host, port).map(|stream| {
+                tracing::debug!("Reusing a pooled connection to {}:{}", host, port);
+                (stream, host.clone(), port)
+            });
+
+            //NOTE: A reused connection skips connecting altogether.
+            let mut candidates = vec![];
+            if connected.is_none() {
+                candidates.push((host, port, options.resolved_addresses));
+                candidates.extend(
+                    options
+                        .fallback_hosts
+                        .into_iter()
+                        .map(|(host, port)| (host, port, None)),
+                );
+            }
+            let attempts = candidates.len();
+            let deadline = Instant::now() + timeout;
+            let mut last_error = None;
+
+            for (index, (host, port, ips)) in candidates.into_iter().enumerate() {
+                if index > 0 {
+                    let allowed = socket_allowed.contains(&format!("{}:{}", host, port))
+                        || match socket_mode {
+                            SocketMode::Allow => true,
+                            SocketMode::Deny => false,
+                            SocketMode::Ask => interface.confirm_socket(&host, port).await,
+                        };
+
+                    if !allowed {
+                        warn!(
+                            "Skipping fallback host {}:{}, as it is not allowed",
+                            host, port
+                        );
+                        continue;
+                    }
+                }
+
+                //NOTE: Every host gets an equal share of the time that is left,
+                //      so a host that doesn't answer can't use up the time of the ones after it.
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                let attempt_timeout = remaining / (attempts - index) as u32;
+                let timeout = async {
+                    Timer::after(attempt_timeout).await;
+                    Result::<TcpStream, _>::Err((
+                        ConnectionState::TimedOut,
+                        io::Error::new(ErrorKind::TimedOut, "connection timed out"),
+                    ))
+                };
 
-            let timeout = async {
-                Timer::after(timeout).await;
-                Result::<TcpStream, io::Error>::Err(io::Error::new(ErrorKind::TimedOut, ""))
-            };
+                //NOTE: The player caches resolved addresses for the given host, not for fallbacks.
+                let resolved = (index == 0).then_some((handle, &sender));
+                let connect = async {
+                    let Some(SocketProxy::Socks4a {
+                        host: proxy_host,
+                        port: proxy_port,
+                        user_id,
+                    }) = &socket_proxy
+                    else {
+                        return connect_tcp(
+                            &host,
+                            port,
+                            ips,
+                            resolver.as_ref(),
+                            resolved,
+                            bind_device.as_deref(),
+                            attempt_timeout,
+                        )
+                        .await;
+                    };
 
-            let stream = match TcpStream::connect((host, port)).or(timeout).await {
-                Err(e) if e.kind() == ErrorKind::TimedOut => {
-                    warn!("Connection to {}:{} timed out", host2, port);
-                    sender
-                        .try_send(SocketAction::Connect(handle, ConnectionState::TimedOut))
-                        .expect("working channel send");
-                    return Ok(());
+                    let mut stream = connect_tcp(
+                        proxy_host,
+                        *proxy_port,
+                        None,
+                        resolver.as_ref(),
+                        None,
+                        bind_device.as_deref(),
+                        attempt_timeout,
+                    )
+                    .await?;
+                    socks4a_connect(&mut stream, &host, port, user_id)
+                        .await
+                        .map_err(|e| (ConnectionState::Failed(Some(e.to_string())), e))?;
+                    Ok(stream)
+                };
+                match connect.or(timeout).await {
+                    Ok(stream) => {
+                        connected = Some((stream, host, port));
+                        break;
+                    }
+                    Err((ConnectionState::TimedOut, err)) => {
+                        warn!("Connection to {}:{} timed out", host, port);
+                        last_error = Some((ConnectionState::TimedOut, err));
+                    }
+                    Err((state, err)) => {
+                        warn!("Failed to connect to {}:{}, error: {}", host, port, err);
+                        last_error = Some((state, err));
+                    }
                 }
-                Ok(stream) => {
+            }
+
+            let (stream, host, port) = match connected {
+                Some((stream, host, port)) => {
+                    if let Ok(local_address) = stream.local_addr() {
+                        sender
+                            .try_send(SocketAction::LocalAddress(handle, local_address))
+                            .expect("working channel send");
+                    }
+                    //NOTE: Through a proxy, the peer is the proxy and not the host the movie asked for.
+                    if let Some(remote_address) =
+                        stream.peer_addr().ok().filter(|_| socket_proxy.is_none())
+                    {
+                        sender
+                            .try_send(SocketAction::RemoteAddress(handle, remote_address))
+                            .expect("working channel send");
+                    }
                     sender
                         .try_send(SocketAction::Connect(handle, ConnectionState::Connected))
                         .expect("working channel send");
 
-                    stream
+                    (stream, host, port)
                 }
-                Err(err) => {
-                    warn!("Failed to connect to {}:{}, error: {}", host2, port, err);
+                None => {
+                    let state = match last_error {
+                        Some((state, _)) if attempts == 1 => state,
+                        //NOTE: Once fallbacks were tried, no single reason explains the failure.
+                        Some((_, err)) => ConnectionState::Failed(Some(err.to_string())),
+                        None => ConnectionState::Failed(None),
+                    };
                     sender
-                        .try_send(SocketAction::Connect(handle, ConnectionState::Failed))
+                        .try_send(SocketAction::Connect(handle, state))
                         .expect("working channel send");
                     return Ok(());
                 }
             };
 
-            let sender = sender;
-            //NOTE: We clone the sender here as we cant share it between async tasks.
-            let sender2 = sender.clone();
-            let (mut read, mut write) = stream.split();
-
-            let read = std::pin::pin!(async move {
-                loop {
-                    let mut buffer = [0; 4096];
-
-                    match read.read(&mut buffer).await {
-                        Err(e) if e.kind() == ErrorKind::TimedOut => {} // try again later.
-                        Err(_) | Ok(0) => {
-                            sender
-                                .try_send(SocketAction::Close(handle))
-                                .expect("working channel send");
-                            drop(read);
-                            break;
-                        }
-                        Ok(read) => {
-                            let buffer = buffer.into_iter().take(read).collect::<Vec<_>>();
-
-                            sender
-                                .try_send(SocketAction::Data(handle, buffer))
-                                .expect("working channel send");
-                        }
-                    };
-                }
-            });
-
-            let write = std::pin::pin!(async move {
-                let mut pending_write = vec![];
-
-                loop {
-                    let close_connection = loop {
-                        match receiver.try_recv() {
-                            Ok(val) => {
-                                pending_write.extend(val);
-                            }
-                            Err(TryRecvError::Empty) => break false,
-                            Err(TryRecvError::Closed) => {
-                                //NOTE: Channel sender has been dropped.
-                                //      This means we have to close the connection,
-                                //      but not here, as we might have a pending write.
-                                break true;
-                            }
-                        }
-                    };
-
-                    if !pending_write.is_empty() {
-                        match write.write(&pending_write).await {
-                            Err(e) if e.kind() == ErrorKind::TimedOut => {} // try again later.
-                            Err(_) => {
-                                sender2
-                                    .try_send(SocketAction::Close(handle))
-                                    .expect("working channel send");
-                                drop(write);
-                                return;
-                            }
-                            Ok(written) => {
-                                let _ = pending_write.drain(..written);
-                            }
-                        }
-                    } else if close_connection {
-                        drop(write);
-                        return;
-                    } else {
-                        // Receiver is empty and there's no pending data,
-                        // we may block here and wait for new data.
-                        match receiver.recv().await {
-                            Ok(val) => {
-                                pending_write.extend(val);
-                            }
-                            Err(_) => {
-                                // Ignore the error here, it will be
-                                // reported again in try_recv.
-                            }
-                        }
-                    }
+            if let Some(tcp_user_timeout) = tcp_user_timeout {
+                set_tcp_user_timeout(&stream, tcp_user_timeout);
+            }
+            if let Some(keepalive) = keepalive {
+                set_keepalive(&stream, keepalive);
+            }
+            if no_delay {
+                if let Err(e) = stream.set_nodelay(true) {
+                    warn!("Failed to set TCP_NODELAY on socket: {}", e);
                 }
-            });
-
-            //NOTE: If one future exits, this will take the other one down too.
-            select(read, write).await;
+            }
+            //NOTE: With a TCP user timeout or keepalive, timing out means the peer is dead,
+            //      so it is reported as a reset instead of being retried.
+            let retry_timeouts = tcp_user_timeout.is_none() && keepalive.is_none();
+            let pooled_stream = stream.clone();
+
+            let healthy = serve_socket(
+                stream,
+                retry_timeouts,
+                read_chunk_size,
+                queued_bytes,
+                handle,
+                receiver,
+                sender,
+            )
+            .await;
+
+            if let Some(idle_timeout) = pool_idle_timeout.filter(|_| healthy) {
+                connection_pool.put(host, port, pooled_stream, idle_timeout);
+
+                // Make sure the connection is closed once it expires, even if nobody asks for it.
+                Timer::after(idle_timeout).await;
+                connection_pool.remove_expired();
+            }
 
             Ok(())
         });
@@ -482,7 +1159,10 @@ impl<F: FutureSpawner, I: NavigatorInterface> NavigatorBackend for ExternalNavig
 #[allow(clippy::unwrap_used)]
 mod tests {
     use async_net::TcpListener;
-    use ruffle_core::socket::SocketAction::{Close, Connect, Data};
+    use ruffle_core::socket::SocketAction::{
+        Close, Connect, Data, Flushed, LocalAddress, OutputProgress, RemoteAddress, Sent,
+        WriteDrained,
+    };
     use std::net::SocketAddr;
     use std::str::FromStr;
     use tokio::task;
@@ -547,11 +1227,26 @@ mod tests {
             // no more actions
         };
         ($receiver:expr; $action:expr, $($more:expr,)*) => {
-            assert_eq!($receiver.recv().or(async_timeout!()).await.expect("receive action"), $action);
+            assert_eq!(next_socket_action(&$receiver).await, $action);
             assert_next_socket_actions!($receiver; $($more,)*);
         };
     }
 
+    /// Receives the next action, skipping reported addresses, as their ports differ on every run.
+    async fn next_socket_action(receiver: &Receiver<SocketAction>) -> SocketAction {
+        loop {
+            match receiver
+                .recv()
+                .or(async_timeout!())
+                .await
+                .expect("receive action")
+            {
+                LocalAddress(_, _) | RemoteAddress(_, _) => continue,
+                action => return action,
+            }
+        }
+    }
+
     fn new_test_backend(socket_allow: bool) -> ExternalNavigatorBackend<TestFutureSpawner, ()> {
         let url = Url::parse("https://example.com/path/").unwrap();
         ExternalNavigatorBackend::new(
@@ -588,16 +1283,33 @@ mod tests {
         addr: SocketAddr,
         timeout: Duration,
         socket_allow: bool,
-    ) -> (Sender<Vec<u8>>, Receiver<SocketAction>) {
-        let mut backend = new_test_backend(socket_allow);
+    ) -> (Sender<SocketCommand>, Receiver<SocketAction>) {
+        connect_test_socket_host(addr.ip().to_string(), addr.port(), timeout, socket_allow)
+    }
+
+    fn connect_test_socket_host(
+        host: String,
+        port: u16,
+        timeout: Duration,
+        socket_allow: bool,
+    ) -> (Sender<SocketCommand>, Receiver<SocketAction>) {
+        connect_test_socket_backend(new_test_backend(socket_allow), host, port, timeout)
+    }
 
+    fn connect_test_socket_backend(
+        mut backend: ExternalNavigatorBackend<TestFutureSpawner, ()>,
+        host: String,
+        port: u16,
+        timeout: Duration,
+    ) -> (Sender<SocketCommand>, Receiver<SocketAction>) {
         let (write, receiver) = async_channel::unbounded();
         let (sender, read) = async_channel::unbounded();
 
         backend.connect_socket(
-            addr.ip().to_string(),
-            addr.port(),
+            host,
+            port,
             timeout,
+            SocketOptions::default(),
             dummy_handle!(),
             receiver,
             sender,
@@ -628,9 +1340,9 @@ mod tests {
         String::from_utf8(buffer).unwrap()
     }
 
-    async fn write_client(client_write: &Sender<Vec<u8>>, data: &str) {
+    async fn write_client(client_write: &Sender<SocketCommand>, data: &str) {
         client_write
-            .send(data.as_bytes().to_vec())
+            .send(SocketCommand::Send(data.as_bytes().to_vec()))
             .or(async_timeout!())
             .await
             .expect("client write");
@@ -657,6 +1369,69 @@ mod tests {
         );
     }
 
+    #[macro_rules_attribute::apply(async_test)]
+    async fn test_socket_local_address() {
+        let (accept_task, addr) = start_test_server().await;
+        let (_client_write, client_read) = connect_test_socket(addr, TIMEOUT, true);
+        let server_socket = accept_task.await.unwrap();
+
+        match client_read.recv().or(async_timeout!()).await {
+            Ok(LocalAddress(handle, local_address)) => {
+                assert_eq!(handle, dummy_handle!());
+                assert_eq!(local_address, server_socket.peer_addr().unwrap());
+            }
+            action => panic!("expected a local address, got {:?}", action),
+        }
+        assert_next_socket_actions!(
+            client_read;
+            Connect(dummy_handle!(), ConnectionState::Connected),
+        );
+    }
+
+    #[macro_rules_attribute::apply(async_test)]
+    async fn test_socket_fallback_host() {
+        // Nothing listens on this port once the listener is dropped.
+        let closed_addr = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let (accept_task, addr) = start_test_server().await;
+        let (_client_write, receiver) = async_channel::unbounded();
+        let (sender, client_read) = async_channel::unbounded();
+        new_test_backend(true).connect_socket(
+            closed_addr.ip().to_string(),
+            closed_addr.port(),
+            TIMEOUT,
+            SocketOptions {
+                fallback_hosts: vec![(addr.ip().to_string(), addr.port())],
+                ..Default::default()
+            },
+            dummy_handle!(),
+            receiver,
+            sender,
+        );
+
+        let _server_socket = accept_task.await.unwrap();
+        let mut remote_address = None;
+        loop {
+            match client_read
+                .recv()
+                .or(async_timeout!())
+                .await
+                .expect("receive action")
+            {
+                LocalAddress(_, _) => {}
+                RemoteAddress(_, address) => remote_address = Some(address),
+                action => {
+                    assert_eq!(action, Connect(dummy_handle!(), ConnectionState::Connected));
+                    break;
+                }
+            }
+        }
+        assert_eq!(remote_address, Some(addr));
+    }
+
     #[macro_rules_attribute::apply(async_test)]
     async fn test_socket_deny() {
         let (_accept_task, addr) = start_test_server().await;
@@ -664,7 +1439,7 @@ mod tests {
 
         assert_next_socket_actions!(
             client_read;
-            Connect(dummy_handle!(), ConnectionState::Failed),
+            Connect(dummy_handle!(), ConnectionState::SecurityDenied),
         );
     }
 
@@ -673,9 +1448,28 @@ mod tests {
         let addr = SocketAddr::from_str("[100::]:42").expect("black hole address");
         let (_client_write, client_read) = connect_test_socket(addr, TIMEOUT, true);
 
+        let action = client_read
+            .recv()
+            .or(async_timeout!())
+            .await
+            .expect("receive action");
+        assert!(
+            matches!(
+                action,
+                Connect(_, ConnectionState::Failed(Some(ref error))) if !error.is_empty()
+            ),
+            "unexpected action {action:?}"
+        );
+    }
+
+    #[macro_rules_attribute::apply(async_test)]
+    async fn test_socket_dns_error() {
+        let (_client_write, client_read) =
+            connect_test_socket_host("invalid host name".to_string(), 42, TIMEOUT, true);
+
         assert_next_socket_actions!(
             client_read;
-            Connect(dummy_handle!(), ConnectionState::Failed),
+            Connect(dummy_handle!(), ConnectionState::DnsError),
         );
     }
 
@@ -694,7 +1488,7 @@ mod tests {
 
         assert_next_socket_actions!(
             client_read;
-            Close(dummy_handle!()),
+            Close(dummy_handle!(), CloseReason::Graceful),
         );
     }
 
@@ -714,6 +1508,87 @@ mod tests {
         assert_eq!(read_server(&mut server_socket).await, "");
     }
 
+    #[macro_rules_attribute::apply(async_test)]
+    async fn test_socket_read_chunk_size() {
+        let (accept_task, addr) = start_test_server().await;
+        let (_client_write, receiver) = async_channel::unbounded();
+        let (sender, client_read) = async_channel::unbounded();
+        new_test_backend(true).connect_socket(
+            addr.ip().to_string(),
+            addr.port(),
+            TIMEOUT,
+            SocketOptions {
+                read_chunk_size: NonZeroUsize::new(4),
+                ..Default::default()
+            },
+            dummy_handle!(),
+            receiver,
+            sender,
+        );
+
+        let mut server_socket = accept_task.await.unwrap();
+        assert_next_socket_actions!(
+            client_read;
+            Connect(dummy_handle!(), ConnectionState::Connected),
+        );
+
+        write_server(&mut server_socket, "Hello World!!").await;
+
+        let mut received = vec![];
+        while received.len() < 13 {
+            match client_read
+                .recv()
+                .or(async_timeout!())
+                .await
+                .expect("receive action")
+            {
+                Data(_, data) => {
+                    assert!(data.len() <= 4, "chunk of {} bytes", data.len());
+                    received.extend(data);
+                }
+                action => panic!("unexpected action {action:?}"),
+            }
+        }
+        assert_eq!(received, b"Hello World!!");
+    }
+
+    #[macro_rules_attribute::apply(async_test)]
+    async fn test_socket_queued_bytes() {
+        let (accept_task, addr) = start_test_server().await;
+        let (client_write, receiver) = async_channel::unbounded();
+        let (sender, client_read) = async_channel::unbounded();
+        let queued_bytes = QueuedBytes::default();
+        new_test_backend(true).connect_socket(
+            addr.ip().to_string(),
+            addr.port(),
+            TIMEOUT,
+            SocketOptions {
+                queued_bytes: queued_bytes.clone(),
+                ..Default::default()
+            },
+            dummy_handle!(),
+            receiver,
+            sender,
+        );
+
+        let mut server_socket = accept_task.await.unwrap();
+        assert_next_socket_actions!(
+            client_read;
+            Connect(dummy_handle!(), ConnectionState::Connected),
+        );
+
+        queued_bytes.add(5);
+        write_client(&client_write, "Hello").await;
+        assert_next_socket_actions!(
+            client_read;
+            Sent(dummy_handle!(), 5),
+            OutputProgress(dummy_handle!(), 0),
+            WriteDrained(dummy_handle!()),
+        );
+        assert_eq!(queued_bytes.get(), 0);
+        assert_eq!(read_server(&mut server_socket).await, "Hello");
+    }
+
     #[macro_rules_attribute::apply(async_test)]
     async fn test_socket_basic_communication() {
         let (accept_task, addr) = start_test_server().await;
@@ -735,14 +1610,31 @@ mod tests {
         write_client(&client_write, "Hello from client").await;
 
         assert_eq!(read_server(&mut server_socket).await, "Hello from client");
+        assert_next_socket_actions!(
+            client_read;
+            Sent(dummy_handle!(), 17),
+            OutputProgress(dummy_handle!(), 0),
+            WriteDrained(dummy_handle!()),
+        );
 
         write_server(&mut server_socket, "from server 2").await;
         write_client(&client_write, "from client 2").await;
 
-        assert_next_socket_actions!(
-            client_read;
-            Data(dummy_handle!(), "from server 2".as_bytes().to_vec()),
-        );
+        // NOTE: The order of these depends on which half of the socket gets to run first.
+        let mut actions = vec![];
+        for _ in 0..4 {
+            actions.push(
+                client_read
+                    .recv()
+                    .or(async_timeout!())
+                    .await
+                    .expect("receive action"),
+            );
+        }
+        assert!(actions.contains(&Data(dummy_handle!(), "from server 2".as_bytes().to_vec())));
+        assert!(actions.contains(&Sent(dummy_handle!(), 13)));
+        assert!(actions.contains(&OutputProgress(dummy_handle!(), 0)));
+        assert!(actions.contains(&WriteDrained(dummy_handle!())));
         assert_eq!(read_server(&mut server_socket).await, "from client 2");
     }
 
@@ -762,4 +1654,319 @@ mod tests {
 
         assert_eq!(read_server(&mut server_socket).await, "Sending some data");
     }
+
+    #[macro_rules_attribute::apply(async_test)]
+    async fn test_socket_flush_is_acknowledged() {
+        let (accept_task, addr) = start_test_server().await;
+        let (client_write, client_read) = connect_test_socket(addr, TIMEOUT, true);
+
+        let mut server_socket = accept_task.await.unwrap();
+        assert_next_socket_actions!(
+            client_read;
+            Connect(dummy_handle!(), ConnectionState::Connected),
+        );
+
+        write_client(&client_write, "Hello").await;
+        client_write
+            .send(SocketCommand::Flush)
+            .or(async_timeout!())
+            .await
+            .expect("client write");
+        assert_next_socket_actions!(
+            client_read;
+            Sent(dummy_handle!(), 5),
+            OutputProgress(dummy_handle!(), 0),
+            WriteDrained(dummy_handle!()),
+            Flushed(dummy_handle!()),
+        );
+        assert_eq!(read_server(&mut server_socket).await, "Hello");
+
+        // With nothing pending, the flush is acknowledged right away.
+        client_write
+            .send(SocketCommand::Flush)
+            .or(async_timeout!())
+            .await
+            .expect("client write");
+        assert_next_socket_actions!(
+            client_read;
+            Flushed(dummy_handle!()),
+        );
+    }
+
+    #[macro_rules_attribute::apply(async_test)]
+    async fn test_socket_abort() {
+        let (accept_task, addr) = start_test_server().await;
+        let (client_write, client_read) = connect_test_socket(addr, TIMEOUT, true);
+
+        let mut server_socket = accept_task.await.unwrap();
+        assert_next_socket_actions!(
+            client_read;
+            Connect(dummy_handle!(), ConnectionState::Connected),
+        );
+
+        client_write
+            .send(SocketCommand::Abort)
+            .or(async_timeout!())
+            .await
+            .expect("client write");
+
+        let mut buffer = [0; 4096];
+        let read = server_socket.read(&mut buffer).or(async_timeout!()).await;
+        assert_eq!(read.map_err(|e| e.kind()), Err(ErrorKind::ConnectionReset));
+    }
+
+    fn connect_pooled_test_socket(
+        backend: &mut ExternalNavigatorBackend<TestFutureSpawner, ()>,
+        addr: SocketAddr,
+    ) -> (Sender<SocketCommand>, Receiver<SocketAction>) {
+        let (write, receiver) = async_channel::unbounded();
+        let (sender, read) = async_channel::unbounded();
+
+        backend.connect_socket(
+            addr.ip().to_string(),
+            addr.port(),
+            TIMEOUT,
+            SocketOptions {
+                pool_idle_timeout: Some(TIMEOUT),
+                ..Default::default()
+            },
+            dummy_handle!(),
+            receiver,
+            sender,
+        );
+
+        (write, read)
+    }
+
+    #[macro_rules_attribute::apply(async_test)]
+    async fn test_socket_connection_pool() {
+        let (accept_task, addr) = start_test_server().await;
+        let mut backend = new_test_backend(true);
+        let (client_write, client_read) = connect_pooled_test_socket(&mut backend, addr);
+
+        let mut server_socket = accept_task.await.unwrap();
+        assert_next_socket_actions!(
+            client_read;
+            Connect(dummy_handle!(), ConnectionState::Connected),
+        );
+
+        write_client(&client_write, "first").await;
+        drop(client_write);
+        assert_eq!(read_server(&mut server_socket).await, "first");
+        // Once the connection isn't served anymore, it is put into the pool.
+        while client_read.recv().or(async_timeout!()).await.is_ok() {}
+
+        // NOTE: The test server only accepts one connection, so this has to reuse the first one.
+        let (client_write, client_read) = connect_pooled_test_socket(&mut backend, addr);
+        assert_next_socket_actions!(
+            client_read;
+            Connect(dummy_handle!(), ConnectionState::Connected),
+        );
+
+        write_client(&client_write, "second").await;
+        assert_eq!(read_server(&mut server_socket).await, "second");
+    }
+
+    #[macro_rules_attribute::apply(async_test)]
+    async fn test_socket_connection_pool_skips_closed_connections() {
+        let (accept_task, addr) = start_test_server().await;
+        let mut backend = new_test_backend(true);
+        let (client_write, client_read) = connect_pooled_test_socket(&mut backend, addr);
+
+        let server_socket = accept_task.await.unwrap();
+        assert_next_socket_actions!(
+            client_read;
+            Connect(dummy_handle!(), ConnectionState::Connected),
+        );
+
+        drop(client_write);
+        while client_read.recv().or(async_timeout!()).await.is_ok() {}
+        drop(server_socket);
+
+        // The pooled connection was closed by the peer, so a new one is attempted and refused.
+        let (_client_write, client_read) = connect_pooled_test_socket(&mut backend, addr);
+        let action = next_socket_action(&client_read).await;
+        assert!(
+            matches!(action, Connect(_, ConnectionState::Failed(_))),
+            "unexpected action: {:?}",
+            action
+        );
+    }
+
+    #[macro_rules_attribute::apply(async_test)]
+    async fn test_socket_priority_lane() {
+        let (accept_task, addr) = start_test_server().await;
+        let (client_write, client_read) = connect_test_socket(addr, TIMEOUT, true);
+
+        let mut server_socket = accept_task.await.unwrap();
+        assert_next_socket_actions!(
+            client_read;
+            Connect(dummy_handle!(), ConnectionState::Connected),
+        );
+
+        // NOTE: The write task only runs once we yield, so it sees all of these at once
+        //       and the priority message goes out before the queued bulk messages.
+        write_client(&client_write, "bulk 1;").await;
+        write_client(&client_write, "bulk 2;").await;
+        client_write
+            .send(SocketCommand::SendPriority(b"control;".to_vec()))
+            .or(async_timeout!())
+            .await
+            .expect("client write");
+
+        let expected = "control;bulk 1;bulk 2;";
+        let mut received = String::new();
+        while received.len() < expected.len() {
+            received.push_str(&read_server(&mut server_socket).await);
+        }
+        assert_eq!(received, expected);
+    }
+
+    #[macro_rules_attribute::apply(async_test)]
+    async fn test_socket_pause_reading() {
+        let (accept_task, addr) = start_test_server().await;
+        let (client_write, client_read) = connect_test_socket(addr, TIMEOUT, true);
+
+        let mut server_socket = accept_task.await.unwrap();
+        assert_next_socket_actions!(
+            client_read;
+            Connect(dummy_handle!(), ConnectionState::Connected),
+        );
+
+        client_write
+            .send(SocketCommand::PauseReading)
+            .or(async_timeout!())
+            .await
+            .expect("client write");
+        // Give the pause a chance to reach the read half before any data arrives.
+        Timer::after(Duration::from_millis(50)).await;
+        write_server(&mut server_socket, "held back").await;
+
+        let early = client_read
+            .recv()
+            .or(async {
+                Timer::after(Duration::from_millis(200)).await;
+                Err(async_channel::RecvError)
+            })
+            .await;
+        assert!(early.is_err(), "no data should be read while paused");
+
+        client_write
+            .send(SocketCommand::ResumeReading)
+            .or(async_timeout!())
+            .await
+            .expect("client write");
+        assert_next_socket_actions!(
+            client_read;
+            Data(dummy_handle!(), "held back".as_bytes().to_vec()),
+        );
+    }
+
+    #[macro_rules_attribute::apply(async_test)]
+    async fn test_socket_custom_resolver() {
+        let (accept_task, addr) = start_test_server().await;
+
+        let mut backend = new_test_backend(true);
+        backend.set_socket_resolver(Some(Rc::new(move |host: &str| match host {
+            "game.test" => Some(vec![addr.ip()]),
+            _ => None,
+        })));
+        let (_client_write, client_read) =
+            connect_test_socket_backend(backend, "game.test".to_string(), addr.port(), TIMEOUT);
+
+        let _server_socket = accept_task.await.unwrap();
+        assert_next_socket_actions!(
+            client_read;
+            Connect(dummy_handle!(), ConnectionState::Connected),
+        );
+
+        let mut backend = new_test_backend(true);
+        backend.set_socket_resolver(Some(Rc::new(move |host: &str| match host {
+            "nowhere.test" => Some(vec![]),
+            _ => None,
+        })));
+        let (_client_write, client_read) =
+            connect_test_socket_backend(backend, "nowhere.test".to_string(), 42, TIMEOUT);
+
+        assert_next_socket_actions!(
+            client_read;
+            Connect(dummy_handle!(), ConnectionState::DnsError),
+        );
+    }
+
+    async fn connect_socks4a_test_socket(
+        reply: [u8; 8],
+    ) -> (Sender<SocketCommand>, Receiver<SocketAction>, TcpStream) {
+        let (accept_task, addr) = start_test_server().await;
+        let mut backend = new_test_backend(true);
+        backend.set_socket_proxy(Some(SocketProxy::Socks4a {
+            host: addr.ip().to_string(),
+            port: addr.port(),
+            user_id: "ruffle".to_string(),
+        }));
+        let (client_write, client_read) =
+            connect_test_socket_backend(backend, "example.invalid".to_string(), 8080, TIMEOUT);
+
+        let mut proxy_socket = accept_task.await.unwrap();
+        let mut request = [0; 31];
+        proxy_socket
+            .read_exact(&mut request)
+            .or(async_timeout!())
+            .await
+            .unwrap();
+        assert_eq!(
+            &request,
+            b"\x04\x01\x1f\x90\0\0\0\x01ruffle\0example.invalid\0"
+        );
+        proxy_socket
+            .write_all(&reply)
+            .or(async_timeout!())
+            .await
+            .unwrap();
+
+        (client_write, client_read, proxy_socket)
+    }
+
+    #[macro_rules_attribute::apply(async_test)]
+    async fn test_socket_socks4a_proxy() {
+        let (client_write, client_read, mut proxy_socket) =
+            connect_socks4a_test_socket([0, 0x5A, 0, 0, 0, 0, 0, 0]).await;
+        assert_next_socket_actions!(
+            client_read;
+            Connect(dummy_handle!(), ConnectionState::Connected),
+        );
+
+        write_client(&client_write, "Hello through the proxy").await;
+        assert_eq!(
+            read_server(&mut proxy_socket).await,
+            "Hello through the proxy"
+        );
+    }
+
+    #[macro_rules_attribute::apply(async_test)]
+    async fn test_socket_socks4a_proxy_refused() {
+        let (_client_write, client_read, _proxy_socket) =
+            connect_socks4a_test_socket([0, 0x5B, 0, 0, 0, 0, 0, 0]).await;
+        let action = next_socket_action(&client_read).await;
+        assert!(
+            matches!(action, Connect(_, ConnectionState::Failed(Some(_)))),
+            "unexpected action {action:?}"
+        );
+    }
+
+    #[test]
+    fn test_socket_proxy_from_url() {
+        assert_eq!(
+            SocketProxy::from_url(&Url::parse("socks4a://ruffle@proxy.example.com").unwrap()),
+            Some(SocketProxy::Socks4a {
+                host: "proxy.example.com".to_string(),
+                port: 1080,
+                user_id: "ruffle".to_string(),
+            })
+        );
+        assert_eq!(
+            SocketProxy::from_url(&Url::parse("http://proxy.example.com:8080").unwrap()),
+            None
+        );
+    }
 }
@@ -1,6 +1,6 @@
 use crate::backends::TestLogBackend;
 use crate::util::read_bytes;
-use async_channel::{Receiver, Sender};
+use async_channel::{Receiver, RecvError, Sender};
 use percent_encoding::percent_decode_str;
 use ruffle_core::backend::log::LogBackend;
 use ruffle_core::backend::navigator::{
@@ -9,7 +9,10 @@ use ruffle_core::backend::navigator::{
 };
 use ruffle_core::indexmap::IndexMap;
 use ruffle_core::loader::Error;
-use ruffle_core::socket::{ConnectionState, SocketAction, SocketHandle};
+use ruffle_core::socket::{
+    CloseReason, ConnectionState, QueuedBytes, SocketAction, SocketCommand, SocketHandle,
+    SocketOptions,
+};
 use ruffle_socket_format::SocketEvent;
 use std::borrow::Cow;
 use std::time::Duration;
@@ -269,8 +272,9 @@ impl NavigatorBackend for TestNavigatorBackend {
         host: String,
         port: u16,
         _timeout: Duration,
+        options: SocketOptions,
         handle: SocketHandle,
-        receiver: Receiver<Vec<u8>>,
+        receiver: Receiver<SocketCommand>,
         sender: Sender<SocketAction>,
     ) {
         if let Some(log) = &self.log {
@@ -279,6 +283,7 @@ impl NavigatorBackend for TestNavigatorBackend {
         }
 
         if let Some(events) = self.socket_events.clone() {
+            let queued_bytes = options.queued_bytes;
             self.spawn_future(Box::pin(async move {
                 sender
                     .try_send(SocketAction::Connect(handle, ConnectionState::Connected))
@@ -288,17 +293,17 @@ impl NavigatorBackend for TestNavigatorBackend {
                     match event {
                         SocketEvent::Disconnect => {
                             sender
-                                .try_send(SocketAction::Close(handle))
+                                .try_send(SocketAction::Close(handle, CloseReason::Graceful))
                                 .expect("working channel send");
                         }
                         SocketEvent::WaitForDisconnect => {
-                            match receiver.recv().await {
+                            match recv_data(handle, &receiver, &sender, &queued_bytes).await {
                                 Err(_) => break,
                                 Ok(_) => panic!("Expected client to disconnect, data was sent instead"),
                             }
                         }
                         SocketEvent::Receive { expected } => {
-                            match receiver.recv().await {
+                            match recv_data(handle, &receiver, &sender, &queued_bytes).await {
                                 Ok(val) => {
                                     if expected != val {
                                         panic!("Received data did not match expected data\nExpected: {:?}\nActual: {:?}", expected, val);
@@ -318,3 +323,26 @@ impl NavigatorBackend for TestNavigatorBackend {
         }
     }
 }
+
+/// Receives the next data sent by the movie, skipping commands that only configure the connection.
+///
+/// Flushes are acknowledged right away, as scripted data is never written anywhere.
+async fn recv_data(
+    handle: SocketHandle,
+    receiver: &Receiver<SocketCommand>,
+    sender: &Sender<SocketAction>,
+    queued_bytes: &QueuedBytes,
+) -> Result<Vec<u8>, RecvError> {
+    loop {
+        match receiver.recv().await? {
+            SocketCommand::Send(data) | SocketCommand::SendPriority(data) => {
+                queued_bytes.remove(data.len() as u64);
+                return Ok(data);
+            }
+            SocketCommand::Flush => sender
+                .try_send(SocketAction::Flushed(handle))
+                .expect("working channel send"),
+            _ => {}
+        }
+    }
+}
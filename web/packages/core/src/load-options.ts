@@ -268,16 +268,19 @@ export enum NetworkingAccessMode {
  */
 export interface SocketProxy {
     /**
-     * Host used by the SWF.
+     * Host used by the SWF, or `*` to match any host.
      */
     host: string;
     /**
-     * Port used by the SWF.
+     * Port used by the SWF, or 0 to match any port.
      */
     port: number;
 
     /**
      * The proxy URL to use when SWF file tries to connect to the specified host and port.
+     *
+     * `{host}` and `{port}` are replaced with the host and port the SWF connects to,
+     * so a single relay can forward connections to any server.
      */
     proxyUrl: string;
 }
@@ -616,6 +619,10 @@ export interface BaseLoadOptions {
      * a matching SocketProxy object in this array and use it to establish a WebSocket connection,
      * through which all communication is tunneled through.
      *
+     * The proxy is expected to connect to the server once the WebSocket is opened, and to close the
+     * WebSocket once the server closes the connection. Data is exchanged as binary messages, which
+     * carry the raw bytes of the connection; text messages are ignored.
+     *
      * When none are found, Ruffle will fail the connection gracefully.
      * When multiple matching SocketProxy objects exist, the first one is used.
      *
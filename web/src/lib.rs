@@ -319,6 +319,26 @@ pub struct SocketProxy {
     proxy_url: String,
 }
 
+impl SocketProxy {
+    /// Whether connections to the given host and port go through this proxy.
+    ///
+    /// A host of `*` matches every host, and a port of 0 matches every port, so a single relay
+    /// can serve all connections.
+    fn matches(&self, host: &str, port: u16) -> bool {
+        (self.host == "*" || self.host == host) && (self.port == 0 || self.port == port)
+    }
+
+    /// The URL of the WebSocket to tunnel a connection to the given host and port through.
+    ///
+    /// `{host}` and `{port}` in the proxy URL are replaced, so a relay knows where to connect to.
+    fn url_for(&self, host: &str, port: u16) -> String {
+        let host: String = url::form_urlencoded::byte_serialize(host.as_bytes()).collect();
+        self.proxy_url
+            .replace("{host}", &host)
+            .replace("{port}", &port.to_string())
+    }
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct Config {
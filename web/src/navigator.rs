@@ -12,7 +12,9 @@ use ruffle_core::backend::navigator::{
 use ruffle_core::config::NetworkingAccessMode;
 use ruffle_core::indexmap::IndexMap;
 use ruffle_core::loader::Error;
-use ruffle_core::socket::{ConnectionState, SocketAction, SocketHandle};
+use ruffle_core::socket::{
+    CloseReason, ConnectionState, SocketAction, SocketCommand, SocketHandle, SocketOptions,
+};
 use std::borrow::Cow;
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -376,36 +378,44 @@ impl NavigatorBackend for WebNavigatorBackend {
         port: u16,
         // NOTE: WebSocket does not allow specifying a timeout, so this goes unused.
         _timeout: Duration,
+        // NOTE: Connections are made by the WebSocket proxy, so most options go unused.
+        options: SocketOptions,
         handle: SocketHandle,
-        receiver: Receiver<Vec<u8>>,
+        receiver: Receiver<SocketCommand>,
         sender: Sender<SocketAction>,
     ) {
-        let Some(proxy) = self
-            .socket_proxies
-            .iter()
-            .find(|x| x.host == host && x.port == port)
-        else {
+        let Some(proxy) = self.socket_proxies.iter().find(|x| x.matches(&host, port)) else {
             tracing::warn!("Missing WebSocket proxy for host {}, port {}", host, port);
+            let error = format!("no WebSocket proxy is configured for {host}:{port}");
             sender
-                .try_send(SocketAction::Connect(handle, ConnectionState::Failed))
+                .try_send(SocketAction::Connect(
+                    handle,
+                    ConnectionState::Failed(Some(error)),
+                ))
                 .expect("working channel send");
             return;
         };
 
-        tracing::info!("Connecting to {}", proxy.proxy_url);
+        let proxy_url = proxy.url_for(&host, port);
+        tracing::info!("Connecting to {}", proxy_url);
 
-        let ws = match WebSocket::open(&proxy.proxy_url) {
+        let ws = match WebSocket::open(&proxy_url) {
             Ok(x) => x,
             Err(e) => {
                 tracing::error!("Failed to create WebSocket, reason {:?}", e);
+                let error = format!("failed to open WebSocket proxy {proxy_url}: {e}");
                 sender
-                    .try_send(SocketAction::Connect(handle, ConnectionState::Failed))
+                    .try_send(SocketAction::Connect(
+                        handle,
+                        ConnectionState::Failed(Some(error)),
+                    ))
                     .expect("working channel send");
                 return;
             }
         };
 
         let (mut ws_write, mut ws_read) = ws.split();
+        let queued_bytes = options.queued_bytes;
         sender
             .try_send(SocketAction::Connect(handle, ConnectionState::Connected))
             .expect("working channel send");
@@ -421,20 +431,46 @@ impl NavigatorBackend for WebNavigatorBackend {
                         Ok(_) => tracing::warn!("Server sent an unexpected text message"),
                         Err(_) => {
                             sender
-                                .try_send(SocketAction::Close(handle))
+                                .try_send(SocketAction::Close(handle, CloseReason::Reset))
                                 .expect("working channel send");
                             break;
                         }
                     },
                     // Handle outgoing messages.
-                    Either::Right((Ok(msg), _)) => {
-                        if let Err(e) = ws_write.send(Message::Bytes(msg)).await {
+                    // NOTE: WebSocket messages can't be reordered once sent, so priority data
+                    //       is sent like any other data.
+                    Either::Right((
+                        Ok(SocketCommand::Send(msg) | SocketCommand::SendPriority(msg)),
+                        _,
+                    )) => {
+                        let len = msg.len() as u64;
+                        let result = ws_write.send(Message::Bytes(msg)).await;
+                        // NOTE: Once handed to the browser, the data is out of our hands.
+                        queued_bytes.remove(len);
+                        if let Err(e) = result {
                             tracing::warn!("Failed to send message to WebSocket {}", e);
                             sender
-                                .try_send(SocketAction::Close(handle))
+                                .try_send(SocketAction::Close(handle, CloseReason::Reset))
                                 .expect("working channel send");
                         }
                     }
+                    // NOTE: WebSocket does not expose TCP options, so these are ignored.
+                    Either::Right((Ok(SocketCommand::SetNoDelay(_)), _)) => {}
+                    // NOTE: WebSocket messages are pushed to us, so reading can't be paused.
+                    Either::Right((
+                        Ok(SocketCommand::PauseReading | SocketCommand::ResumeReading),
+                        _,
+                    )) => {}
+                    Either::Right((Ok(SocketCommand::ShutdownWrite), _)) => {
+                        tracing::warn!("WebSocket does not support shutting down the write half");
+                    }
+                    // NOTE: WebSocket can't reset the underlying connection, so it is just closed.
+                    Either::Right((Ok(SocketCommand::Abort), _)) => break,
+                    // NOTE: Sent messages were already handed to the browser, which is as far
+                    //       as we can tell.
+                    Either::Right((Ok(SocketCommand::Flush), _)) => sender
+                        .try_send(SocketAction::Flushed(handle))
+                        .expect("working channel send"),
                     // The connection was closed.
                     _ => break,
                 };